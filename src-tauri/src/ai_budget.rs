@@ -0,0 +1,382 @@
+// ai_budget.rs - Token estimates and context-window budgeting for chats
+
+use serde::Serialize;
+use std::sync::OnceLock;
+use tauri::AppHandle;
+use tiktoken_rs::tokenizer::{get_tokenizer, Tokenizer};
+use tiktoken_rs::CoreBPE;
+
+use crate::ai_provider;
+use crate::ai_settings::{get_ai_settings, AIProvider, AISettings};
+use crate::ai_stream::ChatMessage;
+
+/// Event reporting a chat's `ChatBudget` just before it is sent
+pub const BUDGET_EVENT: &str = "ai-chat-budget";
+
+// Role and separator tokens each message costs on top of its content
+const MESSAGE_OVERHEAD: usize = 4;
+// Tokens that prime the reply
+const REPLY_PRIMING: usize = 3;
+// Claude and local models tokenize differently from OpenAI's encodings, so
+// counts made with cl100k get some headroom
+const FOREIGN_TOKENIZER_MARGIN: f32 = 1.15;
+// Context cut shorter than this is dropped rather than kept as a fragment
+const MIN_CONTEXT_TOKENS: usize = 64;
+const SUMMARY_MAX_TOKENS: u32 = 500;
+const TRUNCATION_MARK: &str = "\n…[truncated]";
+
+// Ollama only uses what `num_ctx` allows, whatever the model supports
+const OLLAMA_DEFAULT_CONTEXT: usize = 4096;
+const DEFAULT_CONTEXT: usize = 8192;
+
+// Model name prefixes, most specific first
+const CONTEXT_WINDOWS: &[(&str, usize)] = &[
+    ("gpt-5", 400_000),
+    ("gpt-4.1", 1_047_576),
+    ("gpt-4o", 128_000),
+    ("chatgpt-4o", 128_000),
+    ("gpt-4-turbo", 128_000),
+    ("gpt-4-0125", 128_000),
+    ("gpt-4-1106", 128_000),
+    ("gpt-4-32k", 32_768),
+    ("gpt-4", 8_192),
+    ("gpt-3.5-turbo", 16_385),
+    ("o1-mini", 128_000),
+    ("o1", 200_000),
+    ("o3", 200_000),
+    ("o4", 200_000),
+    ("claude-2", 100_000),
+    ("claude", 200_000),
+    ("llama3.1", 128_000),
+    ("llama3.2", 128_000),
+    ("llama3.3", 128_000),
+    ("llama3", 8_192),
+    ("llama2", 4_096),
+    ("mistral-large", 128_000),
+    ("mistral", 32_768),
+    ("mixtral", 32_768),
+    ("qwen", 32_768),
+    ("gemma", 8_192),
+    ("phi3", 4_096),
+    ("deepseek", 64_000),
+];
+
+/// Tokens `settings.model` accepts per request: the configured value if
+/// there is one, else what the app knows about the model.
+pub fn context_window(settings: &AISettings) -> usize {
+    if let Some(window) = settings.context_window.filter(|w| *w > 0) {
+        return window as usize;
+    }
+    if settings.provider == AIProvider::Ollama {
+        return OLLAMA_DEFAULT_CONTEXT;
+    }
+    // Hosted names can carry an org prefix, e.g. `openai/gpt-4o`
+    let model = settings.model.to_lowercase();
+    let model = model.rsplit('/').next().unwrap_or(&model);
+    CONTEXT_WINDOWS
+        .iter()
+        .find(|(prefix, _)| model.starts_with(prefix))
+        .map_or(DEFAULT_CONTEXT, |(_, window)| *window)
+}
+
+/// Counts tokens with the model's own encoding where it's known, and with
+/// cl100k plus a margin where it isn't.
+pub struct TokenCounter {
+    bpe: &'static CoreBPE,
+    margin: f32,
+}
+
+impl TokenCounter {
+    pub fn for_settings(settings: &AISettings) -> Self {
+        let known = match settings.provider {
+            AIProvider::OpenAI => get_tokenizer(&settings.model),
+            _ => None,
+        };
+        // Building an encoding takes a while, so each is built once
+        static O200K: OnceLock<CoreBPE> = OnceLock::new();
+        static CL100K: OnceLock<CoreBPE> = OnceLock::new();
+        let bpe = match known {
+            Some(Tokenizer::O200kBase) => {
+                O200K.get_or_init(|| tiktoken_rs::o200k_base().expect("bundled o200k encoding"))
+            }
+            _ => CL100K.get_or_init(|| tiktoken_rs::cl100k_base().expect("bundled cl100k encoding")),
+        };
+        let margin = if known.is_some() { 1.0 } else { FOREIGN_TOKENIZER_MARGIN };
+        Self { bpe, margin }
+    }
+
+    pub fn text(&self, text: &str) -> usize {
+        let tokens = self.bpe.encode_ordinary(text).len();
+        (tokens as f32 * self.margin).ceil() as usize
+    }
+
+    pub fn message(&self, message: &ChatMessage) -> usize {
+        self.text(&message.content) + MESSAGE_OVERHEAD
+    }
+
+    // `text` cut to about `limit` tokens, marked as truncated
+    fn truncate(&self, text: &str, limit: usize) -> String {
+        let mut keep = text.chars().count();
+        loop {
+            let mut cut: String = text.chars().take(keep).collect();
+            cut.push_str(TRUNCATION_MARK);
+            let tokens = self.text(&cut);
+            if tokens <= limit || keep == 0 {
+                return cut;
+            }
+            // Shrink in proportion to the overshoot, always by at least a char
+            let ratio = limit as f32 / tokens as f32;
+            keep = ((keep as f32 * ratio) as usize).min(keep - 1);
+        }
+    }
+}
+
+/// How a chat's tokens are spent against the model's context window.
+#[derive(Debug, Clone, Serialize)]
+pub struct ChatBudget {
+    pub model: String,
+    pub context_window: usize,
+    /// Held back for the reply (`max_tokens`)
+    pub reply_reserve: usize,
+    /// What the prompt may use
+    pub prompt_limit: usize,
+    /// The leading system prompt
+    pub instructions: usize,
+    /// Other system messages: context notes, excerpts, summaries
+    pub context: usize,
+    /// Earlier turns
+    pub history: usize,
+    /// The message being answered
+    pub latest: usize,
+    pub total: usize,
+    /// Earlier turns left out to make room
+    pub dropped_messages: usize,
+    /// Earlier turns replaced by a summary
+    pub summarized_messages: usize,
+    /// Context messages cut short or left out
+    pub trimmed_context: usize,
+    pub fits: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChatBudgetEvent {
+    pub request_id: String,
+    #[serde(flatten)]
+    pub budget: ChatBudget,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Part {
+    Instructions,
+    Context,
+    History,
+    Latest,
+}
+
+fn part(messages: &[ChatMessage], index: usize) -> Part {
+    if index + 1 == messages.len() {
+        Part::Latest
+    } else if messages[index].role == "system" {
+        if index == 0 {
+            Part::Instructions
+        } else {
+            Part::Context
+        }
+    } else {
+        Part::History
+    }
+}
+
+/// Messages trimmed to fit a budget, with what was left out.
+pub struct FittedChat {
+    pub messages: Vec<ChatMessage>,
+    pub budget: ChatBudget,
+    /// Earlier turns dropped to make room, oldest first
+    pub dropped: Vec<ChatMessage>,
+}
+
+pub struct Budgeter {
+    counter: TokenCounter,
+    model: String,
+    context_window: usize,
+    reply_reserve: usize,
+}
+
+impl Budgeter {
+    pub fn new(settings: &AISettings) -> Self {
+        let context_window = context_window(settings);
+        Self {
+            counter: TokenCounter::for_settings(settings),
+            model: settings.model.clone(),
+            context_window,
+            // A `max_tokens` beyond the window would leave the prompt nothing
+            reply_reserve: (settings.max_tokens as usize).min(context_window / 2),
+        }
+    }
+
+    pub fn prompt_limit(&self) -> usize {
+        self.context_window - self.reply_reserve
+    }
+
+    /// Tokens left for added context once the system prompt, the latest
+    /// message and the reply are provided for.
+    pub fn context_room(&self, messages: &[ChatMessage]) -> usize {
+        let fixed: usize = (0..messages.len())
+            .filter(|&i| matches!(part(messages, i), Part::Instructions | Part::Latest))
+            .map(|i| self.counter.message(&messages[i]))
+            .sum();
+        self.prompt_limit().saturating_sub(fixed + REPLY_PRIMING)
+    }
+
+    pub fn message_tokens(&self, message: &ChatMessage) -> usize {
+        self.counter.message(message)
+    }
+
+    /// Fit `messages` into the prompt limit: earlier turns go first, oldest
+    /// first, then context is cut down, largest first. The system prompt and
+    /// the latest message are never touched; if they alone don't fit, the
+    /// budget says so with `fits: false`.
+    pub fn fit(&self, messages: Vec<ChatMessage>) -> FittedChat {
+        let limit = self.prompt_limit();
+        let parts: Vec<Part> = (0..messages.len()).map(|i| part(&messages, i)).collect();
+        let mut tokens: Vec<usize> = messages.iter().map(|m| self.counter.message(m)).collect();
+        let mut keep = vec![true; messages.len()];
+        let mut contents: Vec<Option<String>> = vec![None; messages.len()];
+        let total = |tokens: &[usize], keep: &[bool]| -> usize {
+            tokens.iter().zip(keep).filter(|(_, k)| **k).map(|(t, _)| t).sum::<usize>() + REPLY_PRIMING
+        };
+
+        let mut dropped_messages = 0;
+        let history: Vec<usize> = (0..messages.len()).filter(|&i| parts[i] == Part::History).collect();
+        let mut next = 0;
+        while total(&tokens, &keep) > limit && next < history.len() {
+            keep[history[next]] = false;
+            dropped_messages += 1;
+            next += 1;
+        }
+        // What's left of the history should open with the user's turn
+        while next < history.len() && messages[history[next]].role != "user" && dropped_messages > 0 {
+            keep[history[next]] = false;
+            dropped_messages += 1;
+            next += 1;
+        }
+
+        let mut trimmed_context = 0;
+        let mut context: Vec<usize> = (0..messages.len()).filter(|&i| parts[i] == Part::Context).collect();
+        context.sort_by_key(|&i| std::cmp::Reverse(tokens[i]));
+        for i in context {
+            let excess = total(&tokens, &keep).saturating_sub(limit);
+            if excess == 0 {
+                break;
+            }
+            trimmed_context += 1;
+            let room = tokens[i].saturating_sub(excess + MESSAGE_OVERHEAD);
+            if room < MIN_CONTEXT_TOKENS {
+                keep[i] = false;
+                continue;
+            }
+            let cut = self.counter.truncate(&messages[i].content, room);
+            tokens[i] = self.counter.text(&cut) + MESSAGE_OVERHEAD;
+            contents[i] = Some(cut);
+        }
+
+        let sum = |part: Part| -> usize {
+            (0..messages.len())
+                .filter(|&i| keep[i] && parts[i] == part)
+                .map(|i| tokens[i])
+                .sum()
+        };
+        let budget = ChatBudget {
+            model: self.model.clone(),
+            context_window: self.context_window,
+            reply_reserve: self.reply_reserve,
+            prompt_limit: limit,
+            instructions: sum(Part::Instructions),
+            context: sum(Part::Context),
+            history: sum(Part::History),
+            latest: sum(Part::Latest),
+            total: total(&tokens, &keep),
+            dropped_messages,
+            summarized_messages: 0,
+            trimmed_context,
+            fits: total(&tokens, &keep) <= limit,
+        };
+
+        let mut kept = Vec::new();
+        let mut dropped = Vec::new();
+        for (i, (mut message, content)) in messages.into_iter().zip(contents).enumerate() {
+            if let Some(content) = content {
+                message.content = content;
+            }
+            if keep[i] {
+                kept.push(message);
+            } else if parts[i] == Part::History {
+                dropped.push(message);
+            }
+        }
+        FittedChat {
+            messages: kept,
+            budget,
+            dropped,
+        }
+    }
+
+    /// Like `fit`, but earlier turns that don't fit are summarized by the
+    /// model into a system message instead of being dropped outright.
+    pub async fn fit_summarized(&self, settings: &AISettings, messages: Vec<ChatMessage>) -> Result<FittedChat, String> {
+        let fitted = self.fit(messages);
+        if fitted.dropped.is_empty() || !fitted.budget.fits {
+            return Ok(fitted);
+        }
+
+        let mut transcript = String::new();
+        for message in &fitted.dropped {
+            transcript.push_str(&format!("{}: {}\n\n", message.role, message.content));
+        }
+        let room = self.prompt_limit().saturating_sub(MESSAGE_OVERHEAD * 2 + REPLY_PRIMING + 64);
+        let transcript = self.counter.truncate(&transcript, room);
+
+        let mut summary_settings = settings.clone();
+        summary_settings.max_tokens = summary_settings.max_tokens.min(SUMMARY_MAX_TOKENS);
+        let provider = ai_provider::provider_for(summary_settings);
+        let summary = provider
+            .chat(&[
+                ChatMessage {
+                    role: "system".to_string(),
+                    content: "Summarize this conversation in a few sentences, keeping facts, decisions \
+                              and open questions the rest of the conversation may depend on."
+                        .to_string(),
+                },
+                ChatMessage {
+                    role: "user".to_string(),
+                    content: transcript,
+                },
+            ])
+            .await?;
+
+        // The summary stands in for the dropped turns, after the system prompt
+        let dropped_count = fitted.dropped.len();
+        let mut messages = fitted.messages;
+        let at = usize::from(messages.first().map_or(false, |m| m.role == "system"));
+        messages.insert(
+            at,
+            ChatMessage {
+                role: "system".to_string(),
+                content: format!("Summary of the earlier conversation:\n{}", summary.trim()),
+            },
+        );
+
+        let mut refitted = self.fit(messages);
+        refitted.budget.summarized_messages = dropped_count;
+        refitted.budget.dropped_messages += dropped_count;
+        Ok(refitted)
+    }
+}
+
+/// Estimate how `messages` would be budgeted for the configured model,
+/// without sending anything.
+#[tauri::command]
+pub async fn estimate_ai_chat(app: AppHandle, messages: Vec<ChatMessage>) -> Result<ChatBudget, String> {
+    let settings = get_ai_settings(app).await?.ok_or("No AI settings configured")?;
+    Ok(Budgeter::new(&settings).fit(messages).budget)
+}
@@ -0,0 +1,419 @@
+// ai_edit.rs - AI rewrites of the editor selection, returned as a reviewable diff
+
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use tauri::AppHandle;
+
+use crate::ai_stream::{self, ChatMessage, ChatMode};
+use crate::auth::VaultAction;
+
+// How much text before the selection `Continue` sees
+const CONTINUE_CONTEXT_CHARS: usize = 4000;
+// Above this many token pairs the diff is reported as one replacement
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EditAction {
+    Summarize,
+    Rewrite,
+    Continue,
+    Translate,
+    FixGrammar,
+}
+
+/// The text to edit. Anything left out comes from the editor state last
+/// reported through `update_editor_state`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct EditTarget {
+    /// Vault-relative file to read when `content` isn't given
+    pub file_path: Option<String>,
+    /// The editor buffer, which may have unsaved changes
+    pub content: Option<String>,
+    /// Start and end in UTF-16 code units, as CodeMirror reports them
+    pub selection: Option<(usize, usize)>,
+}
+
+/// The text an `EditTarget` points at.
+pub struct ResolvedTarget {
+    pub file_path: Option<PathBuf>,
+    pub text: String,
+    /// Selection start and end in UTF-16 code units
+    pub selection: Option<(usize, usize)>,
+    /// The selection's byte range in `text`
+    pub range: Option<Range<usize>>,
+}
+
+impl ResolvedTarget {
+    pub fn selected(&self) -> Option<&str> {
+        self.range.clone().map(|range| &self.text[range])
+    }
+}
+
+impl EditTarget {
+    /// Fill in what the target leaves out from the editor state and read the
+    /// text it points at.
+    pub async fn resolve(self, state: &crate::AppState) -> Result<ResolvedTarget, String> {
+        let EditTarget { file_path, content, selection } = self;
+        let editor_state = state.editor.current_state().await;
+        let (file_path, text) = match (content, file_path) {
+            (Some(content), file_path) => (file_path.map(PathBuf::from), content),
+            (None, Some(file_path)) => {
+                let vault_lock = state.vault.lock().await;
+                let vault = vault_lock.as_ref().ok_or("No vault opened")?;
+                state.auth.authorize(vault.path(), Path::new(&file_path), VaultAction::Read).await?;
+                let text = vault
+                    .read_file(Path::new(&file_path))
+                    .map_err(|e| format!("Failed to read file: {}", e))?;
+                (Some(PathBuf::from(file_path)), text)
+            }
+            (None, None) => (editor_state.current_file, editor_state.content),
+        };
+
+        let selection = selection
+            .or(editor_state.selection)
+            .map(|(from, to)| (from.min(to), from.max(to)));
+        let range = match selection {
+            Some((from, to)) => {
+                let start = utf16_to_byte(&text, from).ok_or("Selection is outside the text")?;
+                let end = utf16_to_byte(&text, to).ok_or("Selection is outside the text")?;
+                Some(start..end)
+            }
+            None => None,
+        };
+        Ok(ResolvedTarget { file_path, text, selection, range })
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct EditOptions {
+    /// Extra direction for `Rewrite`, e.g. "make it more formal"
+    pub instruction: Option<String>,
+    /// Target language for `Translate`; defaults to English
+    pub language: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffKind {
+    Equal,
+    Insert,
+    Delete,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DiffSegment {
+    pub kind: DiffKind,
+    pub text: String,
+}
+
+/// A proposed replacement for the selection. Nothing is written; the editor
+/// applies `replacement` over `from..to` if the user accepts it.
+#[derive(Debug, Serialize)]
+pub struct EditProposal {
+    pub request_id: String,
+    pub action: EditAction,
+    /// Selection start and end in UTF-16 code units, as the editor counts them
+    pub from: usize,
+    pub to: usize,
+    pub original: String,
+    pub replacement: String,
+    /// Word-level changes from `original` to `replacement`
+    pub diff: Vec<DiffSegment>,
+}
+
+// CodeMirror positions count UTF-16 code units; Rust strings index bytes
+fn utf16_to_byte(text: &str, offset: usize) -> Option<usize> {
+    let mut units = 0;
+    for (byte, c) in text.char_indices() {
+        if units == offset {
+            return Some(byte);
+        }
+        if units > offset {
+            return None;
+        }
+        units += c.len_utf16();
+    }
+    (units == offset).then_some(text.len())
+}
+
+fn instructions(action: EditAction, options: &EditOptions) -> String {
+    let task = match action {
+        EditAction::Summarize => "Summarize the text concisely, keeping its key points.".to_string(),
+        EditAction::Rewrite => match options.instruction.as_deref().filter(|i| !i.trim().is_empty()) {
+            Some(instruction) => format!("Rewrite the text as follows: {}", instruction.trim()),
+            None => "Rewrite the text to be clearer and more readable without changing its meaning.".to_string(),
+        },
+        EditAction::Continue => {
+            "Continue writing from where the text ends, matching its style and format. Reply with only the new text."
+                .to_string()
+        }
+        EditAction::Translate => format!(
+            "Translate the text into {}.",
+            options.language.as_deref().unwrap_or("English")
+        ),
+        EditAction::FixGrammar => {
+            "Fix spelling, grammar and punctuation. Change nothing else.".to_string()
+        }
+    };
+    format!(
+        "You edit text in a markdown note. {} Keep the markdown formatting. \
+         Reply with the resulting text only: no preamble, no explanation, no code fences.",
+        task
+    )
+}
+
+// Models sometimes wrap the whole reply in a code fence despite being asked not to
+fn strip_fence(reply: &str) -> &str {
+    let trimmed = reply.trim();
+    let Some(rest) = trimmed.strip_prefix("```") else {
+        return trimmed;
+    };
+    let Some(body) = rest.strip_suffix("```") else {
+        return trimmed;
+    };
+    // Drop the info string on the opening line
+    body.split_once('\n').map_or(body, |(_, body)| body).trim_end()
+}
+
+// Words and the whitespace between them, so a diff never splits a word
+fn tokens(text: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    let mut in_space = None;
+    for (i, c) in text.char_indices() {
+        let space = c.is_whitespace();
+        if in_space.map_or(false, |s| s != space) {
+            tokens.push(&text[start..i]);
+            start = i;
+        }
+        in_space = Some(space);
+    }
+    if start < text.len() {
+        tokens.push(&text[start..]);
+    }
+    tokens
+}
+
+fn push_segment(segments: &mut Vec<DiffSegment>, kind: DiffKind, text: &str) {
+    if text.is_empty() {
+        return;
+    }
+    match segments.last_mut() {
+        Some(last) if std::mem::discriminant(&last.kind) == std::mem::discriminant(&kind) => {
+            last.text.push_str(text)
+        }
+        _ => segments.push(DiffSegment {
+            kind,
+            text: text.to_string(),
+        }),
+    }
+}
+
+/// Word-level diff by longest common subsequence, after trimming the common
+/// prefix and suffix.
+pub fn diff_words(original: &str, replacement: &str) -> Vec<DiffSegment> {
+    let old = tokens(original);
+    let new = tokens(replacement);
+
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_mid = &old[prefix..old.len() - suffix];
+    let new_mid = &new[prefix..new.len() - suffix];
+
+    let mut segments = Vec::new();
+    push_segment(&mut segments, DiffKind::Equal, &old[..prefix].concat());
+
+    if old_mid.len() * new_mid.len() > MAX_DIFF_CELLS {
+        push_segment(&mut segments, DiffKind::Delete, &old_mid.concat());
+        push_segment(&mut segments, DiffKind::Insert, &new_mid.concat());
+    } else {
+        // lcs[i][j]: common subsequence length of old_mid[i..] and new_mid[j..]
+        let (n, m) = (old_mid.len(), new_mid.len());
+        let mut lcs = vec![vec![0u32; m + 1]; n + 1];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lcs[i][j] = if old_mid[i] == new_mid[j] {
+                    lcs[i + 1][j + 1] + 1
+                } else {
+                    lcs[i + 1][j].max(lcs[i][j + 1])
+                };
+            }
+        }
+
+        let (mut i, mut j) = (0, 0);
+        while i < n || j < m {
+            if i < n && j < m && old_mid[i] == new_mid[j] {
+                push_segment(&mut segments, DiffKind::Equal, old_mid[i]);
+                i += 1;
+                j += 1;
+            } else if i < n && (j == m || lcs[i + 1][j] >= lcs[i][j + 1]) {
+                push_segment(&mut segments, DiffKind::Delete, old_mid[i]);
+                i += 1;
+            } else {
+                push_segment(&mut segments, DiffKind::Insert, new_mid[j]);
+                j += 1;
+            }
+        }
+    }
+
+    push_segment(&mut segments, DiffKind::Equal, &old[old.len() - suffix..].concat());
+    segments
+}
+
+/// Run an AI edit over a selection and return the proposed change as a diff.
+/// The replacement streams as `ai-stream-chunk` events under `request_id`
+/// and can be stopped with `cancel_ai_chat`.
+#[tauri::command]
+pub async fn ai_edit_selection(
+    app: AppHandle,
+    action: EditAction,
+    target: Option<EditTarget>,
+    options: Option<EditOptions>,
+    request_id: Option<String>,
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<EditProposal, String> {
+    let target = target.unwrap_or_default().resolve(&state).await?;
+    let (Some((from, to)), Some(range)) = (target.selection, target.range) else {
+        return Err("No text selected".to_string());
+    };
+    let text = target.text;
+    let start = range.start;
+    let original = text[range].to_string();
+    if original.trim().is_empty() {
+        return Err("No text selected".to_string());
+    }
+
+    let options = options.unwrap_or_default();
+    let user_content = match action {
+        EditAction::Continue => {
+            // Give the model what leads up to the selection as well
+            let before: String = {
+                let chars: Vec<char> = text[..start].chars().collect();
+                chars[chars.len().saturating_sub(CONTINUE_CONTEXT_CHARS)..].iter().collect()
+            };
+            format!("{}{}", before, original)
+        }
+        _ => original.clone(),
+    };
+    let messages = vec![
+        ChatMessage {
+            role: "system".to_string(),
+            content: instructions(action, &options),
+        },
+        ChatMessage {
+            role: "user".to_string(),
+            content: user_content,
+        },
+    ];
+
+    let request_id = request_id.unwrap_or_else(ai_stream::new_request_id);
+    println!("AI edit {:?} on {} chars ({})", action, original.chars().count(), request_id);
+    let mode = ChatMode::Chat { stream: true, summarize_history: false, profile: None };
+    let reply = ai_stream::run_chat(&app, &state, &request_id, messages, mode).await?;
+
+    let generated = strip_fence(&reply);
+    let replacement = match action {
+        // The continuation follows the selection, separated as the model intended
+        EditAction::Continue => {
+            let leading = &reply[..reply.len() - reply.trim_start().len()];
+            let separator = if !leading.is_empty() {
+                leading
+            } else if original.ends_with(char::is_whitespace) {
+                ""
+            } else {
+                " "
+            };
+            format!("{}{}{}", original, separator, generated)
+        }
+        _ => {
+            // Keep the selection's surrounding whitespace so the edit drops in cleanly
+            let leading = &original[..original.len() - original.trim_start().len()];
+            let trailing = &original[original.trim_end().len()..];
+            format!("{}{}{}", leading, generated, trailing)
+        }
+    };
+
+    Ok(EditProposal {
+        request_id,
+        action,
+        from,
+        to,
+        diff: diff_words(&original, &replacement),
+        original,
+        replacement,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Deletions as [-text], insertions as {+text}
+    fn marked(segments: &[DiffSegment]) -> String {
+        segments
+            .iter()
+            .map(|s| match s.kind {
+                DiffKind::Equal => s.text.clone(),
+                DiffKind::Delete => format!("[-{}]", s.text),
+                DiffKind::Insert => format!("{{+{}}}", s.text),
+            })
+            .collect()
+    }
+
+    fn side(segments: &[DiffSegment], keep: impl Fn(&DiffKind) -> bool) -> String {
+        segments.iter().filter(|s| keep(&s.kind)).map(|s| s.text.as_str()).collect()
+    }
+
+    #[test]
+    fn utf16_offsets_map_to_char_boundaries() {
+        // `é` is two bytes, `😀` four bytes and two UTF-16 units
+        let text = "aé😀b";
+        assert_eq!(utf16_to_byte(text, 0), Some(0));
+        assert_eq!(utf16_to_byte(text, 1), Some(1));
+        assert_eq!(utf16_to_byte(text, 2), Some(3));
+        assert_eq!(utf16_to_byte(text, 3), None);
+        assert_eq!(utf16_to_byte(text, 4), Some(7));
+        assert_eq!(utf16_to_byte(text, 5), Some(8));
+        assert_eq!(utf16_to_byte(text, 6), None);
+        assert_eq!(utf16_to_byte("", 0), Some(0));
+    }
+
+    #[test]
+    fn diff_marks_whole_words() {
+        let diff = diff_words("The quick brown fox", "The quick red fox");
+        assert_eq!(marked(&diff), "The quick [-brown]{+red} fox");
+    }
+
+    #[test]
+    fn diff_handles_additions_and_removals_at_the_ends() {
+        assert_eq!(marked(&diff_words("one two", "zero one two three")), "{+zero }one two{+ three}");
+        assert_eq!(marked(&diff_words("one two three", "two")), "[-one ]two[- three]");
+        assert_eq!(marked(&diff_words("same", "same")), "same");
+        assert_eq!(marked(&diff_words("", "new")), "{+new}");
+    }
+
+    #[test]
+    fn diff_rebuilds_both_texts() {
+        let original = "Teh cat sat on teh mat.\n\nIt was  happy.";
+        let replacement = "The cat sat on the mat.\n\nIt was very happy!";
+        let diff = diff_words(original, replacement);
+        assert_eq!(side(&diff, |k| !matches!(k, DiffKind::Insert)), original);
+        assert_eq!(side(&diff, |k| !matches!(k, DiffKind::Delete)), replacement);
+    }
+
+    #[test]
+    fn fences_around_the_whole_reply_are_dropped() {
+        assert_eq!(strip_fence("```markdown\n# Title\ntext\n```"), "# Title\ntext");
+        assert_eq!(strip_fence("  plain text \n"), "plain text");
+        assert_eq!(strip_fence("```rust\nlet x = 1;"), "```rust\nlet x = 1;");
+    }
+}
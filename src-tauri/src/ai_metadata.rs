@@ -0,0 +1,488 @@
+// ai_metadata.rs - AI-proposed titles, summaries and tags, written to frontmatter after review
+
+use futures_util::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Emitter};
+
+use crate::ai_provider::{self, AiProvider};
+use crate::ai_settings::get_ai_settings;
+use crate::ai_stream::{self, ChatMessage};
+use crate::auth::{AuthManager, VaultAction};
+use crate::embeddings::content_hash;
+use crate::frontmatter::{self, FieldValue};
+use crate::vault::{self, relative_path, Vault};
+
+const PROPOSALS_FILE: &str = "metadata_proposals.json";
+const DEFAULT_CONCURRENCY: usize = 3;
+const MAX_CONCURRENCY: usize = 8;
+// Long notes are cut; the opening is what a title and summary depend on most
+const MAX_NOTE_CHARS: usize = 12000;
+// A title, a couple of sentences and a few tags fit well within this
+const METADATA_MAX_TOKENS: u32 = 400;
+// Existing tags offered to the model so it reuses the vault's vocabulary
+const KNOWN_TAGS_LIMIT: usize = 50;
+
+/// Event reporting each note a metadata batch finishes
+pub const METADATA_PROGRESS_EVENT: &str = "ai-metadata-progress";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetadataProposal {
+    pub path: String,
+    pub title: String,
+    pub summary: String,
+    pub tags: Vec<String>,
+    /// SHA-256 of the note the proposal was made for
+    hash: String,
+}
+
+// Proposals waiting for review, kept on disk so an interrupted batch resumes
+// where it stopped and nothing is lost before the user has looked at it
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ProposalStore {
+    proposals: BTreeMap<String, MetadataProposal>,
+}
+
+impl ProposalStore {
+    fn file(vault: &Vault) -> PathBuf {
+        vault.path().join(".aura").join(PROPOSALS_FILE)
+    }
+
+    fn load(vault: &Vault) -> Self {
+        std::fs::read_to_string(Self::file(vault))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, vault: &Vault) -> Result<(), String> {
+        let path = Self::file(vault);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize proposals: {}", e))?;
+
+        // Write to a temp file first so a crash never leaves a half-written file
+        let tmp_path = path.with_extension("json.tmp");
+        std::fs::write(&tmp_path, content)
+            .and_then(|_| std::fs::rename(&tmp_path, &path))
+            .map_err(|e| format!("Failed to save proposals: {}", e))
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MetadataProgress {
+    pub request_id: String,
+    pub path: String,
+    pub done: usize,
+    pub total: usize,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MetadataFailure {
+    pub path: String,
+    pub error: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MetadataBatchResult {
+    pub request_id: String,
+    /// Notes proposed for in this run
+    pub proposed: usize,
+    /// Notes that already had a proposal for their current content
+    pub resumed: usize,
+    pub failed: Vec<MetadataFailure>,
+    pub cancelled: bool,
+    /// Every proposal awaiting review for the requested notes
+    pub proposals: Vec<MetadataProposal>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MetadataApplyResult {
+    pub written: Vec<String>,
+    pub failed: Vec<MetadataFailure>,
+}
+
+/// What the user accepted for one note; fields left out aren't written.
+#[derive(Debug, Deserialize)]
+pub struct MetadataUpdate {
+    pub path: String,
+    pub title: Option<String>,
+    pub summary: Option<String>,
+    pub tags: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawMetadata {
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    summary: String,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+// Frontmatter tags: lowercase, no `#`, hyphens for spaces
+fn normalize_tag(tag: &str) -> String {
+    tag.trim()
+        .trim_start_matches('#')
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join("-")
+        .to_lowercase()
+}
+
+fn instructions(known_tags: &[String]) -> String {
+    let mut prompt = String::from(
+        "You write metadata for a markdown note. Reply with a JSON object only, no code fences: \
+         {\"title\": a short descriptive title, \"summary\": one or two sentences, \
+         \"tags\": three to five lowercase tags without #, using hyphens instead of spaces}.",
+    );
+    if !known_tags.is_empty() {
+        prompt.push_str(&format!(
+            " Prefer these existing tags where they fit: {}.",
+            known_tags.join(", ")
+        ));
+    }
+    prompt
+}
+
+// The JSON object in a reply, tolerating text or fences around it
+fn parse_reply(reply: &str) -> Result<RawMetadata, String> {
+    let start = reply.find('{').ok_or("The model didn't return JSON")?;
+    let end = reply.rfind('}').ok_or("The model didn't return JSON")?;
+    if end < start {
+        return Err("The model didn't return JSON".to_string());
+    }
+    serde_json::from_str(&reply[start..=end]).map_err(|e| format!("Invalid metadata JSON: {}", e))
+}
+
+async fn propose(
+    provider: &dyn AiProvider,
+    system: &str,
+    path: &str,
+    content: &str,
+    hash: String,
+) -> Result<MetadataProposal, String> {
+    let note: String = content.chars().take(MAX_NOTE_CHARS).collect();
+    let name = Path::new(path).file_stem().and_then(|s| s.to_str()).unwrap_or(path);
+    let messages = [
+        ChatMessage {
+            role: "system".to_string(),
+            content: system.to_string(),
+        },
+        ChatMessage {
+            role: "user".to_string(),
+            content: format!("File name: {}\n\n{}", name, note),
+        },
+    ];
+
+    let raw = parse_reply(&provider.chat(&messages).await?)?;
+    let mut tags: Vec<String> = Vec::new();
+    for tag in raw.tags.iter().map(|t| normalize_tag(t)) {
+        if !tag.is_empty() && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+
+    Ok(MetadataProposal {
+        path: path.to_string(),
+        title: raw.title.trim().to_string(),
+        summary: raw.summary.trim().to_string(),
+        tags,
+        hash,
+    })
+}
+
+// Vault-relative paths of the requested notes, or of every note
+fn note_paths(vault: &Vault, paths: Option<Vec<String>>) -> Result<Vec<String>, String> {
+    match paths {
+        Some(paths) => paths
+            .iter()
+            .map(|path| {
+                let relative = relative_path(path)?;
+                if !vault::is_note(&relative) {
+                    return Err(format!("Not a note: {}", path));
+                }
+                Ok(relative.to_string_lossy().to_string())
+            })
+            .collect(),
+        None => Ok(vault
+            .list_markdown_files()
+            .map_err(|e| format!("Failed to list files: {}", e))?
+            .into_iter()
+            .filter(|p| vault::is_note(p))
+            .filter_map(|p| {
+                p.strip_prefix(vault.path())
+                    .ok()
+                    .map(|r| r.to_string_lossy().to_string())
+            })
+            .collect()),
+    }
+}
+
+/// Propose a title, summary and tags for the given notes (or the whole
+/// vault), `concurrency` notes at a time. Nothing is written to the notes;
+/// proposals are kept until applied or discarded, and notes that already
+/// have a proposal for their current content are skipped, so running the
+/// same batch again resumes it. Stop a batch with `cancel_ai_chat`.
+#[tauri::command]
+pub async fn propose_note_metadata(
+    app: AppHandle,
+    paths: Option<Vec<String>>,
+    concurrency: Option<usize>,
+    request_id: Option<String>,
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<MetadataBatchResult, String> {
+    let mut settings = get_ai_settings(app.clone())
+        .await?
+        .ok_or("No AI settings configured")?;
+    settings.max_tokens = settings.max_tokens.min(METADATA_MAX_TOKENS);
+    let provider = ai_provider::provider_for(settings);
+
+    let vault = state.vault.lock().await.clone().ok_or("No vault opened")?;
+    let known_tags: Vec<String> = match state.tag_index.lock().await.as_ref() {
+        Some(index) => {
+            let notes = index.note_paths().cloned().collect();
+            let readable = state.auth.readable(vault.path(), notes).await;
+            index
+                .tag_counts(&readable.into_iter().collect())
+                .into_iter()
+                .take(KNOWN_TAGS_LIMIT)
+                .map(|t| t.tag)
+                .collect()
+        }
+        None => Vec::new(),
+    };
+    let system = instructions(&known_tags);
+
+    let requested = note_paths(&vault, paths)?;
+    let mut store = ProposalStore::load(&vault);
+    let mut failed = Vec::new();
+    let mut todo = Vec::new();
+    let mut resumed = 0;
+    for path in &requested {
+        // Proposals are only made for notes the user could apply them to
+        if let Err(e) = state.auth.authorize(vault.path(), Path::new(path), VaultAction::Write).await {
+            failed.push(MetadataFailure {
+                path: path.clone(),
+                error: e,
+            });
+            continue;
+        }
+        match vault.read_file(Path::new(path)) {
+            Ok(content) => {
+                let hash = content_hash(&content);
+                if store.proposals.get(path).map_or(false, |p| p.hash == hash) {
+                    resumed += 1;
+                } else {
+                    todo.push((path.clone(), content, hash));
+                }
+            }
+            Err(e) => failed.push(MetadataFailure {
+                path: path.clone(),
+                error: format!("Failed to read note: {}", e),
+            }),
+        }
+    }
+
+    let request_id = request_id.unwrap_or_else(ai_stream::new_request_id);
+    let limit = concurrency.unwrap_or(DEFAULT_CONCURRENCY).clamp(1, MAX_CONCURRENCY);
+    println!(
+        "📝 Proposing metadata for {} notes ({} already proposed), {} at a time",
+        todo.len(),
+        resumed,
+        limit
+    );
+
+    let mut cancel_rx = ai_stream::register_request(&state, &request_id).await?;
+    let total = todo.len();
+    let provider = provider.as_ref();
+    let system = system.as_str();
+    let mut results = stream::iter(todo)
+        .map(|(path, content, hash)| async move {
+            let result = propose(provider, system, &path, &content, hash).await;
+            (path, result)
+        })
+        .buffer_unordered(limit);
+
+    let mut proposed = 0;
+    let mut cancelled = false;
+    let mut done = 0;
+    loop {
+        let next = tokio::select! {
+            next = results.next() => next,
+            _ = &mut cancel_rx => {
+                cancelled = true;
+                None
+            }
+        };
+        let Some((path, result)) = next else { break };
+        done += 1;
+
+        let error = match result {
+            Ok(proposal) => {
+                store.proposals.insert(path.clone(), proposal);
+                proposed += 1;
+                // Saved as each note finishes so an interruption loses at most the notes in flight
+                if let Err(e) = store.save(&vault) {
+                    println!("⚠️ {}", e);
+                }
+                None
+            }
+            Err(e) => {
+                failed.push(MetadataFailure {
+                    path: path.clone(),
+                    error: e.clone(),
+                });
+                Some(e)
+            }
+        };
+
+        let progress = MetadataProgress {
+            request_id: request_id.clone(),
+            path,
+            done,
+            total,
+            error,
+        };
+        if let Err(e) = app.emit(METADATA_PROGRESS_EVENT, progress) {
+            println!("⚠️ Failed to emit metadata progress: {}", e);
+        }
+    }
+    drop(results);
+    state.ai_requests.lock().await.remove(&request_id);
+
+    println!(
+        "📝 Metadata batch {}: {} proposed, {} failed{}",
+        request_id,
+        proposed,
+        failed.len(),
+        if cancelled { ", cancelled" } else { "" }
+    );
+
+    let proposals = requested
+        .iter()
+        .filter_map(|path| store.proposals.get(path).cloned())
+        .collect();
+    Ok(MetadataBatchResult {
+        request_id,
+        proposed,
+        resumed,
+        failed,
+        cancelled,
+        proposals,
+    })
+}
+
+/// Proposals from earlier batches that haven't been applied or discarded.
+#[tauri::command]
+pub async fn list_metadata_proposals(
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<Vec<MetadataProposal>, String> {
+    let vault = state.vault.lock().await.clone().ok_or("No vault opened")?;
+    let mut proposals = Vec::new();
+    for proposal in ProposalStore::load(&vault).proposals.into_values() {
+        // Proposals quote the note's title and summary
+        if state.auth.allows(vault.path(), Path::new(&proposal.path), VaultAction::Read).await {
+            proposals.push(proposal);
+        }
+    }
+    Ok(proposals)
+}
+
+/// Write reviewed metadata into each note's frontmatter. Suggested tags are
+/// added to the note's existing ones. A note that can't be written doesn't
+/// stop the others; it's reported in `failed` and keeps its proposal.
+#[tauri::command]
+pub async fn apply_note_metadata(
+    updates: Vec<MetadataUpdate>,
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<MetadataApplyResult, String> {
+    let vault = state.vault.lock().await.clone().ok_or("No vault opened")?;
+    let mut store = ProposalStore::load(&vault);
+    let mut written = Vec::new();
+    let mut failed = Vec::new();
+
+    for update in updates {
+        match apply_update(&vault, &update, &state.auth).await {
+            // Proposals are keyed by the normalised path, not what the caller sent
+            Ok(relative) => {
+                store.proposals.remove(&relative);
+                written.push(relative);
+            }
+            Err(error) => failed.push(MetadataFailure {
+                path: update.path,
+                error,
+            }),
+        }
+    }
+
+    // Save even if some failed, so applied proposals don't come back
+    store.save(&vault)?;
+    println!("📝 Applied metadata to {} notes, {} failed", written.len(), failed.len());
+    Ok(MetadataApplyResult { written, failed })
+}
+
+// Write one note's metadata, returning its vault-relative path
+async fn apply_update(
+    vault: &Vault,
+    update: &MetadataUpdate,
+    auth: &AuthManager,
+) -> Result<String, String> {
+    let relative = relative_path(&update.path)?;
+    auth.authorize(vault.path(), &relative, VaultAction::Write).await?;
+    let mut content = vault
+        .read_file(&relative)
+        .map_err(|e| format!("Failed to read {}: {}", update.path, e))?;
+
+    if let Some(title) = update.title.as_deref().map(str::trim).filter(|t| !t.is_empty()) {
+        content = frontmatter::set_field(&content, "title", FieldValue::Text(title));
+    }
+    if let Some(summary) = update.summary.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+        content = frontmatter::set_field(&content, "summary", FieldValue::Text(summary));
+    }
+    if let Some(tags) = update.tags.as_ref().filter(|t| !t.is_empty()) {
+        let mut merged = frontmatter::get_list(frontmatter::yaml(&content), "tags");
+        for tag in tags.iter().map(|t| normalize_tag(t)) {
+            if !tag.is_empty() && !merged.iter().any(|t| normalize_tag(t) == tag) {
+                merged.push(tag);
+            }
+        }
+        content = frontmatter::set_field(&content, "tags", FieldValue::List(&merged));
+    }
+
+    vault
+        .write_file(&relative, &content)
+        .map_err(|e| format!("Failed to write {}: {}", update.path, e))?;
+    Ok(relative.to_string_lossy().into_owned())
+}
+
+/// Drop proposals without applying them; all of them when `paths` is absent.
+/// Only proposals for notes the user could apply them to can be dropped.
+#[tauri::command]
+pub async fn discard_metadata_proposals(
+    paths: Option<Vec<String>>,
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<(), String> {
+    let vault = state.vault.lock().await.clone().ok_or("No vault opened")?;
+    let access = state.auth.vault_access(vault.path()).await;
+    let mut store = ProposalStore::load(&vault);
+    match paths {
+        Some(paths) => {
+            for path in paths {
+                let relative = relative_path(&path)?;
+                access.check(&relative, VaultAction::Write)?;
+                store.proposals.remove(relative.to_string_lossy().as_ref());
+            }
+        }
+        None => store
+            .proposals
+            .retain(|path, _| !access.allows(Path::new(path), VaultAction::Write)),
+    }
+    store.save(&vault)
+}
@@ -0,0 +1,281 @@
+// ai_provider.rs - The AiProvider trait and the plumbing its backends share
+
+use futures_util::future::BoxFuture;
+use futures_util::{FutureExt, StreamExt};
+use std::time::Duration;
+
+use crate::ai_settings::{AIProvider, AISettings, ConnectionTestResult};
+use crate::ai_stream::ChatMessage;
+use crate::ai_tools::{AgentMessage, AgentTurn, ToolSpec};
+use crate::anthropic::AnthropicProvider;
+use crate::ollama::OllamaProvider;
+use crate::openai::OpenAIProvider;
+
+// Long answers can take minutes, so only connecting and silent gaps are timed
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+pub const STREAM_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+pub const TEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A chat backend. Each provider owns its wire format; callers only deal in
+/// `ChatMessage`s and text.
+pub trait AiProvider: Send + Sync {
+    /// Send a chat and wait for the whole reply.
+    fn chat<'a>(&'a self, messages: &'a [ChatMessage]) -> BoxFuture<'a, Result<String, String>>;
+
+    /// Send a chat, handing each piece of the reply to `on_text` as it arrives.
+    fn stream<'a>(
+        &'a self,
+        messages: &'a [ChatMessage],
+        on_text: &'a mut (dyn FnMut(String) + Send),
+    ) -> BoxFuture<'a, Result<(), String>>;
+
+    /// One non-streamed turn of an agent conversation with `tools` on offer.
+    /// Backends without OpenAI-style function calling keep this default.
+    fn chat_with_tools<'a>(
+        &'a self,
+        _messages: &'a [AgentMessage],
+        _tools: &'a [ToolSpec],
+    ) -> BoxFuture<'a, Result<AgentTurn, String>> {
+        async { Err("This provider doesn't support tool calling".to_string()) }.boxed()
+    }
+
+    /// Embedding vectors for `texts`, in order, from the settings' embedding model.
+    fn embed<'a>(&'a self, texts: &'a [String]) -> BoxFuture<'a, Result<Vec<Vec<f32>>, String>>;
+
+    /// Model names the endpoint offers.
+    fn list_models(&self) -> BoxFuture<'_, Result<Vec<String>, String>>;
+
+    /// Check reachability, credentials and the configured model.
+    fn test_connection(&self) -> BoxFuture<'_, ConnectionTestResult>;
+}
+
+pub fn provider_for(settings: AISettings) -> Box<dyn AiProvider> {
+    match settings.provider {
+        AIProvider::OpenAI => Box::new(OpenAIProvider::new(settings)),
+        AIProvider::Anthropic => Box::new(AnthropicProvider::new(settings)),
+        AIProvider::Ollama => Box::new(OllamaProvider::new(settings)),
+    }
+}
+
+/// A client for chat requests: bounded connect time, unbounded replies.
+pub fn streaming_client() -> Result<reqwest::Client, String> {
+    reqwest::Client::builder()
+        .connect_timeout(CONNECT_TIMEOUT)
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))
+}
+
+pub fn test_client() -> Result<reqwest::Client, String> {
+    reqwest::Client::builder()
+        .timeout(TEST_TIMEOUT)
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))
+}
+
+/// Send a request, turning connection failures and error statuses into messages.
+pub async fn send_checked(request: reqwest::RequestBuilder) -> Result<reqwest::Response, String> {
+    let response = request
+        .send()
+        .await
+        .map_err(|e| format!("Failed to connect: {}", e))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let url = response.url().to_string();
+        let error_text = response.text().await.unwrap_or_default();
+        println!("API Error - Status: {}, Error: {}", status, error_text);
+        println!("Request URL was: {}", url);
+        return Err(format!("API error ({}): {}", status, error_text));
+    }
+
+    Ok(response)
+}
+
+pub async fn read_json(response: reqwest::Response) -> Result<serde_json::Value, String> {
+    let text = response
+        .text()
+        .await
+        .map_err(|e| format!("Failed to read response: {}", e))?;
+    serde_json::from_str(&text).map_err(|e| format!("Failed to parse JSON: {}", e))
+}
+
+pub fn no_embedding_model() -> String {
+    "No embedding model configured for this AI profile".to_string()
+}
+
+/// The message of an `{"error": ...}` body, whether a string or an object.
+pub fn api_error_message(value: &serde_json::Value) -> Option<String> {
+    let error = value.get("error")?;
+    Some(
+        error
+            .get("message")
+            .and_then(|m| m.as_str())
+            .or_else(|| error.as_str())
+            .map(|m| m.to_string())
+            .unwrap_or_else(|| error.to_string()),
+    )
+}
+
+/// What one line of a streamed response means.
+#[derive(Debug, PartialEq)]
+pub enum StreamLine {
+    Content(String),
+    /// Content that also ends the stream
+    Last(String),
+    Error(String),
+    Done,
+    Skip,
+}
+
+/// The payload of an SSE `data:` line; comments, `event:` and `id:` fields
+/// carry no content.
+pub fn sse_data(line: &str) -> Option<&str> {
+    line.trim().strip_prefix("data:").map(str::trim)
+}
+
+// Splits a byte stream into lines; a multi-byte character split across
+// network chunks stays buffered until its line is complete
+#[derive(Default)]
+struct LineBuffer {
+    pending: Vec<u8>,
+}
+
+impl LineBuffer {
+    fn push(&mut self, bytes: &[u8]) -> Vec<String> {
+        self.pending.extend_from_slice(bytes);
+        let mut lines = Vec::new();
+        while let Some(pos) = self.pending.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=pos).collect();
+            lines.push(String::from_utf8_lossy(&line).trim_end().to_string());
+        }
+        lines
+    }
+
+    fn finish(self) -> Option<String> {
+        let line = String::from_utf8_lossy(&self.pending).trim().to_string();
+        (!line.is_empty()).then_some(line)
+    }
+}
+
+/// Read a streamed response line by line, passing text on until `parse`
+/// reports the end of the stream.
+pub async fn read_stream(
+    response: reqwest::Response,
+    parse: impl Fn(&str) -> StreamLine,
+    on_text: &mut (dyn FnMut(String) + Send),
+) -> Result<(), String> {
+    let mut stream = response.bytes_stream();
+    let mut lines = LineBuffer::default();
+
+    // Returns whether the stream has ended
+    let mut handle_line = |line: &str| -> Result<bool, String> {
+        if line.trim().is_empty() {
+            return Ok(false);
+        }
+        let (text, last) = match parse(line) {
+            StreamLine::Content(text) => (text, false),
+            StreamLine::Last(text) => (text, true),
+            StreamLine::Error(error) => return Err(format!("API error: {}", error)),
+            StreamLine::Done => return Ok(true),
+            StreamLine::Skip => return Ok(false),
+        };
+        on_text(text);
+        Ok(last)
+    };
+
+    loop {
+        let bytes = match tokio::time::timeout(STREAM_IDLE_TIMEOUT, stream.next()).await {
+            Err(_) => return Err("Response stream timed out".to_string()),
+            Ok(None) => break,
+            Ok(Some(Err(e))) => return Err(format!("Failed to read response: {}", e)),
+            Ok(Some(Ok(bytes))) => bytes,
+        };
+
+        for line in lines.push(&bytes) {
+            if handle_line(&line)? {
+                return Ok(());
+            }
+        }
+    }
+
+    // The last line may arrive without a trailing newline
+    if let Some(line) = lines.finish() {
+        handle_line(&line)?;
+    }
+
+    Ok(())
+}
+
+/// Reachability check shared by every backend: local servers must answer,
+/// remote ones must use HTTPS. Returns false when testing should stop.
+pub async fn check_endpoint(
+    client: &reqwest::Client,
+    endpoint: &str,
+    result: &mut ConnectionTestResult,
+) -> bool {
+    // For local endpoints, check if server is running
+    if endpoint.contains("localhost") || endpoint.contains("127.0.0.1") {
+        match client.get(endpoint).send().await {
+            Ok(_) => {
+                result.endpoint_status.success = true;
+                result.endpoint_status.message = "Local server is running".to_string();
+                true
+            }
+            Err(e) => {
+                result.endpoint_status.message = format!("Local server not accessible: {}", e);
+                result.overall_status.message = "Failed to connect to local AI server".to_string();
+                false
+            }
+        }
+    } else if !endpoint.starts_with("https://") {
+        // For external endpoints, check HTTPS
+        result.endpoint_status.message = "External endpoints must use HTTPS".to_string();
+        result.overall_status.message = "Security error: HTTPS required".to_string();
+        false
+    } else {
+        result.endpoint_status.success = true;
+        result.endpoint_status.message = "Endpoint URL is valid".to_string();
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sse_data_takes_only_data_lines() {
+        assert_eq!(sse_data("data: {\"a\":1}"), Some("{\"a\":1}"));
+        assert_eq!(sse_data("data:[DONE]"), Some("[DONE]"));
+        assert_eq!(sse_data("event: ping"), None);
+        assert_eq!(sse_data(": keep-alive"), None);
+    }
+
+    #[test]
+    fn api_errors_are_read_as_objects_or_strings() {
+        let object = serde_json::json!({ "error": { "message": "Bad key", "type": "auth" } });
+        assert_eq!(api_error_message(&object).as_deref(), Some("Bad key"));
+        let string = serde_json::json!({ "error": "model not found" });
+        assert_eq!(api_error_message(&string).as_deref(), Some("model not found"));
+        assert_eq!(api_error_message(&serde_json::json!({ "ok": true })), None);
+    }
+
+    #[test]
+    fn line_buffer_keeps_characters_split_across_chunks() {
+        let text = "é\nü";
+        let bytes = text.as_bytes();
+        let mut lines = LineBuffer::default();
+        // Split inside the two-byte `é`
+        assert!(lines.push(&bytes[..1]).is_empty());
+        assert_eq!(lines.push(&bytes[1..4]), ["é"]);
+        assert!(lines.push(&bytes[4..]).is_empty());
+        assert_eq!(lines.finish().as_deref(), Some("ü"));
+    }
+
+    #[test]
+    fn line_buffer_splits_crlf_lines() {
+        let mut lines = LineBuffer::default();
+        assert_eq!(lines.push(b"one\r\ntwo\n\nthree"), ["one", "two", ""]);
+        assert_eq!(lines.finish().as_deref(), Some("three"));
+    }
+}
@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
-use tauri::AppHandle;
+use std::collections::BTreeMap;
+use tauri::{AppHandle, Manager};
 use tauri_plugin_store::StoreExt;
 use sha2::{Sha256, Digest};
 use aes_gcm::{
@@ -9,22 +10,107 @@ use aes_gcm::{
 use rand::RngCore;
 use base64::{Engine as _, engine::general_purpose};
 
+use crate::ai_provider;
+
+const SETTINGS_STORE: &str = "ai_settings.json";
+const PROFILES_KEY: &str = "profiles";
+// The single settings object written before profiles existed
+const LEGACY_SETTINGS_KEY: &str = "settings";
+const DEFAULT_PROFILE_NAME: &str = "Default";
+
+/// Which wire protocol the endpoint speaks.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum AIProvider {
+    /// OpenAI-compatible `/chat/completions` (OpenAI, LM Studio, Ollama's `/v1`)
+    #[default]
+    OpenAI,
+    /// Anthropic's Messages API
+    Anthropic,
+    /// Ollama's native `/api/chat`
+    Ollama,
+}
+
+impl AIProvider {
+    /// The name used in settings and recorded with conversations.
+    pub fn name(self) -> &'static str {
+        match self {
+            AIProvider::OpenAI => "openai",
+            AIProvider::Anthropic => "anthropic",
+            AIProvider::Ollama => "ollama",
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct AISettings {
+    #[serde(default)]
+    pub provider: AIProvider,
     pub endpoint: String,
     pub api_key: Option<String>,
     pub model: String,
     pub temperature: f32,
     pub max_tokens: u32,
+    /// Model for note embeddings; each provider has a sensible default
+    #[serde(default)]
+    pub embedding_model: Option<String>,
+    /// Tokens the model accepts per request, for models the app doesn't know
+    #[serde(default)]
+    pub context_window: Option<u32>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl AISettings {
+    pub fn embedding_model(&self) -> Option<String> {
+        if let Some(model) = self.embedding_model.as_ref().filter(|m| !m.trim().is_empty()) {
+            return Some(model.clone());
+        }
+        match self.provider {
+            AIProvider::OpenAI => Some("text-embedding-3-small".to_string()),
+            AIProvider::Ollama => Some("nomic-embed-text".to_string()),
+            AIProvider::Anthropic => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct StoredSettings {
+    // Missing in settings saved before providers were explicit
+    #[serde(default)]
+    provider: Option<AIProvider>,
     endpoint: String,
     api_key_encrypted: Option<String>,
     model: String,
     temperature: f32,
     max_tokens: u32,
+    #[serde(default)]
+    embedding_model: Option<String>,
+    #[serde(default)]
+    context_window: Option<u32>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ProfileStore {
+    profiles: BTreeMap<String, StoredSettings>,
+    default_profile: Option<String>,
+    /// Vault path -> profile name
+    vault_profiles: BTreeMap<String, String>,
+}
+
+/// A profile as listed in settings; the API key itself is never sent.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AIProfileInfo {
+    pub name: String,
+    pub provider: AIProvider,
+    pub endpoint: String,
+    pub model: String,
+    pub temperature: f32,
+    pub max_tokens: u32,
+    pub has_api_key: bool,
+    pub is_default: bool,
+    /// Chosen for the open vault, overriding the default
+    pub is_vault_default: bool,
+    /// What `get_ai_settings` returns right now
+    pub is_active: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -42,6 +128,49 @@ pub struct TestStatus {
     pub message: String,
 }
 
+impl TestStatus {
+    fn new(stage: &str, message: &str) -> Self {
+        Self {
+            stage: stage.to_string(),
+            success: false,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl ConnectionTestResult {
+    /// Every stage untested, for providers to fill in.
+    pub fn pending() -> Self {
+        Self {
+            endpoint_status: TestStatus::new("endpoint", "Checking endpoint..."),
+            auth_status: TestStatus::new("auth", "Not tested"),
+            model_status: TestStatus::new("model", "Not tested"),
+            overall_status: TestStatus::new("overall", "Testing..."),
+        }
+    }
+
+    /// Mark the whole test successful once the endpoint and auth checks passed.
+    pub fn finish(&mut self) {
+        if self.endpoint_status.success && self.auth_status.success {
+            self.overall_status.success = true;
+            self.overall_status.message = "Connection successful!".to_string();
+        }
+    }
+}
+
+// Settings saved before the provider field existed picked Ollama's native
+// API by sniffing the endpoint URL
+fn legacy_provider(endpoint: &str) -> AIProvider {
+    if (endpoint.contains("ollama") || endpoint.contains("11434"))
+        && !endpoint.contains("/v1")
+        && !endpoint.contains("chat/completions")
+    {
+        AIProvider::Ollama
+    } else {
+        AIProvider::OpenAI
+    }
+}
+
 // Derive a key from the app's unique identifier
 fn derive_encryption_key(app: &AppHandle) -> [u8; 32] {
     let mut hasher = Sha256::new();
@@ -98,189 +227,273 @@ fn decrypt_string(encrypted: &str, key: &[u8; 32]) -> Result<String, String> {
         .map_err(|e| format!("UTF-8 decode failed: {}", e))
 }
 
-#[tauri::command]
-pub async fn save_ai_settings(
-    app: AppHandle,
-    settings: AISettings,
-) -> Result<(), String> {
-    println!("Saving AI settings...");
-    
-    // Get encryption key
-    let key = derive_encryption_key(&app);
+fn encrypt_settings(app: &AppHandle, settings: AISettings) -> Result<StoredSettings, String> {
+    let key = derive_encryption_key(app);
     
     // Encrypt API key if present
-    let encrypted_api_key = if let Some(api_key) = &settings.api_key {
-        if !api_key.is_empty() {
-            Some(encrypt_string(api_key, &key)?)
-        } else {
-            None
-        }
-    } else {
-        None
+    let encrypted_api_key = match settings.api_key.as_deref() {
+        Some(api_key) if !api_key.is_empty() => Some(encrypt_string(api_key, &key)?),
+        _ => None,
     };
     
-    // Create stored settings
-    let stored = StoredSettings {
+    Ok(StoredSettings {
+        provider: Some(settings.provider),
         endpoint: settings.endpoint,
         api_key_encrypted: encrypted_api_key,
         model: settings.model,
         temperature: settings.temperature,
         max_tokens: settings.max_tokens,
+        embedding_model: settings.embedding_model,
+        context_window: settings.context_window,
+    })
+}
+
+fn decrypt_settings(app: &AppHandle, stored: &StoredSettings) -> Result<AISettings, String> {
+    let key = derive_encryption_key(app);
+    let api_key = match &stored.api_key_encrypted {
+        Some(encrypted) => Some(decrypt_string(encrypted, &key)?),
+        None => None,
     };
     
-    // Save to store
-    let store = app.store("ai_settings.json")
+    Ok(AISettings {
+        provider: stored.provider.unwrap_or_else(|| legacy_provider(&stored.endpoint)),
+        endpoint: stored.endpoint.clone(),
+        api_key,
+        model: stored.model.clone(),
+        temperature: stored.temperature,
+        max_tokens: stored.max_tokens,
+        embedding_model: stored.embedding_model.clone(),
+        context_window: stored.context_window,
+    })
+}
+
+fn load_profiles(app: &AppHandle) -> Result<ProfileStore, String> {
+    let store = app.store(SETTINGS_STORE)
+        .map_err(|e| format!("Failed to access store: {}", e))?;
+    
+    if let Some(value) = store.get(PROFILES_KEY) {
+        return serde_json::from_value(value.clone())
+            .map_err(|e| format!("Failed to parse AI profiles: {}", e));
+    }
+    
+    // Settings saved before profiles existed become the default profile
+    let mut profiles = ProfileStore::default();
+    if let Some(value) = store.get(LEGACY_SETTINGS_KEY) {
+        let stored: StoredSettings = serde_json::from_value(value.clone())
+            .map_err(|e| format!("Failed to parse settings: {}", e))?;
+        println!("Migrating AI settings to profile '{}'", DEFAULT_PROFILE_NAME);
+        profiles.profiles.insert(DEFAULT_PROFILE_NAME.to_string(), stored);
+        profiles.default_profile = Some(DEFAULT_PROFILE_NAME.to_string());
+    }
+    Ok(profiles)
+}
+
+fn save_profiles(app: &AppHandle, profiles: &ProfileStore) -> Result<(), String> {
+    let store = app.store(SETTINGS_STORE)
         .map_err(|e| format!("Failed to access store: {}", e))?;
     
-    let value = serde_json::to_value(&stored).map_err(|e| e.to_string())?;
-    store.set("settings", value);
+    let value = serde_json::to_value(profiles).map_err(|e| e.to_string())?;
+    store.set(PROFILES_KEY, value);
+    store.delete(LEGACY_SETTINGS_KEY);
     
     store.save()
-        .map_err(|e| format!("Failed to persist settings: {}", e))?;
+        .map_err(|e| format!("Failed to persist settings: {}", e))
+}
+
+// Path of the open vault, for per-vault profile overrides
+async fn current_vault_key(app: &AppHandle) -> Option<String> {
+    let state = app.state::<crate::AppState>();
+    let vault_lock = state.vault.lock().await;
+    vault_lock.as_ref().map(|vault| vault.path().to_string_lossy().to_string())
+}
+
+impl ProfileStore {
+    /// The open vault's override if it has one, else the default profile.
+    fn active_name(&self, vault_key: Option<&str>) -> Option<&String> {
+        let vault_profile = vault_key
+            .and_then(|key| self.vault_profiles.get(key))
+            .filter(|name| self.profiles.contains_key(*name));
+        let default_profile = self
+            .default_profile
+            .as_ref()
+            .filter(|name| self.profiles.contains_key(*name));
+        vault_profile
+            .or(default_profile)
+            .or_else(|| self.profiles.keys().next())
+    }
+}
+
+fn validate_profile_name(name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Profile name cannot be empty".to_string());
+    }
+    Ok(name.to_string())
+}
+
+/// Save settings into the active profile (creating the default profile the
+/// first time).
+#[tauri::command]
+pub async fn save_ai_settings(
+    app: AppHandle,
+    settings: AISettings,
+) -> Result<(), String> {
+    println!("Saving AI settings...");
+    
+    let mut profiles = load_profiles(&app)?;
+    let vault_key = current_vault_key(&app).await;
+    let name = profiles
+        .active_name(vault_key.as_deref())
+        .cloned()
+        .unwrap_or_else(|| DEFAULT_PROFILE_NAME.to_string());
+    
+    let stored = encrypt_settings(&app, settings)?;
+    profiles.profiles.insert(name.clone(), stored);
+    profiles.default_profile.get_or_insert_with(|| name.clone());
+    save_profiles(&app, &profiles)?;
     
-    println!("AI settings saved successfully");
+    println!("AI settings saved to profile '{}'", name);
     Ok(())
 }
 
+/// Settings of the active profile: the open vault's override, else the default.
+///
+/// Locks the vault to find its override, so don't call it while holding that lock.
 #[tauri::command]
 pub async fn get_ai_settings(app: AppHandle) -> Result<Option<AISettings>, String> {
     println!("Loading AI settings...");
     
-    let store = app.store("ai_settings.json")
-        .map_err(|e| format!("Failed to access store: {}", e))?;
-    
-    let Some(value) = store.get("settings") else {
+    let profiles = load_profiles(&app)?;
+    let vault_key = current_vault_key(&app).await;
+    let Some(name) = profiles.active_name(vault_key.as_deref()) else {
         println!("No AI settings found");
         return Ok(None);
     };
     
-    let stored: StoredSettings = serde_json::from_value(value.clone())
-        .map_err(|e| format!("Failed to parse settings: {}", e))?;
-    
-    // Decrypt API key if present
-    let key = derive_encryption_key(&app);
-    let api_key = if let Some(encrypted) = &stored.api_key_encrypted {
-        Some(decrypt_string(encrypted, &key)?)
-    } else {
-        None
-    };
+    decrypt_settings(&app, &profiles.profiles[name]).map(Some)
+}
+
+#[tauri::command]
+pub async fn list_ai_profiles(app: AppHandle) -> Result<Vec<AIProfileInfo>, String> {
+    let profiles = load_profiles(&app)?;
+    let vault_key = current_vault_key(&app).await;
+    let active = profiles.active_name(vault_key.as_deref());
+    let vault_profile = vault_key.as_deref().and_then(|key| profiles.vault_profiles.get(key));
     
-    Ok(Some(AISettings {
-        endpoint: stored.endpoint,
-        api_key,
-        model: stored.model,
-        temperature: stored.temperature,
-        max_tokens: stored.max_tokens,
-    }))
+    Ok(profiles
+        .profiles
+        .iter()
+        .map(|(name, stored)| AIProfileInfo {
+            name: name.clone(),
+            provider: stored.provider.unwrap_or_else(|| legacy_provider(&stored.endpoint)),
+            endpoint: stored.endpoint.clone(),
+            model: stored.model.clone(),
+            temperature: stored.temperature,
+            max_tokens: stored.max_tokens,
+            has_api_key: stored.api_key_encrypted.is_some(),
+            is_default: profiles.default_profile.as_ref() == Some(name),
+            is_vault_default: vault_profile == Some(name),
+            is_active: active == Some(name),
+        })
+        .collect())
 }
 
 #[tauri::command]
-pub async fn test_ai_connection(settings: AISettings) -> Result<ConnectionTestResult, String> {
-    println!("Testing AI connection to: {}", settings.endpoint);
+pub async fn get_ai_profile(app: AppHandle, name: String) -> Result<AISettings, String> {
+    let profiles = load_profiles(&app)?;
+    let stored = profiles
+        .profiles
+        .get(&name)
+        .ok_or_else(|| format!("No AI profile named '{}'", name))?;
+    decrypt_settings(&app, stored)
+}
+
+/// Create a profile, or replace the settings of an existing one.
+#[tauri::command]
+pub async fn save_ai_profile(
+    app: AppHandle,
+    name: String,
+    settings: AISettings,
+) -> Result<(), String> {
+    let name = validate_profile_name(&name)?;
+    println!("Saving AI profile '{}'", name);
     
-    let mut result = ConnectionTestResult {
-        endpoint_status: TestStatus {
-            stage: "endpoint".to_string(),
-            success: false,
-            message: "Checking endpoint...".to_string(),
-        },
-        auth_status: TestStatus {
-            stage: "auth".to_string(),
-            success: false,
-            message: "Not tested".to_string(),
-        },
-        model_status: TestStatus {
-            stage: "model".to_string(),
-            success: false,
-            message: "Not tested".to_string(),
-        },
-        overall_status: TestStatus {
-            stage: "overall".to_string(),
-            success: false,
-            message: "Testing...".to_string(),
-        },
-    };
+    let mut profiles = load_profiles(&app)?;
+    let stored = encrypt_settings(&app, settings)?;
+    profiles.profiles.insert(name.clone(), stored);
+    profiles.default_profile.get_or_insert(name);
+    save_profiles(&app, &profiles)
+}
+
+#[tauri::command]
+pub async fn delete_ai_profile(app: AppHandle, name: String) -> Result<(), String> {
+    println!("Deleting AI profile '{}'", name);
     
-    // Test 1: Check endpoint reachability
-    let client = reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(10))
-        .build()
-        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+    let mut profiles = load_profiles(&app)?;
+    if profiles.profiles.remove(&name).is_none() {
+        return Err(format!("No AI profile named '{}'", name));
+    }
     
-    // For local endpoints, check if server is running
-    if settings.endpoint.contains("localhost") || settings.endpoint.contains("127.0.0.1") {
-        match client.get(&settings.endpoint).send().await {
-            Ok(_) => {
-                result.endpoint_status.success = true;
-                result.endpoint_status.message = "Local server is running".to_string();
-            }
-            Err(e) => {
-                result.endpoint_status.message = format!("Local server not accessible: {}", e);
-                result.overall_status.message = "Failed to connect to local AI server".to_string();
-                return Ok(result);
-            }
-        }
-    } else {
-        // For external endpoints, check HTTPS
-        if !settings.endpoint.starts_with("https://") {
-            result.endpoint_status.message = "External endpoints must use HTTPS".to_string();
-            result.overall_status.message = "Security error: HTTPS required".to_string();
-            return Ok(result);
-        }
-        result.endpoint_status.success = true;
-        result.endpoint_status.message = "Endpoint URL is valid".to_string();
+    profiles.vault_profiles.retain(|_, profile| *profile != name);
+    if profiles.default_profile.as_ref() == Some(&name) {
+        profiles.default_profile = profiles.profiles.keys().next().cloned();
+    }
+    save_profiles(&app, &profiles)
+}
+
+/// Make a profile the default, or with `for_vault` the open vault's override.
+#[tauri::command]
+pub async fn switch_ai_profile(
+    app: AppHandle,
+    name: String,
+    for_vault: Option<bool>,
+) -> Result<(), String> {
+    let mut profiles = load_profiles(&app)?;
+    if !profiles.profiles.contains_key(&name) {
+        return Err(format!("No AI profile named '{}'", name));
     }
     
-    // Test 2: Check authentication (if API key provided)
-    if let Some(api_key) = &settings.api_key {
-        if !api_key.is_empty() {
-            // Make a minimal request to test auth
-            let test_url = format!("{}/chat/completions", settings.endpoint.trim_end_matches('/'));
-            let test_body = serde_json::json!({
-                "model": settings.model,
-                "messages": [{"role": "user", "content": "Hi"}],
-                "max_tokens": 1,
-                "stream": false
-            });
-            
-            match client
-                .post(&test_url)
-                .header("Authorization", format!("Bearer {}", api_key))
-                .header("Content-Type", "application/json")
-                .json(&test_body)
-                .send()
-                .await
-            {
-                Ok(response) => {
-                    if response.status() == 401 {
-                        result.auth_status.message = "Invalid API key".to_string();
-                        result.overall_status.message = "Authentication failed".to_string();
-                        return Ok(result);
-                    } else if response.status().is_success() || response.status() == 400 {
-                        result.auth_status.success = true;
-                        result.auth_status.message = "API key is valid".to_string();
-                        result.model_status.success = true;
-                        result.model_status.message = "Model is available".to_string();
-                    }
-                }
-                Err(e) => {
-                    result.auth_status.message = format!("Failed to test authentication: {}", e);
-                }
-            }
-        } else {
-            result.auth_status.success = true;
-            result.auth_status.message = "No authentication required".to_string();
-        }
+    if for_vault.unwrap_or(false) {
+        let vault_key = current_vault_key(&app).await.ok_or("No vault opened")?;
+        println!("Using AI profile '{}' for vault {}", name, vault_key);
+        profiles.vault_profiles.insert(vault_key, name);
     } else {
-        result.auth_status.success = true;
-        result.auth_status.message = "No authentication required".to_string();
+        println!("Default AI profile is now '{}'", name);
+        profiles.default_profile = Some(name);
     }
-    
-    // Set overall status
-    if result.endpoint_status.success && result.auth_status.success {
-        result.overall_status.success = true;
-        result.overall_status.message = "Connection successful!".to_string();
+    save_profiles(&app, &profiles)
+}
+
+/// Drop the open vault's override so it follows the default profile again.
+#[tauri::command]
+pub async fn clear_vault_ai_profile(app: AppHandle) -> Result<(), String> {
+    let vault_key = current_vault_key(&app).await.ok_or("No vault opened")?;
+    let mut profiles = load_profiles(&app)?;
+    if profiles.vault_profiles.remove(&vault_key).is_some() {
+        save_profiles(&app, &profiles)?;
     }
-    
-    Ok(result)
-}
\ No newline at end of file
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn test_ai_connection(settings: AISettings) -> Result<ConnectionTestResult, String> {
+    println!("Testing AI connection to: {} ({:?})", settings.endpoint, settings.provider);
+    Ok(ai_provider::provider_for(settings).test_connection().await)
+}
+
+/// Models offered by the given settings' endpoint, or the saved settings'.
+#[tauri::command]
+pub async fn list_ai_models(
+    app: AppHandle,
+    settings: Option<AISettings>,
+) -> Result<Vec<String>, String> {
+    let settings = match settings {
+        Some(settings) => settings,
+        None => get_ai_settings(app)
+            .await?
+            .ok_or("No AI settings configured")?,
+    };
+
+    println!("Listing models at {} ({:?})", settings.endpoint, settings.provider);
+    ai_provider::provider_for(settings).list_models().await
+}
@@ -1,6 +1,16 @@
 use serde::{Deserialize, Serialize};
-use tauri::AppHandle;
-use crate::ai_settings::get_ai_settings;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{oneshot, Mutex};
+use tauri::{AppHandle, Emitter};
+use crate::ai_budget::{self, Budgeter, ChatBudgetEvent};
+use crate::ai_provider;
+use crate::ai_settings::{get_ai_profile, get_ai_settings, AISettings};
+use crate::ai_tools::{self, AgentMessage, AgentTurn, ToolCall};
+use crate::auth::VaultAction;
+use crate::conversations::{ConversationMessage, ConversationStore, ToolCallRecord};
+use crate::embeddings::{self, ChunkHit};
 
 #[tauri::command]
 pub async fn test_messages(messages: Vec<ChatMessage>) -> Result<String, String> {
@@ -41,162 +51,606 @@ pub struct ChatMessage {
     pub content: String,
 }
 
+/// Event carrying `StreamChunk`s for in-flight chats
+pub const STREAM_EVENT: &str = "ai-stream-chunk";
+
 #[derive(Clone, Debug, Serialize)]
 pub struct StreamChunk {
+    /// Correlates chunks with the `send_ai_chat` call that produced them
+    pub request_id: String,
     #[serde(rename = "type")]
-    pub chunk_type: String, // "content", "error", "done"
+    pub chunk_type: String, // "content", "error", "done", "cancelled"
     pub content: Option<String>,
     pub error: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
-struct OpenAIStreamChunk {
-    choices: Vec<Choice>,
+impl StreamChunk {
+    fn content(request_id: &str, content: String) -> Self {
+        Self {
+            request_id: request_id.to_string(),
+            chunk_type: "content".to_string(),
+            content: Some(content),
+            error: None,
+        }
+    }
+
+    fn error(request_id: &str, error: String) -> Self {
+        Self {
+            request_id: request_id.to_string(),
+            chunk_type: "error".to_string(),
+            content: None,
+            error: Some(error),
+        }
+    }
+
+    fn done(request_id: &str) -> Self {
+        Self {
+            request_id: request_id.to_string(),
+            chunk_type: "done".to_string(),
+            content: None,
+            error: None,
+        }
+    }
+
+    /// Carries everything generated before the cancel
+    fn cancelled(request_id: &str, partial: String) -> Self {
+        Self {
+            request_id: request_id.to_string(),
+            chunk_type: "cancelled".to_string(),
+            content: Some(partial),
+            error: None,
+        }
+    }
 }
 
-#[derive(Debug, Deserialize)]
-struct Choice {
-    delta: Delta,
+fn emit_chunk(app: &AppHandle, chunk: StreamChunk) {
+    if let Err(e) = app.emit(STREAM_EVENT, chunk) {
+        println!("Failed to emit stream chunk: {}", e);
+    }
 }
 
-#[derive(Debug, Deserialize)]
-struct Delta {
-    content: Option<String>,
+pub fn new_request_id() -> String {
+    format!("chat-{:016x}", rand::random::<u64>())
 }
 
+/// Send a chat to the configured model, streaming the reply as
+/// `ai-stream-chunk` events tagged with `request_id`.
+///
+/// Resolves with the full reply once the stream ends, so callers that don't
+/// listen for events still get the complete answer. A chat stopped with
+/// `cancel_ai_chat` resolves with the partial reply.
+///
+/// Earlier turns that don't fit the model's context window are left out, or
+/// with `summarize_history` replaced by a summary; the budget is reported as
+/// an `ai-chat-budget` event before the chat is sent. `profile` picks an AI
+/// profile other than the active one.
 #[tauri::command]
 pub async fn send_ai_chat(
     app: AppHandle,
     messages: Vec<ChatMessage>,
+    request_id: Option<String>,
+    stream: Option<bool>,
+    summarize_history: Option<bool>,
+    profile: Option<String>,
+    state: tauri::State<'_, crate::AppState>,
 ) -> Result<String, String> {
     println!("\n=== SEND_AI_CHAT CALLED ===");
-    println!("Starting AI chat...");
-    
-    // Debug the raw input
-    println!("Messages type info: Vec<ChatMessage>");
-    println!("Received {} messages", messages.len());
-    
+    let request_id = request_id.unwrap_or_else(new_request_id);
+    let mode = ChatMode::Chat {
+        stream: stream.unwrap_or(true),
+        summarize_history: summarize_history.unwrap_or(false),
+        profile,
+    };
+    run_chat(&app, &state, &request_id, messages, mode).await
+}
+
+/// A cited excerpt given to the model by `send_ai_chat_with_context`.
+#[derive(Debug, Serialize)]
+pub struct Citation {
+    /// The `[n]` the answer uses to refer to this excerpt
+    pub index: usize,
+    pub path: String,
+    pub heading: Option<String>,
+    pub start_line: usize,
+    pub score: f32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ContextualAnswer {
+    pub content: String,
+    pub citations: Vec<Citation>,
+}
+
+const DEFAULT_CONTEXT_CHUNKS: usize = 6;
+
+/// Like `send_ai_chat`, but first retrieves the vault excerpts most relevant
+/// to the last user message and gives them to the model as numbered sources.
+/// The reply streams as usual; the sources come back with the full answer.
+#[tauri::command]
+pub async fn send_ai_chat_with_context(
+    app: AppHandle,
+    messages: Vec<ChatMessage>,
+    request_id: Option<String>,
+    top_k: Option<usize>,
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<ContextualAnswer, String> {
+    let request_id = request_id.unwrap_or_else(new_request_id);
+    let question = messages
+        .iter()
+        .rposition(|m| m.role == "user")
+        .ok_or("No user message to answer")?;
+
+    let mut hits = embeddings::retrieve(
+        &app,
+        &state,
+        &messages[question].content,
+        top_k.unwrap_or(DEFAULT_CONTEXT_CHUNKS),
+    )
+    .await?;
+    println!("Retrieved {} vault excerpts for chat {}", hits.len(), request_id);
+
+    // Drop the weakest excerpts until the rest fit, so every citation is one
+    // the model actually saw
+    let settings = get_ai_settings(app.clone())
+        .await?
+        .ok_or("No AI settings configured")?;
+    let budgeter = Budgeter::new(&settings);
+    let room = budgeter.context_room(&messages);
+    while !hits.is_empty() && budgeter.message_tokens(&context_message(&hits)) > room {
+        hits.pop();
+    }
+
+    let mut messages = messages;
+    if !hits.is_empty() {
+        messages.insert(question, context_message(&hits));
+    }
+    let mode = ChatMode::Chat { stream: true, summarize_history: false, profile: None };
+    let content = run_chat(&app, &state, &request_id, messages, mode).await?;
+
+    let citations = hits
+        .into_iter()
+        .enumerate()
+        .map(|(i, hit)| Citation {
+            index: i + 1,
+            path: hit.path,
+            heading: hit.heading,
+            start_line: hit.start_line,
+            score: hit.score,
+        })
+        .collect();
+    Ok(ContextualAnswer { content, citations })
+}
+
+// A system message listing the excerpts as numbered sources
+fn context_message(hits: &[ChunkHit]) -> ChatMessage {
+    let mut content = String::from(
+        "Answer using the following excerpts from the user's notes where they are relevant. \
+         Cite them inline by number, like [1]. If they don't contain the answer, say so.\n",
+    );
+    for (i, hit) in hits.iter().enumerate() {
+        let source = match &hit.heading {
+            Some(heading) => format!("{} › {}", hit.path, heading),
+            None => hit.path.clone(),
+        };
+        content.push_str(&format!("\n[{}] {}\n{}\n", i + 1, source, hit.text));
+    }
+    ChatMessage {
+        role: "system".to_string(),
+        content,
+    }
+}
+
+/// Make `request_id` stoppable with `cancel_ai_chat`. The caller must remove
+/// it from `ai_requests` when done.
+pub async fn register_request(
+    state: &crate::AppState,
+    request_id: &str,
+) -> Result<oneshot::Receiver<()>, String> {
+    let (cancel_tx, cancel_rx) = oneshot::channel();
+    let mut requests = state.ai_requests.lock().await;
+    if requests.contains_key(request_id) {
+        return Err(format!("A request with id {} is already running", request_id));
+    }
+    requests.insert(request_id.to_string(), cancel_tx);
+    Ok(cancel_rx)
+}
+
+pub enum ChatMode {
+    /// With `summarize_history`, earlier turns that don't fit are summarized
+    /// instead of dropped; `profile` overrides the active AI profile
+    Chat { stream: bool, summarize_history: bool, profile: Option<String> },
+    /// Let the model call vault tools, recording them in a stored conversation
+    Agent { conversation_id: Option<String> },
+}
+
+/// Run a chat that `cancel_ai_chat` can stop, streaming it as `ai-stream-chunk`
+/// events and reporting its end as a done, error or cancelled chunk.
+pub async fn run_chat(
+    app: &AppHandle,
+    state: &crate::AppState,
+    request_id: &str,
+    messages: Vec<ChatMessage>,
+    mode: ChatMode,
+) -> Result<String, String> {
+    println!("Starting AI chat {} with {} messages", request_id, messages.len());
+
+    let cancel_rx = register_request(state, request_id).await?;
+
+    // Dropping the chat future on cancel aborts the HTTP stream and frees the
+    // connection; what arrived so far is kept in `partial`
+    let mut partial = String::new();
+    let chat = async {
+        let settings = match &mode {
+            ChatMode::Chat { profile: Some(profile), .. } => get_ai_profile(app.clone(), profile.clone()).await?,
+            _ => get_ai_settings(app.clone())
+                .await?
+                .ok_or("No AI settings configured")?,
+        };
+        println!("Got settings: endpoint={}, model={}", settings.endpoint, settings.model);
+
+        let summarize = matches!(mode, ChatMode::Chat { summarize_history: true, .. });
+        let messages = fit_to_window(app, request_id, &settings, messages, summarize).await?;
+        match mode {
+            ChatMode::Chat { stream, .. } => {
+                stream_chat(app, request_id, settings, messages, stream, &mut partial).await
+            }
+            ChatMode::Agent { conversation_id } => {
+                agent_chat(app, state, request_id, settings, messages, conversation_id.as_deref(), &mut partial).await
+            }
+        }
+    };
+    let result = tokio::select! {
+        result = chat => Some(result),
+        _ = cancel_rx => None,
+    };
+    state.ai_requests.lock().await.remove(request_id);
+
+    let Some(result) = result else {
+        println!("Chat {} cancelled after {} chars", request_id, partial.len());
+        emit_chunk(app, StreamChunk::cancelled(request_id, partial.clone()));
+        return Ok(partial);
+    };
+
+    match result.map(|_| partial) {
+        Ok(content) => {
+            println!("Chat {} finished: {} chars", request_id, content.len());
+            emit_chunk(app, StreamChunk::done(request_id));
+            Ok(content)
+        }
+        Err(e) => {
+            println!("Chat {} failed: {}", request_id, e);
+            emit_chunk(app, StreamChunk::error(request_id, e.clone()));
+            Err(e)
+        }
+    }
+}
+
+// Trims the chat to the model's context window and reports the budget,
+// failing before anything is sent if the latest message alone is too long
+async fn fit_to_window(
+    app: &AppHandle,
+    request_id: &str,
+    settings: &AISettings,
+    messages: Vec<ChatMessage>,
+    summarize: bool,
+) -> Result<Vec<ChatMessage>, String> {
+    let budgeter = Budgeter::new(settings);
+    let fitted = if summarize {
+        budgeter.fit_summarized(settings, messages).await?
+    } else {
+        budgeter.fit(messages)
+    };
+    let budget = fitted.budget;
+    println!(
+        "Chat {} budget: {} of {} prompt tokens ({} messages dropped, {} summarized, {} context trimmed)",
+        request_id,
+        budget.total,
+        budget.prompt_limit,
+        budget.dropped_messages,
+        budget.summarized_messages,
+        budget.trimmed_context
+    );
+
+    let event = ChatBudgetEvent {
+        request_id: request_id.to_string(),
+        budget: budget.clone(),
+    };
+    if let Err(e) = app.emit(ai_budget::BUDGET_EVENT, event) {
+        println!("Failed to emit chat budget: {}", e);
+    }
+
+    if !budget.fits {
+        return Err(format!(
+            "The message is too long for {}: about {} tokens, with room for {}. \
+             Shorten it, or set a larger context window in AI settings.",
+            budget.model, budget.total, budget.prompt_limit
+        ));
+    }
+    Ok(fitted.messages)
+}
+
+/// Stop an in-flight `send_ai_chat`. Returns false if no chat with that id
+/// is running.
+#[tauri::command]
+pub async fn cancel_ai_chat(
+    request_id: String,
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<bool, String> {
+    println!("Cancelling AI chat {}", request_id);
+    match state.ai_requests.lock().await.remove(&request_id) {
+        Some(cancel_tx) => Ok(cancel_tx.send(()).is_ok()),
+        None => Ok(false),
+    }
+}
+
+// Streams the reply into `full_content` as it arrives. Without `stream` the
+// reply is fetched in one piece and sent as a single chunk, for proxies that
+// buffer or reject streamed responses
+async fn stream_chat(
+    app: &AppHandle,
+    request_id: &str,
+    settings: AISettings,
+    messages: Vec<ChatMessage>,
+    stream: bool,
+    full_content: &mut String,
+) -> Result<(), String> {
     if messages.is_empty() {
         println!("ERROR: Messages array is empty!");
         println!("This likely means the JavaScript array was not properly serialized");
         return Err("No messages provided".to_string());
     }
-    
+
     for (i, msg) in messages.iter().enumerate() {
         println!("Message {}: role='{}', content_length={}", i, msg.role, msg.content.len());
-        println!("  Content preview: {}", &msg.content[..msg.content.len().min(50)]);
     }
-    
-    // Get settings
-    let settings = match get_ai_settings(app).await? {
-        Some(s) => {
-            println!("Got settings: endpoint={}, model={}", s.endpoint, s.model);
-            s
-        },
-        None => {
-            return Err("No AI settings configured".to_string());
-        }
-    };
-    
-    // Build request
-    let client = reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(30))
-        .build()
-        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
-    
-    let url = format!("{}/chat/completions", settings.endpoint.trim_end_matches('/'));
-    
-    // Clone messages to ensure they're not moved
-    let messages_clone = messages.clone();
-    println!("Messages before JSON: {} items", messages_clone.len());
-    
-    let mut request_body = serde_json::json!({
-        "model": settings.model,
-        "messages": messages_clone,
-        "temperature": settings.temperature,
-        "max_tokens": settings.max_tokens,
-        "stream": false
-    });
-    
-    println!("Request body: {}", serde_json::to_string_pretty(&request_body).unwrap());
-    
-    // Handle Ollama's native API format (only if not using OpenAI compatibility endpoint)
-    if (settings.endpoint.contains("ollama") || settings.endpoint.contains("11434")) 
-        && !settings.endpoint.contains("/v1") 
-        && !settings.endpoint.contains("chat/completions") {
-        println!("Detected Ollama native endpoint, reformatting request...");
-        // Ollama uses a different format for its native API
-        let prompt = messages.last()
-            .map(|m| m.content.clone())
-            .unwrap_or_default();
-        
-        request_body = serde_json::json!({
-            "model": settings.model,
-            "prompt": prompt,
-            "stream": false
-        });
-    } else {
-        println!("Using OpenAI-compatible format");
+
+    let provider = ai_provider::provider_for(settings);
+    if !stream {
+        let content = provider.chat(&messages).await?;
+        full_content.push_str(&content);
+        emit_chunk(app, StreamChunk::content(request_id, content));
+        return Ok(());
     }
-    
-    let mut request = client.post(&url);
-    
-    // Add auth header if API key is present
-    if let Some(api_key) = &settings.api_key {
-        if !api_key.is_empty() {
-            request = request.header("Authorization", format!("Bearer {}", api_key));
-        }
+
+    provider
+        .stream(&messages, &mut |text: String| {
+            full_content.push_str(&text);
+            emit_chunk(app, StreamChunk::content(request_id, text));
+        })
+        .await
+}
+
+/// Event asking the user to approve a tool call that writes to the vault;
+/// answered with `confirm_ai_tool_call`
+pub const TOOL_CONFIRMATION_EVENT: &str = "ai-tool-confirmation";
+/// Event reporting each tool call an agent chat made and how it went
+pub const TOOL_CALL_EVENT: &str = "ai-tool-call";
+
+// Bounds the model's tool round-trips so a confused model can't loop forever
+const MAX_AGENT_STEPS: usize = 8;
+const CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(300);
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ToolConfirmationRequest {
+    pub request_id: String,
+    pub confirmation_id: String,
+    pub call: ToolCall,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ToolCallEvent {
+    pub request_id: String,
+    pub call: ToolCall,
+    /// "ok", "error" or "declined"
+    pub status: String,
+    pub result: String,
+}
+
+/// Like `send_ai_chat`, but the model may search, read and list notes, and
+/// create or append to notes once the user approves each write. Tool calls
+/// are reported as `ai-tool-call` events and, with `conversation_id`,
+/// recorded in that conversation along with the question and the answer.
+#[tauri::command]
+pub async fn send_ai_agent_chat(
+    app: AppHandle,
+    messages: Vec<ChatMessage>,
+    request_id: Option<String>,
+    conversation_id: Option<String>,
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<String, String> {
+    let request_id = request_id.unwrap_or_else(new_request_id);
+    run_chat(&app, &state, &request_id, messages, ChatMode::Agent { conversation_id }).await
+}
+
+/// Answer an `ai-tool-confirmation` event. Returns false if the request is
+/// no longer waiting, e.g. because the chat was cancelled.
+#[tauri::command]
+pub async fn confirm_ai_tool_call(
+    confirmation_id: String,
+    approved: bool,
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<bool, String> {
+    println!("Tool call {} {}", confirmation_id, if approved { "approved" } else { "declined" });
+    match state.tool_confirmations.lock().await.remove(&confirmation_id) {
+        Some(reply_tx) => Ok(reply_tx.send(approved).is_ok()),
+        None => Ok(false),
     }
-    
-    request = request
-        .header("Content-Type", "application/json")
-        .json(&request_body);
-    
-    println!("Final request body being sent: {}", serde_json::to_string(&request_body).unwrap());
-    
-    // Send request and get response
-    let response = match request.send().await {
-        Ok(resp) => resp,
-        Err(e) => {
-            return Err(format!("Failed to connect: {}", e));
+}
+
+// Appends to the chat's conversation, if it has one. A failed write is
+// logged rather than failing the chat
+async fn record(state: &crate::AppState, conversation_id: Option<&str>, message: ConversationMessage) {
+    let Some(id) = conversation_id else { return };
+    let Ok(owner) = state.auth.current_user().await else { return };
+    let Some(vault) = state.vault.lock().await.clone() else { return };
+    if let Err(e) = ConversationStore::new(&vault, owner).append(id, message) {
+        println!("Failed to record message in conversation {}: {}", id, e);
+    }
+}
+
+fn conversation_message(role: &str, content: String) -> ConversationMessage {
+    ConversationMessage {
+        role: role.to_string(),
+        content,
+        timestamp: String::new(),
+        model: None,
+        provider: None,
+        tool_call: None,
+    }
+}
+
+// Takes a confirmation out of the pending ones however the wait ends,
+// including the chat being cancelled and its future dropped
+struct PendingConfirmation {
+    confirmations: Arc<Mutex<HashMap<String, oneshot::Sender<bool>>>>,
+    id: String,
+}
+
+impl Drop for PendingConfirmation {
+    fn drop(&mut self) {
+        let id = std::mem::take(&mut self.id);
+        // Drop can't wait for the lock, so a busy map is cleaned up later
+        match self.confirmations.try_lock() {
+            Ok(mut confirmations) => {
+                confirmations.remove(&id);
+            }
+            Err(_) => {
+                let confirmations = self.confirmations.clone();
+                tauri::async_runtime::spawn(async move {
+                    confirmations.lock().await.remove(&id);
+                });
+            }
         }
+    }
+}
+
+// Asks the frontend to approve a write and waits for the answer; no answer
+// in time counts as declined
+async fn confirm_tool_call(app: &AppHandle, state: &crate::AppState, request_id: &str, call: &ToolCall) -> bool {
+    let confirmation_id = format!("{}-{:08x}", request_id, rand::random::<u32>());
+    let (reply_tx, reply_rx) = oneshot::channel();
+    state
+        .tool_confirmations
+        .lock()
+        .await
+        .insert(confirmation_id.clone(), reply_tx);
+    let _pending = PendingConfirmation {
+        confirmations: state.tool_confirmations.clone(),
+        id: confirmation_id.clone(),
     };
-    
-    if !response.status().is_success() {
-        let status = response.status();
-        let error_text = response.text().await.unwrap_or_default();
-        println!("API Error - Status: {}, Error: {}", status, error_text);
-        println!("Request URL was: {}", url);
-        println!("Request had {} messages", messages.len());
-        return Err(format!("API error ({}): {}", status, error_text));
+
+    let request = ToolConfirmationRequest {
+        request_id: request_id.to_string(),
+        confirmation_id: confirmation_id.clone(),
+        call: call.clone(),
+    };
+    if let Err(e) = app.emit(TOOL_CONFIRMATION_EVENT, request) {
+        println!("Failed to emit tool confirmation request: {}", e);
     }
-    
-    // Parse JSON response
-    let response_text = response.text().await
-        .map_err(|e| format!("Failed to read response: {}", e))?;
-    
-    let json: serde_json::Value = serde_json::from_str(&response_text)
-        .map_err(|e| format!("Failed to parse JSON: {}", e))?;
-    
-    // Extract content from response
-    if let Some(choices) = json.get("choices").and_then(|c| c.as_array()) {
-        if let Some(first_choice) = choices.first() {
-            if let Some(content) = first_choice
-                .get("message")
-                .and_then(|m| m.get("content"))
-                .and_then(|c| c.as_str())
-            {
-                return Ok(content.to_string());
+
+    matches!(
+        tokio::time::timeout(CONFIRMATION_TIMEOUT, reply_rx).await,
+        Ok(Ok(true))
+    )
+}
+
+async fn run_tool(app: &AppHandle, state: &crate::AppState, request_id: &str, call: &ToolCall) -> (&'static str, String) {
+    if ai_tools::is_write(&call.name) && !confirm_tool_call(app, state, request_id, call).await {
+        return ("declined", "The user declined this action.".to_string());
+    }
+    match ai_tools::execute(state, call).await {
+        Ok(result) => ("ok", result),
+        Err(e) => ("error", format!("Error: {}", e)),
+    }
+}
+
+// The tool-calling loop: ask the model, run the tools it asks for, hand back
+// the results, until it answers in plain text
+async fn agent_chat(
+    app: &AppHandle,
+    state: &crate::AppState,
+    request_id: &str,
+    settings: AISettings,
+    messages: Vec<ChatMessage>,
+    conversation_id: Option<&str>,
+    full_content: &mut String,
+) -> Result<(), String> {
+    if messages.is_empty() {
+        return Err("No messages provided".to_string());
+    }
+
+    let model = settings.model.clone();
+    let provider_name = settings.provider.name();
+    let provider = ai_provider::provider_for(settings);
+    let tools = ai_tools::tools();
+
+    if let Some(id) = conversation_id {
+        let owner = state.auth.current_user().await?;
+        let vault = state.vault.lock().await.clone().ok_or("No vault opened")?;
+        ConversationStore::new(&vault, owner).load(id)?;
+    }
+    if let Some(question) = messages.last().filter(|m| m.role == "user") {
+        record(state, conversation_id, conversation_message("user", question.content.clone())).await;
+    }
+
+    let mut transcript: Vec<AgentMessage> = messages.into_iter().map(AgentMessage::Chat).collect();
+    for step in 0..MAX_AGENT_STEPS {
+        let (content, calls) = match provider.chat_with_tools(&transcript, &tools).await? {
+            AgentTurn::Reply(content) => (content, Vec::new()),
+            AgentTurn::ToolCalls { content, calls } => (content, calls),
+        };
+
+        if !content.is_empty() {
+            full_content.push_str(&content);
+            emit_chunk(app, StreamChunk::content(request_id, content.clone()));
+        }
+        if calls.is_empty() {
+            let mut reply = conversation_message("assistant", content);
+            reply.model = Some(model);
+            reply.provider = Some(provider_name.to_string());
+            record(state, conversation_id, reply).await;
+            return Ok(());
+        }
+
+        println!("Agent chat {} step {}: {} tool calls", request_id, step + 1, calls.len());
+        transcript.push(AgentMessage::ToolCalls {
+            content,
+            calls: calls.clone(),
+        });
+        for call in calls {
+            let (status, result) = run_tool(app, state, request_id, &call).await;
+            println!("Tool {} -> {}", call.name, status);
+
+            let event = ToolCallEvent {
+                request_id: request_id.to_string(),
+                call: call.clone(),
+                status: status.to_string(),
+                result: result.clone(),
+            };
+            if let Err(e) = app.emit(TOOL_CALL_EVENT, event) {
+                println!("Failed to emit tool call event: {}", e);
             }
+
+            let mut entry = conversation_message("tool", result.clone());
+            entry.tool_call = Some(ToolCallRecord {
+                name: call.name.clone(),
+                arguments: call.arguments.clone(),
+                status: status.to_string(),
+            });
+            record(state, conversation_id, entry).await;
+
+            transcript.push(AgentMessage::ToolResult {
+                call_id: call.id,
+                name: call.name,
+                content: result,
+            });
         }
     }
-    
-    // Handle Ollama format
-    if let Some(response_content) = json.get("response").and_then(|r| r.as_str()) {
-        return Ok(response_content.to_string());
-    }
-    
-    Err("No content found in response".to_string())
+
+    Err(format!(
+        "Stopped after {} rounds of tool calls without an answer",
+        MAX_AGENT_STEPS
+    ))
 }
 
 #[tauri::command]
@@ -208,8 +662,13 @@ pub async fn search_notes_by_name(
     
     match &*vault_lock {
         Some(vault) => {
-            let files = vault.list_markdown_files()
-                .map_err(|e| format!("Failed to list files: {}", e))?;
+            // Prefer the search index's note list over re-walking the vault on every keystroke
+            let index_lock = state.search_index.lock().await;
+            let files = match &*index_lock {
+                Some(index) => index.note_paths().map(|p| vault.path().join(p)).collect(),
+                None => vault.list_markdown_files()
+                    .map_err(|e| format!("Failed to list files: {}", e))?,
+            };
             
             let mut results = Vec::new();
             
@@ -219,7 +678,9 @@ pub async fn search_notes_by_name(
                     .unwrap_or("");
                 
                 // Fuzzy match
-                if file_name.to_lowercase().contains(&search_term.to_lowercase()) {
+                if file_name.to_lowercase().contains(&search_term.to_lowercase())
+                    && state.auth.allows(vault.path(), &file, VaultAction::Read).await
+                {
                     results.push(crate::NoteSearchResult {
                         name: file_name.to_string(),
                         path: file.strip_prefix(vault.path())
@@ -0,0 +1,243 @@
+// ai_tools.rs - Vault operations the chat model can call as tools
+
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+
+use crate::ai_stream::ChatMessage;
+use crate::auth::VaultAction;
+use crate::vault::{self, relative_path};
+
+// Long notes are cut so one read can't fill the model's context
+const MAX_READ_CHARS: usize = 12000;
+const DEFAULT_SEARCH_LIMIT: usize = 8;
+
+/// A tool offered to the model.
+pub struct ToolSpec {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// JSON Schema for the arguments
+    pub parameters: Value,
+    /// Writes to the vault, so the user must approve each call
+    pub writes: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolCall {
+    pub id: String,
+    pub name: String,
+    pub arguments: Value,
+}
+
+/// One entry of an agent transcript. Providers translate these into their
+/// own wire format.
+#[derive(Debug, Clone)]
+pub enum AgentMessage {
+    Chat(ChatMessage),
+    /// An assistant turn that asked for tools, with any text it also said
+    ToolCalls { content: String, calls: Vec<ToolCall> },
+    ToolResult { call_id: String, name: String, content: String },
+}
+
+/// What the model did with its turn.
+pub enum AgentTurn {
+    Reply(String),
+    ToolCalls { content: String, calls: Vec<ToolCall> },
+}
+
+pub fn tools() -> Vec<ToolSpec> {
+    vec![
+        ToolSpec {
+            name: "search_notes",
+            description: "Full-text search over the user's notes. Returns matching note paths with snippets.",
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "query": { "type": "string", "description": "Words or a \"quoted phrase\" to find" },
+                    "limit": { "type": "integer", "description": "Maximum number of notes to return" }
+                },
+                "required": ["query"]
+            }),
+            writes: false,
+        },
+        ToolSpec {
+            name: "read_note",
+            description: "Read the markdown content of a note.",
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string", "description": "Note path relative to the vault, e.g. Projects/Plan.md" }
+                },
+                "required": ["path"]
+            }),
+            writes: false,
+        },
+        ToolSpec {
+            name: "list_tag",
+            description: "List the notes tagged with a tag or any tag nested below it.",
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "tag": { "type": "string", "description": "Tag without the leading #, e.g. project/active" }
+                },
+                "required": ["tag"]
+            }),
+            writes: false,
+        },
+        ToolSpec {
+            name: "create_note",
+            description: "Create a new note. Fails if the note already exists.",
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string", "description": "Note path relative to the vault, ending in .md" },
+                    "content": { "type": "string", "description": "Markdown content" }
+                },
+                "required": ["path", "content"]
+            }),
+            writes: true,
+        },
+        ToolSpec {
+            name: "append_to_note",
+            description: "Append markdown to the end of an existing note.",
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string", "description": "Note path relative to the vault" },
+                    "content": { "type": "string", "description": "Markdown to append" }
+                },
+                "required": ["path", "content"]
+            }),
+            writes: true,
+        },
+    ]
+}
+
+pub fn is_write(name: &str) -> bool {
+    tools().iter().any(|tool| tool.name == name && tool.writes)
+}
+
+/// Tools in the OpenAI `tools` format, which Ollama's native API shares.
+pub fn function_tools(tools: &[ToolSpec]) -> Vec<Value> {
+    tools
+        .iter()
+        .map(|tool| {
+            json!({
+                "type": "function",
+                "function": {
+                    "name": tool.name,
+                    "description": tool.description,
+                    "parameters": tool.parameters
+                }
+            })
+        })
+        .collect()
+}
+
+fn string_arg<'a>(arguments: &'a Value, name: &str) -> Result<&'a str, String> {
+    arguments
+        .get(name)
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| format!("Missing argument: {}", name))
+}
+
+// Notes only, never app data; a model asking for `Plan` means `Plan.md`
+fn note_path(arguments: &Value) -> Result<PathBuf, String> {
+    let mut path = relative_path(string_arg(arguments, "path")?)?;
+    if path.extension().map_or(true, |ext| ext != "md") {
+        let mut with_extension = path.into_os_string();
+        with_extension.push(".md");
+        path = with_extension.into();
+    }
+    if !vault::is_note(&path) {
+        return Err(format!("Not a note: {}", path.display()));
+    }
+    Ok(path)
+}
+
+/// Run a tool call against the open vault. Errors are meant for the model,
+/// which can usually recover from them.
+pub async fn execute(state: &crate::AppState, call: &ToolCall) -> Result<String, String> {
+    let vault_lock = state.vault.lock().await;
+    let Some(vault) = &*vault_lock else {
+        return Err("No vault opened".to_string());
+    };
+
+    match call.name.as_str() {
+        "search_notes" => {
+            let query = string_arg(&call.arguments, "query")?;
+            let limit = call
+                .arguments
+                .get("limit")
+                .and_then(|v| v.as_u64())
+                .map_or(DEFAULT_SEARCH_LIMIT, |l| l as usize);
+
+            let index_lock = state.search_index.lock().await;
+            let index = index_lock.as_ref().ok_or("Search index not available")?;
+            let access = state.auth.vault_access(vault.path()).await;
+            let hits: Vec<Value> = index
+                .search(query, limit, |path| access.allows(Path::new(path), VaultAction::Read))
+                .into_iter()
+                .map(|hit| {
+                    json!({
+                        "path": hit.path,
+                        "snippets": hit.snippets.iter().map(|s| s.text.as_str()).collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            Ok(Value::Array(hits).to_string())
+        }
+        "read_note" => {
+            let path = note_path(&call.arguments)?;
+            state.auth.authorize(vault.path(), &path, VaultAction::Read).await?;
+            let content = vault
+                .read_file(&path)
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            if content.chars().count() > MAX_READ_CHARS {
+                let truncated: String = content.chars().take(MAX_READ_CHARS).collect();
+                Ok(format!("{}\n…[truncated]", truncated))
+            } else {
+                Ok(content)
+            }
+        }
+        "list_tag" => {
+            let tag = string_arg(&call.arguments, "tag")?.trim_start_matches('#');
+            let tag_lock = state.tag_index.lock().await;
+            let index = tag_lock.as_ref().ok_or("Tag index not available")?;
+            let notes = state.auth.readable(vault.path(), index.notes_with_tag(tag)).await;
+            Ok(json!(notes).to_string())
+        }
+        "create_note" => {
+            let path = note_path(&call.arguments)?;
+            let content = string_arg(&call.arguments, "content")?;
+            state.auth.authorize(vault.path(), &path, VaultAction::Write).await?;
+            if vault.path().join(&path).exists() {
+                return Err(format!("{} already exists", path.display()));
+            }
+            vault
+                .write_file(&path, content)
+                .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+            println!("🤖 Created note {:?}", path);
+            Ok(format!("Created {}", path.display()))
+        }
+        "append_to_note" => {
+            let path = note_path(&call.arguments)?;
+            let addition = string_arg(&call.arguments, "content")?;
+            state.auth.authorize(vault.path(), &path, VaultAction::Write).await?;
+            let existing = vault
+                .read_file(&path)
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+
+            let mut content = existing.trim_end().to_string();
+            content.push_str("\n\n");
+            content.push_str(addition.trim_end());
+            content.push('\n');
+            vault
+                .write_file(&path, &content)
+                .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+            println!("🤖 Appended to note {:?}", path);
+            Ok(format!("Appended to {}", path.display()))
+        }
+        other => Err(format!("Unknown tool: {}", other)),
+    }
+}
@@ -0,0 +1,271 @@
+// anthropic.rs - Anthropic Messages API backend
+
+use futures_util::future::BoxFuture;
+use futures_util::FutureExt;
+use serde::Deserialize;
+
+use crate::ai_provider::{self, AiProvider, StreamLine};
+use crate::ai_settings::{AISettings, ConnectionTestResult};
+use crate::ai_stream::ChatMessage;
+
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+#[derive(Debug, Deserialize)]
+struct StreamEvent {
+    #[serde(rename = "type")]
+    event_type: String,
+    delta: Option<TextDelta>,
+    error: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TextDelta {
+    text: Option<String>,
+}
+
+pub struct AnthropicProvider {
+    settings: AISettings,
+}
+
+impl AnthropicProvider {
+    pub fn new(settings: AISettings) -> Self {
+        Self { settings }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/{}", self.settings.endpoint.trim_end_matches('/'), path)
+    }
+
+    fn with_auth(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        let request = request.header("anthropic-version", ANTHROPIC_VERSION);
+        match self.settings.api_key.as_deref().filter(|key| !key.is_empty()) {
+            Some(api_key) => request.header("x-api-key", api_key),
+            None => request,
+        }
+    }
+
+    fn body(&self, messages: &[ChatMessage], max_tokens: u32, stream: bool) -> serde_json::Value {
+        // System prompts are a top-level field rather than a message role
+        let system: Vec<&str> = messages
+            .iter()
+            .filter(|m| m.role == "system")
+            .map(|m| m.content.as_str())
+            .collect();
+        let turns: Vec<serde_json::Value> = messages
+            .iter()
+            .filter(|m| m.role != "system")
+            .map(|m| {
+                let role = if m.role == "assistant" { "assistant" } else { "user" };
+                serde_json::json!({ "role": role, "content": m.content })
+            })
+            .collect();
+
+        let mut body = serde_json::json!({
+            "model": self.settings.model,
+            "messages": turns,
+            "max_tokens": max_tokens,
+            "temperature": self.settings.temperature.min(1.0),
+            "stream": stream
+        });
+        if !system.is_empty() {
+            body["system"] = serde_json::Value::String(system.join("\n\n"));
+        }
+        body
+    }
+
+    fn chat_request(
+        &self,
+        client: &reqwest::Client,
+        messages: &[ChatMessage],
+        stream: bool,
+    ) -> reqwest::RequestBuilder {
+        self.with_auth(client.post(self.url("messages")))
+            .header("Content-Type", "application/json")
+            .json(&self.body(messages, self.settings.max_tokens, stream))
+    }
+}
+
+fn parse_stream_line(line: &str) -> StreamLine {
+    let Some(data) = ai_provider::sse_data(line) else {
+        return StreamLine::Skip;
+    };
+
+    match serde_json::from_str::<StreamEvent>(data) {
+        Ok(event) => match event.event_type.as_str() {
+            "content_block_delta" => match event.delta.and_then(|d| d.text) {
+                Some(text) if !text.is_empty() => StreamLine::Content(text),
+                _ => StreamLine::Skip,
+            },
+            "message_stop" => StreamLine::Done,
+            "error" => StreamLine::Error(
+                event
+                    .error
+                    .map(|e| {
+                        e.get("message")
+                            .and_then(|m| m.as_str())
+                            .map(|m| m.to_string())
+                            .unwrap_or_else(|| e.to_string())
+                    })
+                    .unwrap_or_else(|| "Unknown error".to_string()),
+            ),
+            // message_start, content_block_start/stop, message_delta, ping
+            _ => StreamLine::Skip,
+        },
+        Err(e) => {
+            println!("Skipping unparseable stream data: {} ({})", data, e);
+            StreamLine::Skip
+        }
+    }
+}
+
+impl AiProvider for AnthropicProvider {
+    fn chat<'a>(&'a self, messages: &'a [ChatMessage]) -> BoxFuture<'a, Result<String, String>> {
+        async move {
+            let client = ai_provider::streaming_client()?;
+            let response = ai_provider::send_checked(self.chat_request(&client, messages, false)).await?;
+            let json = ai_provider::read_json(response).await?;
+
+            let blocks = json
+                .get("content")
+                .and_then(|c| c.as_array())
+                .ok_or_else(|| "No content found in response".to_string())?;
+            Ok(blocks
+                .iter()
+                .filter_map(|block| block.get("text").and_then(|t| t.as_str()))
+                .collect())
+        }
+        .boxed()
+    }
+
+    fn stream<'a>(
+        &'a self,
+        messages: &'a [ChatMessage],
+        on_text: &'a mut (dyn FnMut(String) + Send),
+    ) -> BoxFuture<'a, Result<(), String>> {
+        async move {
+            println!("Using Anthropic Messages API, streaming SSE...");
+            let client = ai_provider::streaming_client()?;
+            let response = ai_provider::send_checked(self.chat_request(&client, messages, true)).await?;
+            ai_provider::read_stream(response, parse_stream_line, on_text).await
+        }
+        .boxed()
+    }
+
+    fn embed<'a>(&'a self, _texts: &'a [String]) -> BoxFuture<'a, Result<Vec<Vec<f32>>, String>> {
+        async move {
+            Err("Anthropic has no embeddings API; use an OpenAI-compatible or Ollama profile for note embeddings".to_string())
+        }
+        .boxed()
+    }
+
+    fn list_models(&self) -> BoxFuture<'_, Result<Vec<String>, String>> {
+        async move {
+            let client = ai_provider::test_client()?;
+            let response = ai_provider::send_checked(self.with_auth(client.get(self.url("models")))).await?;
+            let json = ai_provider::read_json(response).await?;
+
+            Ok(json
+                .get("data")
+                .and_then(|d| d.as_array())
+                .map(|models| {
+                    models
+                        .iter()
+                        .filter_map(|m| m.get("id").and_then(|id| id.as_str()))
+                        .map(|id| id.to_string())
+                        .collect()
+                })
+                .unwrap_or_default())
+        }
+        .boxed()
+    }
+
+    fn test_connection(&self) -> BoxFuture<'_, ConnectionTestResult> {
+        async move {
+            let mut result = ConnectionTestResult::pending();
+            let client = match ai_provider::test_client() {
+                Ok(client) => client,
+                Err(e) => {
+                    result.overall_status.message = e;
+                    return result;
+                }
+            };
+
+            if !ai_provider::check_endpoint(&client, &self.settings.endpoint, &mut result).await {
+                return result;
+            }
+
+            // A one-token message checks the key and the model together
+            let test_messages = [ChatMessage {
+                role: "user".to_string(),
+                content: "Hi".to_string(),
+            }];
+            match self
+                .with_auth(client.post(self.url("messages")))
+                .header("Content-Type", "application/json")
+                .json(&self.body(&test_messages, 1, false))
+                .send()
+                .await
+            {
+                Ok(response) if response.status() == 401 || response.status() == 403 => {
+                    result.auth_status.message = "Invalid API key".to_string();
+                    result.overall_status.message = "Authentication failed".to_string();
+                    return result;
+                }
+                Ok(response) if response.status() == 404 => {
+                    result.auth_status.success = true;
+                    result.auth_status.message = "API key is valid".to_string();
+                    result.model_status.message = format!("Model '{}' not found", self.settings.model);
+                    result.overall_status.message = "Model not available".to_string();
+                    return result;
+                }
+                Ok(response) if response.status().is_success() => {
+                    result.auth_status.success = true;
+                    result.auth_status.message = "API key is valid".to_string();
+                    result.model_status.success = true;
+                    result.model_status.message = "Model is available".to_string();
+                }
+                Ok(response) => {
+                    let status = response.status();
+                    let error_text = response.text().await.unwrap_or_default();
+                    result.auth_status.message = format!("Unexpected response ({}): {}", status, error_text);
+                }
+                Err(e) => {
+                    result.auth_status.message = format!("Failed to test authentication: {}", e);
+                }
+            }
+
+            result.finish();
+            result
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stream_lines_carry_text_deltas() {
+        let line = r#"data: {"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Hi"}}"#;
+        assert_eq!(parse_stream_line(line), StreamLine::Content("Hi".to_string()));
+    }
+
+    #[test]
+    fn stream_ends_on_message_stop() {
+        assert_eq!(parse_stream_line(r#"data: {"type":"message_stop"}"#), StreamLine::Done);
+    }
+
+    #[test]
+    fn stream_errors_are_reported() {
+        let line = r#"data: {"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#;
+        assert_eq!(parse_stream_line(line), StreamLine::Error("Overloaded".to_string()));
+    }
+
+    #[test]
+    fn other_events_are_skipped() {
+        assert_eq!(parse_stream_line("event: content_block_delta"), StreamLine::Skip);
+        assert_eq!(parse_stream_line(r#"data: {"type":"ping"}"#), StreamLine::Skip);
+        assert_eq!(parse_stream_line("data: {broken"), StreamLine::Skip);
+    }
+}
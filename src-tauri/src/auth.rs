@@ -2,17 +2,119 @@
 
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
 use chrono::{DateTime, Utc, Duration};
 use sha2::{Sha256, Digest};
 use rand::{thread_rng, Rng};
-use std::sync::Arc;
+use argon2::password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::{Algorithm, Argon2, Params, Version};
+use regex::Regex;
+use std::sync::OnceLock;
+use tauri::{AppHandle, Manager};
 use tokio::sync::RwLock;
 
+use crate::totp;
+
+// Users, sessions and vault permissions, under the app config directory
+pub const AUTH_FILE: &str = "auth.json";
+
+const MIN_PASSWORD_LEN: usize = 8;
+// Lets a user manage other users
+const ADMIN_PERMISSION: &str = "admin";
+// Bumped when stored permissions need migrating on load
+const STORE_VERSION: u32 = 1;
+// How often expired sessions and stale failed logins are purged
+const SWEEP_INTERVAL_SECS: u64 = 5 * 60;
+// Activity is written to disk at most this often per session
+const TOUCH_SAVE_SECS: i64 = 60;
+// Time between the password and the two-factor code
+const CHALLENGE_MINUTES: i64 = 5;
+// Where `Archive` and `archive` are the same folder, rules must match both
+const CASE_INSENSITIVE_FS: bool = cfg!(any(target_os = "macos", target_os = "windows"));
+
+// Built-in account for trying the app out, only in debug builds and only
+// while no real users exist. It is never written to disk.
+const DEMO_USERNAME: &str = "demo";
+const DEMO_PASSWORD: &str = "demo123";
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct User {
     pub username: String,
     pub password_hash: String,
     pub permissions: Vec<String>,
+    #[serde(default)]
+    pub two_factor: Option<TwoFactor>,
+}
+
+impl User {
+    fn two_factor_enabled(&self) -> bool {
+        self.two_factor.as_ref().map_or(false, |tf| tf.enabled)
+    }
+}
+
+/// A user's TOTP authenticator, asked for after the password once enabled.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TwoFactor {
+    /// Base32 shared secret
+    pub secret: String,
+    /// False until a code from the app confirms the enrolment
+    pub enabled: bool,
+    /// Hashes of the unused recovery codes
+    #[serde(default)]
+    pub recovery_codes: Vec<String>,
+    /// Time step of the last accepted code; a code works only once
+    #[serde(default)]
+    pub last_step: u64,
+}
+
+impl TwoFactor {
+    // Accept an authenticator code, or use up a recovery code
+    fn accept(&mut self, code: &str) -> bool {
+        if totp::is_code(code) {
+            let now = Utc::now().timestamp().max(0) as u64;
+            return match totp::verify(&self.secret, code, now, self.last_step) {
+                Some(step) => {
+                    self.last_step = step;
+                    true
+                }
+                None => false,
+            };
+        }
+        let hash = totp::hash_recovery_code(code);
+        match self
+            .recovery_codes
+            .iter()
+            .position(|stored| constant_time_eq(stored.as_bytes(), hash.as_bytes()))
+        {
+            Some(index) => {
+                self.recovery_codes.remove(index);
+                println!("🔑 Recovery code used; {} left", self.recovery_codes.len());
+                true
+            }
+            None => false,
+        }
+    }
+
+    // New recovery codes, replacing any old ones; only the hashes are kept
+    fn reset_recovery_codes(&mut self) -> Vec<String> {
+        let codes = totp::generate_recovery_codes();
+        self.recovery_codes = codes.iter().map(|c| totp::hash_recovery_code(c)).collect();
+        codes
+    }
+}
+
+/// What an authenticator app needs to enrol; shown once.
+#[derive(Debug, Clone, Serialize)]
+pub struct TwoFactorSetup {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+// A password accepted, waiting for the second factor
+#[derive(Debug, Clone)]
+struct Challenge {
+    username: String,
+    expires_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -20,13 +122,160 @@ pub struct Session {
     pub token: String,
     pub username: String,
     pub created_at: DateTime<Utc>,
+    /// Absolute end of the session, however active it is
+    pub expires_at: DateTime<Utc>,
+    /// Last use; the session ends after `SessionPolicy::idle_minutes` without one
+    #[serde(default = "Utc::now")]
+    pub last_seen_at: DateTime<Utc>,
+    /// Trades for a new session with `refresh_session`, once, until
+    /// `refresh_expires_at`
+    #[serde(default)]
+    pub refresh_token: Option<String>,
+    #[serde(default)]
+    pub refresh_expires_at: Option<DateTime<Utc>>,
+}
+
+impl Session {
+    fn new(username: &str, policy: &SessionPolicy, refresh_expires_at: DateTime<Utc>) -> Self {
+        let now = Utc::now();
+        Session {
+            token: generate_token(),
+            username: username.to_string(),
+            created_at: now,
+            expires_at: now + Duration::hours(policy.absolute_hours),
+            last_seen_at: now,
+            refresh_token: Some(generate_token()),
+            refresh_expires_at: Some(refresh_expires_at),
+        }
+    }
+
+    fn is_live(&self, policy: &SessionPolicy, now: DateTime<Utc>) -> bool {
+        self.expires_at > now && self.last_seen_at + Duration::minutes(policy.idle_minutes) > now
+    }
+
+    fn can_refresh(&self, now: DateTime<Utc>) -> bool {
+        self.refresh_token.is_some() && self.refresh_expires_at.map_or(false, |at| at > now)
+    }
+}
+
+/// How long sessions last and how failed sign-ins are throttled.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionPolicy {
+    /// A session ends after this long without use
+    pub idle_minutes: i64,
+    /// ...and this long after sign-in whatever its use
+    pub absolute_hours: i64,
+    /// How long after sign-in a session can be refreshed
+    pub refresh_days: i64,
+    /// Failed sign-ins in a row before the account is locked
+    pub max_failed_attempts: u32,
+    pub lockout_minutes: i64,
+}
+
+impl Default for SessionPolicy {
+    fn default() -> Self {
+        Self {
+            idle_minutes: 120,
+            absolute_hours: 24,
+            refresh_days: 30,
+            max_failed_attempts: 5,
+            lockout_minutes: 15,
+        }
+    }
+}
+
+impl SessionPolicy {
+    fn validate(&self) -> Result<(), String> {
+        if self.idle_minutes < 1 || self.absolute_hours < 1 || self.refresh_days < 0 || self.lockout_minutes < 1 {
+            return Err("Session timeouts must be positive".to_string());
+        }
+        if self.max_failed_attempts < 1 {
+            return Err("Allow at least one sign-in attempt".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// A user's session as listed for management; the tokens are never sent.
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionInfo {
+    /// Stands in for the token when revoking
+    pub id: String,
+    pub username: String,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
     pub expires_at: DateTime<Utc>,
+    /// The session making the request
+    pub current: bool,
+}
+
+// Failed sign-ins since the last success, per username
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct FailedLogins {
+    count: u32,
+    last_failure: Option<DateTime<Utc>>,
+    locked_until: Option<DateTime<Utc>>,
+}
+
+/// What a vault command is about to do to a path, checked against the
+/// signed-in user's `access` for the vault.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VaultAction {
+    Read,
+    Write,
+    Delete,
+    Export,
+}
+
+impl VaultAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            VaultAction::Read => "read",
+            VaultAction::Write => "write",
+            VaultAction::Delete => "delete",
+            VaultAction::Export => "export",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VaultPermission {
+    /// Vault directory, or `*` for any vault without its own entry
     pub path: String,
-    pub access: Vec<String>, // ["read", "write"]
+    pub access: Vec<String>, // ["read", "write", "delete", "export"]
+    /// Exceptions within the vault; the last rule matching a path wins
+    #[serde(default)]
+    pub rules: Vec<PathRule>,
+}
+
+/// Access for the paths matching a vault-relative glob, e.g. `Archive/**`
+/// with `["read"]` to make the archive read-only. `*` and `?` stay within a
+/// folder, `**` spans folders.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PathRule {
+    pub pattern: String,
+    pub access: Vec<String>,
+    // `pattern` compiled on first use
+    #[serde(skip)]
+    regex: OnceLock<Regex>,
+}
+
+impl PathRule {
+    fn regex(&self) -> &Regex {
+        self.regex.get_or_init(|| glob_regex(&self.pattern))
+    }
+}
+
+impl VaultPermission {
+    // What this grants on a vault-relative path, after its path rules
+    fn access_to(&self, relative: &str) -> &[String] {
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| rule.regex().is_match(relative))
+            .map_or(&self.access, |rule| &rule.access)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -34,152 +283,1093 @@ pub struct UserPermissions {
     pub vaults: Vec<VaultPermission>,
 }
 
+/// A user as listed for management; the password hash is never sent.
+#[derive(Debug, Clone, Serialize)]
+pub struct UserInfo {
+    pub username: String,
+    pub permissions: Vec<String>,
+    pub vaults: Vec<VaultPermission>,
+    pub two_factor: bool,
+}
+
+/// Argon2id cost for new password hashes. Hashes made with other costs are
+/// redone the next time their user signs in.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct KdfParams {
+    /// Memory in KiB
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl Default for KdfParams {
+    // OWASP's recommended minimum for Argon2id
+    fn default() -> Self {
+        Self {
+            memory_kib: Params::DEFAULT_M_COST,
+            iterations: Params::DEFAULT_T_COST,
+            parallelism: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+impl KdfParams {
+    fn argon2(&self) -> Result<Argon2<'static>, String> {
+        let params = Params::new(self.memory_kib, self.iterations, self.parallelism, None)
+            .map_err(|e| format!("Invalid password hashing parameters: {}", e))?;
+        Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct AuthStore {
+    #[serde(default)]
+    version: u32,
+    users: HashMap<String, User>,
+    sessions: HashMap<String, Session>,
+    vault_permissions: HashMap<String, Vec<VaultPermission>>,
+    #[serde(default)]
+    password_hashing: KdfParams,
+    #[serde(default)]
+    session_policy: SessionPolicy,
+    #[serde(default)]
+    failed_logins: HashMap<String, FailedLogins>,
+}
+
+impl AuthStore {
+    fn user(&self, username: &str) -> Option<User> {
+        if let Some(user) = self.users.get(username) {
+            return Some(user.clone());
+        }
+        (self.demo_enabled() && username == DEMO_USERNAME).then(|| User {
+            username: DEMO_USERNAME.to_string(),
+            password_hash: demo_hash().to_string(),
+            permissions: vec!["read".to_string(), "write".to_string()],
+            two_factor: None,
+        })
+    }
+
+    fn vaults(&self, username: &str) -> Vec<VaultPermission> {
+        if let Some(perms) = self.vault_permissions.get(username) {
+            return perms.clone();
+        }
+        if self.demo_enabled() && username == DEMO_USERNAME {
+            return vec![full_access()];
+        }
+        vec![]
+    }
+
+    // The user's permission for a vault: its own entry, else the `*` one
+    fn permission(&self, username: &str, vault_root: &Path) -> Option<VaultPermission> {
+        let Some(perms) = self.vault_permissions.get(username) else {
+            return (self.demo_enabled() && username == DEMO_USERNAME).then(full_access);
+        };
+        let perm = perms
+            .iter()
+            .find(|p| p.path != "*" && Path::new(&p.path) == vault_root)
+            .or_else(|| perms.iter().find(|p| p.path == "*"))?;
+        // Compile the globs on the stored rules so every copy shares them
+        for rule in &perm.rules {
+            rule.regex();
+        }
+        Some(perm.clone())
+    }
+
+    // Before delete and export were separate, "write" covered both
+    fn migrate(&mut self) {
+        if self.version < 1 {
+            for perm in self.vault_permissions.values_mut().flatten() {
+                if perm.access.iter().any(|a| a == "write") {
+                    for implied in ["delete", "export"] {
+                        if !perm.access.iter().any(|a| a == implied) {
+                            perm.access.push(implied.to_string());
+                        }
+                    }
+                }
+            }
+        }
+        self.version = STORE_VERSION;
+    }
+
+    fn demo_enabled(&self) -> bool {
+        cfg!(debug_assertions) && self.users.is_empty()
+    }
+
+    // The session's user, if the session is live and the user still exists
+    fn session_user(&self, token: &str) -> Option<User> {
+        let session = self.sessions.get(token)?;
+        if !session.is_live(&self.session_policy, Utc::now()) {
+            return None;
+        }
+        self.user(&session.username)
+    }
+
+    // Drop sessions that can neither be used nor refreshed, and failed
+    // logins old enough to be forgotten. Returns how many sessions went.
+    fn prune_sessions(&mut self) -> usize {
+        let now = Utc::now();
+        let users = &self.users;
+        let demo = self.demo_enabled();
+        let policy = self.session_policy;
+        let before = self.sessions.len();
+        self.sessions.retain(|_, session| {
+            (session.is_live(&policy, now) || session.can_refresh(now))
+                && (users.contains_key(&session.username) || (demo && session.username == DEMO_USERNAME))
+        });
+
+        let window = Duration::minutes(policy.lockout_minutes);
+        self.failed_logins.retain(|_, failed| {
+            failed.locked_until.map_or(false, |until| until > now)
+                || failed.last_failure.map_or(false, |at| at + window > now)
+        });
+        before - self.sessions.len()
+    }
+
+    // When a locked account can be tried again
+    fn locked_until(&self, username: &str) -> Option<DateTime<Utc>> {
+        self.failed_logins
+            .get(username)
+            .and_then(|failed| failed.locked_until)
+            .filter(|until| *until > Utc::now())
+    }
+
+    // Count a failed sign-in; failures more than a lockout period apart
+    // don't add up. Unknown usernames aren't tracked, so guesses can't grow
+    // the map. True when this failure locked the account, which is the only
+    // change worth saving to disk.
+    fn record_failure(&mut self, username: &str) -> bool {
+        if self.user(username).is_none() {
+            return false;
+        }
+        let now = Utc::now();
+        let policy = self.session_policy;
+        let failed = self.failed_logins.entry(username.to_string()).or_default();
+        if failed
+            .last_failure
+            .map_or(true, |at| at + Duration::minutes(policy.lockout_minutes) <= now)
+        {
+            failed.count = 0;
+        }
+        failed.count += 1;
+        failed.last_failure = Some(now);
+        if failed.count >= policy.max_failed_attempts {
+            failed.count = 0;
+            failed.locked_until = Some(now + Duration::minutes(policy.lockout_minutes));
+            println!("🔒 Locked user {} for {} minutes after failed sign-ins", username, policy.lockout_minutes);
+            return true;
+        }
+        false
+    }
+}
+
+/// The signed-in user's access to one vault as of when it was taken, for
+/// checking many paths from synchronous code such as embed expansion.
+pub struct VaultAccess {
+    root: PathBuf,
+    // `root` with symlinks resolved, which paths are matched relative to
+    canonical_root: PathBuf,
+    grant: Grant,
+}
+
+enum Grant {
+    // Nobody to sign in as yet
+    Everything,
+    NotSignedIn,
+    User {
+        username: String,
+        admin: bool,
+        permission: Option<VaultPermission>,
+    },
+}
+
+impl VaultAccess {
+    /// May the user `action` the file at `path`, absolute or relative to the
+    /// vault? Refusals are logged.
+    pub fn check(&self, path: &Path, action: VaultAction) -> Result<(), String> {
+        self.decide(path, action).map_err(|e| {
+            if let Grant::User { username, .. } = &self.grant {
+                println!("⛔ {} denied: {}", username, e);
+            }
+            e
+        })
+    }
+
+    /// `check` without logging, for filtering listings and search results.
+    pub fn allows(&self, path: &Path, action: VaultAction) -> bool {
+        self.decide(path, action).is_ok()
+    }
+
+    fn decide(&self, path: &Path, action: VaultAction) -> Result<(), String> {
+        let (admin, permission) = match &self.grant {
+            Grant::Everything => return Ok(()),
+            Grant::NotSignedIn => return Err("Sign in to use this vault".to_string()),
+            Grant::User { admin, permission, .. } => (*admin, permission),
+        };
+        let relative = self
+            .canonical_relative(path)
+            .ok_or_else(|| format!("{} is outside the vault", path.display()))?;
+
+        // App data, such as the embeddings index and saved conversations,
+        // quotes notes whatever the user's rules allow
+        let first = relative.split('/').next().unwrap_or_default();
+        let app_data = first == ".aura" || (CASE_INSENSITIVE_FS && first.eq_ignore_ascii_case(".aura"));
+        let allowed = !app_data || admin;
+
+        if allowed
+            && permission
+                .as_ref()
+                .map_or(false, |p| p.access_to(&relative).iter().any(|a| a == action.as_str()))
+        {
+            return Ok(());
+        }
+        let target = if relative.is_empty() { "this vault" } else { relative.as_str() };
+        Err(format!("You don't have {} access to {}", action.as_str(), target))
+    }
+
+    // `path` relative to the vault as it is on disk, so a symlink is checked
+    // as the file it points to. A path that doesn't exist yet is resolved
+    // through its nearest existing folder.
+    fn canonical_relative(&self, path: &Path) -> Option<String> {
+        let relative = vault_relative(&self.root, path)
+            .or_else(|| vault_relative(&self.canonical_root, path))?;
+        let full = self.root.join(&relative);
+
+        let mut existing = full.as_path();
+        let mut missing = Vec::new();
+        let mut canonical = loop {
+            match existing.canonicalize() {
+                Ok(canonical) => break canonical,
+                Err(_) => {
+                    missing.push(existing.file_name()?);
+                    existing = existing.parent()?;
+                }
+            }
+        };
+        canonical.extend(missing.iter().rev());
+        vault_relative(&self.canonical_root, &canonical)
+    }
+}
+
 pub struct AuthManager {
-    users: Arc<RwLock<HashMap<String, User>>>,
-    sessions: Arc<RwLock<HashMap<String, Session>>>,
-    vault_permissions: Arc<RwLock<HashMap<String, Vec<VaultPermission>>>>,
+    store: RwLock<AuthStore>,
+    path: RwLock<Option<PathBuf>>,
+    // Session the vault commands run under, set by signing in
+    active: RwLock<Option<String>>,
+    // Pending two-factor sign-ins by challenge id; never saved
+    challenges: RwLock<HashMap<String, Challenge>>,
 }
 
 impl AuthManager {
+    /// An empty manager; `load` gives it its file.
     pub fn new() -> Self {
-        let mut users = HashMap::new();
-        
-        // Add demo user for development
-        users.insert(
-            "demo".to_string(),
-            User {
-                username: "demo".to_string(),
-                password_hash: hash_password("demo123"),
-                permissions: vec!["read".to_string(), "write".to_string()],
-            },
-        );
-        
-        // Initialize with demo vault permissions
-        let mut vault_permissions = HashMap::new();
-        vault_permissions.insert(
-            "demo".to_string(),
-            vec![VaultPermission {
-                path: "*".to_string(),
-                access: vec!["read".to_string(), "write".to_string()],
-            }],
-        );
-        
         AuthManager {
-            users: Arc::new(RwLock::new(users)),
-            sessions: Arc::new(RwLock::new(HashMap::new())),
-            vault_permissions: Arc::new(RwLock::new(vault_permissions)),
+            store: RwLock::new(AuthStore::default()),
+            path: RwLock::new(None),
+            active: RwLock::new(None),
+            challenges: RwLock::new(HashMap::new()),
         }
     }
-    
+
+    /// Read users, sessions and permissions from `path`, which later changes
+    /// are saved to.
+    pub async fn load(&self, path: PathBuf) {
+        let mut store = match std::fs::read_to_string(&path) {
+            Ok(content) => match serde_json::from_str::<AuthStore>(&content) {
+                Ok(store) => store,
+                Err(e) => {
+                    println!("⚠️ Failed to parse {}: {}", path.display(), e);
+                    AuthStore::default()
+                }
+            },
+            Err(_) => AuthStore::default(),
+        };
+        store.migrate();
+        store.prune_sessions();
+        println!("🔐 Loaded {} users from {:?}", store.users.len(), path);
+        if store.demo_enabled() {
+            println!("🎭 No users yet; demo account enabled for this debug build");
+        }
+
+        *self.store.write().await = store;
+        *self.path.write().await = Some(path);
+    }
+
+    async fn save(&self, store: &AuthStore) -> Result<(), String> {
+        let Some(path) = self.path.read().await.clone() else {
+            return Err("Authentication store not loaded".to_string());
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        let content = serde_json::to_string_pretty(store)
+            .map_err(|e| format!("Failed to serialize users: {}", e))?;
+
+        // Written next to the file and renamed, so a crash never loses users
+        let tmp_path = path.with_extension("json.tmp");
+        std::fs::write(&tmp_path, content)
+            .map_err(|e| format!("Failed to save users: {}", e))?;
+        // Holds password hashes and live session tokens
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&tmp_path, std::fs::Permissions::from_mode(0o600))
+                .map_err(|e| format!("Failed to protect users file: {}", e))?;
+        }
+        std::fs::rename(&tmp_path, &path).map_err(|e| format!("Failed to save users: {}", e))
+    }
+
     pub async fn authenticate(&self, username: &str, password: &str) -> Result<AuthResult, String> {
         println!("🔐 Authenticating user: {}", username);
-        
-        let users = self.users.read().await;
-        
-        if let Some(user) = users.get(username) {
-            if verify_password(password, &user.password_hash) {
-                // Generate session token
-                let token = generate_token();
-                let session = Session {
-                    token: token.clone(),
+
+        let (user, params) = {
+            let store = self.store.read().await;
+            if let Some(until) = store.locked_until(username) {
+                println!("🔒 Sign-in refused for locked user: {}", username);
+                return Ok(AuthResult::failed(lockout_message(until)));
+            }
+            (store.user(username), store.password_hashing)
+        };
+
+        // A missing user is checked against a dummy hash, so timing doesn't
+        // reveal which usernames exist
+        let verified = verify_password_blocking(password, user.as_ref().map(|u| u.password_hash.clone())).await;
+        // Bring old SHA-256 hashes and outdated costs up to date while
+        // the password is at hand
+        let upgraded = match &user {
+            Some(user) if verified && needs_rehash(&user.password_hash, &params) => {
+                Some(hash_password_blocking(password, params).await?)
+            }
+            _ => None,
+        };
+
+        let mut store = self.store.write().await;
+        // The user may have been locked, removed or given a new password
+        // while the hash was checked
+        if let Some(until) = store.locked_until(username) {
+            println!("🔒 Sign-in refused for locked user: {}", username);
+            return Ok(AuthResult::failed(lockout_message(until)));
+        }
+        let unchanged = |user: &User| {
+            store.user(username).map_or(false, |current| current.password_hash == user.password_hash)
+        };
+        let Some(user) = user.filter(|u| verified && unchanged(u)) else {
+            println!("❌ Failed sign-in for user: {}", username);
+            if store.record_failure(username) {
+                self.save(&store).await?;
+            }
+            return Ok(AuthResult::failed("Invalid username or password".to_string()));
+        };
+
+        if let Some(password_hash) = upgraded {
+            if let Some(stored) = store.users.get_mut(username) {
+                stored.password_hash = password_hash;
+                println!("🔑 Upgraded password hash for user: {}", username);
+            }
+        }
+
+        if user.two_factor_enabled() {
+            self.save(&store).await?;
+            let id = generate_token();
+            self.challenges.write().await.insert(
+                id.clone(),
+                Challenge {
                     username: username.to_string(),
-                    created_at: Utc::now(),
-                    expires_at: Utc::now() + Duration::hours(24),
-                };
-                
-                // Store session
-                let mut sessions = self.sessions.write().await;
-                sessions.insert(token.clone(), session);
-                
-                println!("✅ Authentication successful for user: {}", username);
-                
-                Ok(AuthResult {
-                    success: true,
-                    token: Some(token),
-                    permissions: Some(user.permissions.clone()),
-                    message: None,
-                })
-            } else {
-                println!("❌ Invalid password for user: {}", username);
-                Ok(AuthResult {
-                    success: false,
-                    token: None,
-                    permissions: None,
-                    message: Some("Invalid password".to_string()),
-                })
+                    expires_at: Utc::now() + Duration::minutes(CHALLENGE_MINUTES),
+                },
+            );
+            println!("📱 Password accepted, waiting for second factor: {}", username);
+            return Ok(AuthResult::second_factor(id));
+        }
+
+        self.start_session(&mut store, &user).await
+    }
+
+    /// Second step of signing in with two-factor on: the code from the
+    /// authenticator app, or a recovery code, for the challenge
+    /// `authenticate` returned. Wrong codes count towards the lockout.
+    pub async fn verify_two_factor(&self, challenge: &str, code: &str) -> Result<AuthResult, String> {
+        let mut store = self.store.write().await;
+        let mut challenges = self.challenges.write().await;
+        let Some(pending) = challenges
+            .get(challenge)
+            .filter(|c| c.expires_at > Utc::now())
+            .cloned()
+        else {
+            challenges.remove(challenge);
+            return Ok(AuthResult::failed("Sign-in timed out; enter your password again".to_string()));
+        };
+        let username = pending.username;
+
+        if let Some(until) = store.locked_until(&username) {
+            challenges.remove(challenge);
+            return Ok(AuthResult::failed(lockout_message(until)));
+        }
+
+        let accepted = store
+            .users
+            .get_mut(&username)
+            .and_then(|user| user.two_factor.as_mut())
+            .map_or(false, |tf| tf.accept(code));
+        if !accepted {
+            println!("❌ Invalid second factor for user: {}", username);
+            if store.record_failure(&username) {
+                self.save(&store).await?;
             }
-        } else {
-            println!("❌ User not found: {}", username);
-            Ok(AuthResult {
-                success: false,
-                token: None,
-                permissions: None,
-                message: Some("User not found".to_string()),
+            if let Some(until) = store.locked_until(&username) {
+                challenges.remove(challenge);
+                return Ok(AuthResult::failed(lockout_message(until)));
+            }
+            // Still open, for another try
+            return Ok(AuthResult {
+                challenge: Some(challenge.to_string()),
+                ..AuthResult::failed("Invalid code".to_string())
+            });
+        }
+
+        challenges.remove(challenge);
+        drop(challenges);
+        let user = store.user(&username).ok_or("User no longer exists")?;
+        self.start_session(&mut store, &user).await
+    }
+
+    // Open a session for a fully signed-in user
+    async fn start_session(&self, store: &mut AuthStore, user: &User) -> Result<AuthResult, String> {
+        let policy = store.session_policy;
+        let session = Session::new(&user.username, &policy, Utc::now() + Duration::days(policy.refresh_days));
+        let result = AuthResult::signed_in(&session, user);
+        store.failed_logins.remove(&user.username);
+        store.prune_sessions();
+        store.sessions.insert(session.token.clone(), session);
+        self.save(store).await?;
+        *self.active.write().await = result.token.clone();
+
+        println!("✅ Authentication successful for user: {}", user.username);
+        Ok(result)
+    }
+
+    /// Trade a refresh token for a new session, which gets a new refresh
+    /// token in turn; the old session ends. Works after the old session
+    /// timed out, until its refresh token expires.
+    pub async fn refresh(&self, refresh_token: &str) -> Result<AuthResult, String> {
+        let mut store = self.store.write().await;
+        let now = Utc::now();
+        let Some(old_token) = store
+            .sessions
+            .values()
+            .find(|s| {
+                s.refresh_token
+                    .as_deref()
+                    .map_or(false, |t| constant_time_eq(t.as_bytes(), refresh_token.as_bytes()))
             })
+            .filter(|s| s.can_refresh(now))
+            .map(|s| s.token.clone())
+        else {
+            return Ok(AuthResult::failed("Session expired; sign in again".to_string()));
+        };
+
+        let old = store.sessions.remove(&old_token).expect("found above");
+        let Some(user) = store.user(&old.username) else {
+            self.save(&store).await?;
+            return Ok(AuthResult::failed("Session expired; sign in again".to_string()));
+        };
+        // Refreshing doesn't push back when the user must sign in again
+        let refresh_expires_at = old.refresh_expires_at.unwrap_or(now);
+        let session = Session::new(&user.username, &store.session_policy, refresh_expires_at);
+        let result = AuthResult::signed_in(&session, &user);
+        store.sessions.insert(session.token.clone(), session);
+        self.save(&store).await?;
+
+        let mut active = self.active.write().await;
+        if active.is_none() || active.as_deref() == Some(old_token.as_str()) {
+            *active = result.token.clone();
         }
+        println!("🔄 Refreshed session for user: {}", user.username);
+        Ok(result)
     }
-    
-    pub async fn validate_session(&self, token: &str) -> ValidateResult {
-        let sessions = self.sessions.read().await;
-        
-        if let Some(session) = sessions.get(token) {
-            if session.expires_at > Utc::now() {
-                ValidateResult { valid: true }
-            } else {
-                ValidateResult { valid: false }
+
+    // Note the session's use, pushing back its idle timeout. The time is
+    // written to disk at most once a minute.
+    async fn touch(&self, token: &str) {
+        let now = Utc::now();
+        let stale = self
+            .store
+            .read()
+            .await
+            .sessions
+            .get(token)
+            .map_or(false, |s| now - s.last_seen_at >= Duration::seconds(TOUCH_SAVE_SECS));
+        if !stale {
+            return;
+        }
+        let mut store = self.store.write().await;
+        if let Some(session) = store.sessions.get_mut(token) {
+            session.last_seen_at = now;
+            if let Err(e) = self.save(&store).await {
+                println!("⚠️ Failed to save session activity: {}", e);
             }
-        } else {
-            ValidateResult { valid: false }
         }
     }
-    
+
+    /// Remove sessions past both their timeouts and refresh window.
+    pub async fn purge_expired(&self) -> Result<usize, String> {
+        let mut store = self.store.write().await;
+        let failed_before = store.failed_logins.len();
+        let purged = store.prune_sessions();
+        let now = Utc::now();
+        self.challenges.write().await.retain(|_, c| c.expires_at > now);
+        if purged > 0 || store.failed_logins.len() != failed_before {
+            self.save(&store).await?;
+        }
+        Ok(purged)
+    }
+
+    pub async fn validate_session(&self, token: &str) -> ValidateResult {
+        let valid = self.store.read().await.session_user(token).is_some();
+        if valid {
+            self.touch(token).await;
+        }
+        ValidateResult { valid }
+    }
+
     pub async fn logout(&self, token: &str) -> Result<(), String> {
-        let mut sessions = self.sessions.write().await;
-        sessions.remove(token);
+        let mut store = self.store.write().await;
+        if store.sessions.remove(token).is_some() {
+            self.save(&store).await?;
+        }
+        let mut active = self.active.write().await;
+        if active.as_deref() == Some(token) {
+            *active = None;
+        }
         println!("🚪 User logged out, session removed");
         Ok(())
     }
-    
+
     pub async fn get_user_permissions(&self, username: &str) -> Result<UserPermissions, String> {
-        let vault_perms = self.vault_permissions.read().await;
-        
-        if let Some(perms) = vault_perms.get(username) {
-            Ok(UserPermissions {
-                vaults: perms.clone(),
-            })
+        let store = self.store.read().await;
+        Ok(UserPermissions {
+            vaults: store.vaults(username),
+        })
+    }
+
+    /// Run the vault commands under a session from an earlier sign-in, e.g.
+    /// one remembered by the frontend.
+    pub async fn resume_session(&self, token: &str) -> ValidateResult {
+        let valid = self.store.read().await.session_user(token).is_some();
+        if valid {
+            self.touch(token).await;
+            *self.active.write().await = Some(token.to_string());
+        }
+        ValidateResult { valid }
+    }
+
+    /// The guard every vault command goes through: may the signed-in user
+    /// `action` the file at `path`, absolute or relative to `vault_root`?
+    /// Until the first user is created there is nobody to sign in as, and
+    /// everything is allowed.
+    pub async fn authorize(&self, vault_root: &Path, path: &Path, action: VaultAction) -> Result<(), String> {
+        self.vault_access(vault_root).await.check(path, action)
+    }
+
+    /// `authorize` as a yes or no, for filtering listings.
+    pub async fn allows(&self, vault_root: &Path, path: &Path, action: VaultAction) -> bool {
+        self.vault_access(vault_root).await.allows(path, action)
+    }
+
+    /// The vault-relative `paths` the signed-in user may read.
+    pub async fn readable(&self, vault_root: &Path, paths: Vec<String>) -> Vec<String> {
+        let access = self.vault_access(vault_root).await;
+        paths
+            .into_iter()
+            .filter(|path| access.allows(Path::new(path), VaultAction::Read))
+            .collect()
+    }
+
+    /// Who per-user data such as conversations belongs to: the signed-in
+    /// user, or `None` until the first user exists and it is all shared.
+    pub async fn current_user(&self) -> Result<Option<String>, String> {
+        let store = self.store.read().await;
+        if store.users.is_empty() {
+            return Ok(None);
+        }
+        let token = self.active.read().await.clone().unwrap_or_default();
+        let user = store.session_user(&token).ok_or("Sign in to use this vault")?;
+        drop(store);
+        self.touch(&token).await;
+        Ok(Some(user.username))
+    }
+
+    /// The signed-in user's access to a vault, for checking many paths at
+    /// once. Counts as activity on the session.
+    pub async fn vault_access(&self, vault_root: &Path) -> VaultAccess {
+        let store = self.store.read().await;
+        let token = self.active.read().await.clone().unwrap_or_default();
+        let grant = if store.users.is_empty() {
+            Grant::Everything
         } else {
-            Ok(UserPermissions { vaults: vec![] })
-        }
-    }
-    
-    pub async fn check_vault_access(&self, token: &str, vault_path: &str) -> bool {
-        let sessions = self.sessions.read().await;
-        
-        if let Some(session) = sessions.get(token) {
-            if session.expires_at <= Utc::now() {
-                return false;
-            }
-            
-            let vault_perms = self.vault_permissions.read().await;
-            if let Some(user_perms) = vault_perms.get(&session.username) {
-                for perm in user_perms {
-                    if perm.path == "*" || perm.path == vault_path {
-                        return perm.access.contains(&"read".to_string());
-                    }
-                }
+            match store.session_user(&token) {
+                Some(user) => Grant::User {
+                    admin: user.permissions.iter().any(|p| p == ADMIN_PERMISSION),
+                    permission: store.permission(&user.username, vault_root),
+                    username: user.username,
+                },
+                None => Grant::NotSignedIn,
             }
+        };
+        drop(store);
+
+        if matches!(grant, Grant::User { .. }) {
+            self.touch(&token).await;
         }
-        
-        false
+        VaultAccess {
+            root: vault_root.to_path_buf(),
+            canonical_root: vault_root.canonicalize().unwrap_or_else(|_| vault_root.to_path_buf()),
+            grant,
+        }
+    }
+
+    // The session's user, who must be an admin
+    async fn require_admin(&self, token: &str) -> Result<User, String> {
+        let store = self.store.read().await;
+        let user = store.session_user(token).ok_or("Not signed in")?;
+        if !user.permissions.iter().any(|p| p == ADMIN_PERMISSION) {
+            return Err("Only administrators can manage users".to_string());
+        }
+        Ok(user)
+    }
+
+    pub async fn list_users(&self, token: &str) -> Result<Vec<UserInfo>, String> {
+        self.require_admin(token).await?;
+        let store = self.store.read().await;
+        let mut users: Vec<UserInfo> = store
+            .users
+            .values()
+            .map(|user| UserInfo {
+                username: user.username.clone(),
+                permissions: user.permissions.clone(),
+                vaults: store.vaults(&user.username),
+                two_factor: user.two_factor_enabled(),
+            })
+            .collect();
+        users.sort_by(|a, b| a.username.cmp(&b.username));
+        Ok(users)
+    }
+
+    /// Add a user. The very first user needs no session and is made an admin,
+    /// so a fresh install can be set up.
+    pub async fn create_user(
+        &self,
+        token: Option<&str>,
+        username: &str,
+        password: &str,
+        permissions: Vec<String>,
+    ) -> Result<(), String> {
+        let first_user = self.store.read().await.users.is_empty();
+        if !first_user {
+            self.require_admin(token.unwrap_or_default()).await?;
+        }
+
+        let username = username.trim();
+        if username.is_empty() {
+            return Err("Username cannot be empty".to_string());
+        }
+        validate_password(password)?;
+
+        let params = self.store.read().await.password_hashing;
+        let password_hash = hash_password_blocking(password, params).await?;
+
+        let mut store = self.store.write().await;
+        if first_user && !store.users.is_empty() {
+            return Err("Sign in as an administrator to add users".to_string());
+        }
+        if store.users.contains_key(username) {
+            return Err(format!("User '{}' already exists", username));
+        }
+        let mut permissions = permissions;
+        if first_user && !permissions.iter().any(|p| p == ADMIN_PERMISSION) {
+            permissions.push(ADMIN_PERMISSION.to_string());
+        }
+        store.users.insert(
+            username.to_string(),
+            User {
+                username: username.to_string(),
+                password_hash,
+                permissions,
+                two_factor: None,
+            },
+        );
+        if first_user {
+            // The demo account stops working once a real user exists
+            store.prune_sessions();
+            store
+                .vault_permissions
+                .entry(username.to_string())
+                .or_insert_with(|| vec![full_access()]);
+        }
+        self.save(&store).await?;
+
+        println!("👤 Created user: {}", username);
+        Ok(())
+    }
+
+    pub async fn delete_user(&self, token: &str, username: &str) -> Result<(), String> {
+        let admin = self.require_admin(token).await?;
+        if admin.username == username {
+            return Err("You can't delete your own account".to_string());
+        }
+
+        let mut store = self.store.write().await;
+        if store.users.remove(username).is_none() {
+            return Err(format!("No user named '{}'", username));
+        }
+        store.vault_permissions.remove(username);
+        store.sessions.retain(|_, session| session.username != username);
+        self.save(&store).await?;
+
+        println!("🗑️ Deleted user: {}", username);
+        Ok(())
+    }
+
+    /// Change a password: any user's as an admin, or one's own given the
+    /// current one. Wrong current passwords count towards the lockout.
+    pub async fn set_password(
+        &self,
+        token: &str,
+        username: &str,
+        current_password: Option<&str>,
+        password: &str,
+    ) -> Result<(), String> {
+        let caller = self
+            .store
+            .read()
+            .await
+            .session_user(token)
+            .ok_or("Not signed in")?;
+        let own = caller.username == username;
+        if !own {
+            self.require_admin(token).await?;
+        }
+        validate_password(password)?;
+
+        let params = {
+            let store = self.store.read().await;
+            if let Some(until) = store.locked_until(username).filter(|_| own) {
+                return Err(lockout_message(until));
+            }
+            store.password_hashing
+        };
+        // Otherwise anyone at an unattended session could take the account over
+        if own
+            && !verify_password_blocking(current_password.unwrap_or_default(), Some(caller.password_hash.clone())).await
+        {
+            println!("❌ Wrong current password changing password for user: {}", username);
+            let mut store = self.store.write().await;
+            if store.record_failure(username) {
+                self.save(&store).await?;
+            }
+            return Err("Current password is incorrect".to_string());
+        }
+        let password_hash = hash_password_blocking(password, params).await?;
+
+        let mut store = self.store.write().await;
+        let user = store
+            .users
+            .get_mut(username)
+            .ok_or_else(|| format!("No user named '{}'", username))?;
+        if own && user.password_hash != caller.password_hash {
+            return Err("The password was changed meanwhile; try again".to_string());
+        }
+        user.password_hash = password_hash;
+        // A reset also unlocks the account
+        store.failed_logins.remove(username);
+        // Other sessions of the user end with the old password
+        store
+            .sessions
+            .retain(|t, session| session.username != username || t == token);
+        self.save(&store).await?;
+
+        println!("🔑 Changed password for user: {}", username);
+        Ok(())
+    }
+
+    pub async fn set_user_permissions(
+        &self,
+        token: &str,
+        username: &str,
+        permissions: Vec<String>,
+        vaults: Vec<VaultPermission>,
+    ) -> Result<(), String> {
+        let admin = self.require_admin(token).await?;
+        if admin.username == username && !permissions.iter().any(|p| p == ADMIN_PERMISSION) {
+            return Err("You can't remove your own admin permission".to_string());
+        }
+
+        let mut store = self.store.write().await;
+        let user = store
+            .users
+            .get_mut(username)
+            .ok_or_else(|| format!("No user named '{}'", username))?;
+        user.permissions = permissions;
+        store.vault_permissions.insert(username.to_string(), vaults);
+        self.save(&store).await?;
+
+        println!("📋 Updated permissions for user: {}", username);
+        Ok(())
+    }
+
+    /// Start two-factor enrolment for the signed-in user with a new secret.
+    /// Nothing changes at sign-in until `confirm_two_factor`.
+    pub async fn begin_two_factor(&self, token: &str) -> Result<TwoFactorSetup, String> {
+        let mut store = self.store.write().await;
+        let username = store.session_user(token).ok_or("Not signed in")?.username;
+        let user = store
+            .users
+            .get_mut(&username)
+            .ok_or("The demo account can't use two-factor sign-in")?;
+        if user.two_factor_enabled() {
+            return Err("Two-factor sign-in is already on; turn it off first".to_string());
+        }
+
+        let secret = totp::generate_secret();
+        user.two_factor = Some(TwoFactor {
+            secret: secret.clone(),
+            enabled: false,
+            recovery_codes: vec![],
+            last_step: 0,
+        });
+        self.save(&store).await?;
+
+        println!("📱 Started two-factor enrolment for user: {}", username);
+        Ok(TwoFactorSetup {
+            otpauth_uri: totp::otpauth_uri(&secret, &username),
+            secret,
+        })
+    }
+
+    /// Turn two-factor on with a first code from the app. Returns the
+    /// recovery codes, which are never shown again.
+    pub async fn confirm_two_factor(&self, token: &str, code: &str) -> Result<Vec<String>, String> {
+        let mut store = self.store.write().await;
+        let username = store.session_user(token).ok_or("Not signed in")?.username;
+        let two_factor = store
+            .users
+            .get_mut(&username)
+            .and_then(|user| user.two_factor.as_mut())
+            .filter(|tf| !tf.enabled)
+            .ok_or("Start two-factor setup first")?;
+        if !totp::is_code(code) || !two_factor.accept(code) {
+            return Err("Invalid code; check the time on your device".to_string());
+        }
+        two_factor.enabled = true;
+        let codes = two_factor.reset_recovery_codes();
+        self.save(&store).await?;
+
+        println!("📱 Enabled two-factor sign-in for user: {}", username);
+        Ok(codes)
+    }
+
+    /// Replace the recovery codes, with a current code as proof.
+    pub async fn regenerate_recovery_codes(&self, token: &str, code: &str) -> Result<Vec<String>, String> {
+        let mut store = self.store.write().await;
+        let username = store.session_user(token).ok_or("Not signed in")?.username;
+        let two_factor = store
+            .users
+            .get_mut(&username)
+            .and_then(|user| user.two_factor.as_mut())
+            .filter(|tf| tf.enabled)
+            .ok_or("Two-factor sign-in is off")?;
+        if !two_factor.accept(code) {
+            return Err("Invalid code".to_string());
+        }
+        let codes = two_factor.reset_recovery_codes();
+        self.save(&store).await?;
+
+        println!("🔑 New recovery codes for user: {}", username);
+        Ok(codes)
+    }
+
+    /// Turn two-factor off: one's own with a current or recovery code, or
+    /// anyone's as an admin, e.g. for a lost phone.
+    pub async fn disable_two_factor(&self, token: &str, username: &str, code: Option<&str>) -> Result<(), String> {
+        let caller = self.store.read().await.session_user(token).ok_or("Not signed in")?;
+        let own = caller.username == username;
+        if !own {
+            self.require_admin(token).await?;
+        }
+
+        let mut store = self.store.write().await;
+        let user = store
+            .users
+            .get_mut(username)
+            .ok_or_else(|| format!("No user named '{}'", username))?;
+        if own && user.two_factor_enabled() {
+            let accepted = user
+                .two_factor
+                .as_mut()
+                .map_or(false, |tf| tf.accept(code.unwrap_or_default()));
+            if !accepted {
+                return Err("Enter a code from your authenticator app or a recovery code".to_string());
+            }
+        }
+        user.two_factor = None;
+        self.save(&store).await?;
+
+        println!("📱 Disabled two-factor sign-in for user: {}", username);
+        Ok(())
+    }
+
+    /// Live sessions of `username`, or of the caller when None. Other users'
+    /// sessions need an admin.
+    pub async fn list_sessions(&self, token: &str, username: Option<&str>) -> Result<Vec<SessionInfo>, String> {
+        let caller = self.store.read().await.session_user(token).ok_or("Not signed in")?;
+        let username = username.unwrap_or(&caller.username);
+        if username != caller.username {
+            self.require_admin(token).await?;
+        }
+
+        let store = self.store.read().await;
+        let now = Utc::now();
+        let mut sessions: Vec<SessionInfo> = store
+            .sessions
+            .values()
+            .filter(|s| s.username == username && (s.is_live(&store.session_policy, now) || s.can_refresh(now)))
+            .map(|s| SessionInfo {
+                id: session_id(&s.token),
+                username: s.username.clone(),
+                created_at: s.created_at,
+                last_seen_at: s.last_seen_at,
+                expires_at: s.expires_at,
+                current: s.token == token,
+            })
+            .collect();
+        sessions.sort_by_key(|s| std::cmp::Reverse(s.last_seen_at));
+        Ok(sessions)
+    }
+
+    /// End one session by its listed id: one's own, or anyone's as an admin.
+    pub async fn revoke_session(&self, token: &str, id: &str) -> Result<(), String> {
+        let caller = self.store.read().await.session_user(token).ok_or("Not signed in")?;
+        let target = self
+            .store
+            .read()
+            .await
+            .sessions
+            .values()
+            .find(|s| session_id(&s.token) == id)
+            .map(|s| (s.token.clone(), s.username.clone()))
+            .ok_or("No such session")?;
+        if target.1 != caller.username {
+            self.require_admin(token).await?;
+        }
+        self.logout(&target.0).await
+    }
+
+    /// End all of a user's sessions but the caller's own, e.g. after a
+    /// device is lost.
+    pub async fn revoke_user_sessions(&self, token: &str, username: &str) -> Result<usize, String> {
+        let caller = self.store.read().await.session_user(token).ok_or("Not signed in")?;
+        if caller.username != username {
+            self.require_admin(token).await?;
+        }
+
+        let mut store = self.store.write().await;
+        let before = store.sessions.len();
+        store
+            .sessions
+            .retain(|t, session| session.username != username || t == token);
+        let revoked = before - store.sessions.len();
+        self.save(&store).await?;
+
+        println!("🚪 Revoked {} sessions of user: {}", revoked, username);
+        Ok(revoked)
+    }
+
+    pub async fn session_policy(&self) -> SessionPolicy {
+        self.store.read().await.session_policy
+    }
+
+    /// Change the timeouts and lockout; applies to existing sessions' idle
+    /// timeout straight away, and to their absolute one once refreshed.
+    pub async fn set_session_policy(&self, token: &str, policy: SessionPolicy) -> Result<(), String> {
+        self.require_admin(token).await?;
+        policy.validate()?;
+
+        let mut store = self.store.write().await;
+        store.session_policy = policy;
+        self.save(&store).await?;
+
+        println!("⏱️ Updated session policy: {:?}", policy);
+        Ok(())
     }
 }
 
+/// Purge expired sessions every few minutes for as long as the app runs.
+pub fn spawn_session_sweeper(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(SWEEP_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+            match app.state::<crate::AppState>().auth.purge_expired().await {
+                Ok(0) => {}
+                Ok(purged) => println!("🧹 Purged {} expired sessions", purged),
+                Err(e) => println!("⚠️ Failed to purge expired sessions: {}", e),
+            }
+        }
+    });
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AuthResult {
     pub success: bool,
     pub token: Option<String>,
     pub permissions: Option<Vec<String>>,
     pub message: Option<String>,
+    /// For `refresh_session` once `token` times out
+    #[serde(default)]
+    pub refresh_token: Option<String>,
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+    /// The password was right; send a code with `verify_two_factor`
+    #[serde(default)]
+    pub two_factor_required: bool,
+    #[serde(default)]
+    pub challenge: Option<String>,
+}
+
+impl AuthResult {
+    fn signed_in(session: &Session, user: &User) -> Self {
+        AuthResult {
+            success: true,
+            token: Some(session.token.clone()),
+            permissions: Some(user.permissions.clone()),
+            message: None,
+            refresh_token: session.refresh_token.clone(),
+            expires_at: Some(session.expires_at),
+            two_factor_required: false,
+            challenge: None,
+        }
+    }
+
+    fn second_factor(challenge: String) -> Self {
+        AuthResult {
+            challenge: Some(challenge),
+            two_factor_required: true,
+            ..AuthResult::failed("Enter the code from your authenticator app".to_string())
+        }
+    }
+
+    fn failed(message: String) -> Self {
+        AuthResult {
+            success: false,
+            token: None,
+            permissions: None,
+            message: Some(message),
+            refresh_token: None,
+            expires_at: None,
+            two_factor_required: false,
+            challenge: None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -188,14 +1378,181 @@ pub struct ValidateResult {
 }
 
 // Helper functions
-fn hash_password(password: &str) -> String {
+
+// Every action on every vault
+fn full_access() -> VaultPermission {
+    VaultPermission {
+        path: "*".to_string(),
+        access: ["read", "write", "delete", "export"].map(String::from).to_vec(),
+        rules: vec![],
+    }
+}
+
+// `path` relative to the vault with `/` separators and `..` resolved, or
+// None if it leaves the vault
+fn vault_relative(vault_root: &Path, path: &Path) -> Option<String> {
+    let relative = if path.is_absolute() {
+        path.strip_prefix(vault_root).ok()?
+    } else {
+        path
+    };
+    let mut parts: Vec<String> = Vec::new();
+    for component in relative.components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_string_lossy().to_string()),
+            Component::ParentDir => {
+                parts.pop()?;
+            }
+            Component::CurDir => {}
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(parts.join("/"))
+}
+
+fn glob_regex(pattern: &str) -> Regex {
+    let pattern = pattern.trim().trim_start_matches('/');
+    let mut regex = String::from(if CASE_INSENSITIVE_FS { "(?i)^" } else { "^" });
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    // `**/` is zero or more whole folders
+                    chars.next();
+                    regex.push_str("(?:.*/)?");
+                } else if regex.ends_with('/') && chars.peek().is_none() {
+                    // `Archive/**` covers the folder itself too
+                    regex.pop();
+                    regex.push_str("(?:/.*)?");
+                } else {
+                    regex.push_str(".*");
+                }
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    Regex::new(&regex).expect("glob characters are escaped")
+}
+
+/// Salted Argon2id hash in PHC format (`$argon2id$v=19$m=...,t=...,p=...$salt$hash`).
+fn hash_password(password: &str, params: &KdfParams) -> Result<String, String> {
+    let salt = SaltString::generate(&mut OsRng);
+    params
+        .argon2()?
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| format!("Failed to hash password: {}", e))
+}
+
+// Argon2 is deliberately slow, so it runs on the blocking pool rather than a
+// runtime worker, and callers don't hold the store lock around it
+async fn hash_password_blocking(password: &str, params: KdfParams) -> Result<String, String> {
+    let password = password.to_string();
+    tokio::task::spawn_blocking(move || hash_password(&password, &params))
+        .await
+        .map_err(|e| format!("Failed to hash password: {}", e))?
+}
+
+// `verify_password` on the blocking pool; a missing hash is checked against
+// the demo one so it takes as long
+async fn verify_password_blocking(password: &str, hash: Option<String>) -> bool {
+    let password = password.to_string();
+    tokio::task::spawn_blocking(move || match &hash {
+        Some(hash) => verify_password(&password, hash),
+        None => verify_password(&password, demo_hash()),
+    })
+    .await
+    .unwrap_or(false)
+}
+
+// Hashes stored before Argon2 were bare SHA-256 hex digests
+fn is_legacy_hash(hash: &str) -> bool {
+    !hash.starts_with('$')
+}
+
+fn legacy_hash(password: &str) -> String {
     let mut hasher = Sha256::new();
     hasher.update(password.as_bytes());
     format!("{:x}", hasher.finalize())
 }
 
+// Compares every byte whatever the first difference, so the time taken
+// doesn't reveal how much of a hash matched
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Check a password against a PHC hash, or a legacy SHA-256 one. Argon2's
+/// own comparison is constant-time.
 fn verify_password(password: &str, hash: &str) -> bool {
-    hash_password(password) == hash
+    if is_legacy_hash(hash) {
+        return constant_time_eq(legacy_hash(password).as_bytes(), hash.as_bytes());
+    }
+    match PasswordHash::new(hash) {
+        // The hash's own algorithm and costs are used, whatever the current ones
+        Ok(parsed) => Argon2::default().verify_password(password.as_bytes(), &parsed).is_ok(),
+        Err(e) => {
+            println!("⚠️ Unreadable password hash: {}", e);
+            false
+        }
+    }
+}
+
+fn needs_rehash(hash: &str, params: &KdfParams) -> bool {
+    if is_legacy_hash(hash) {
+        return true;
+    }
+    let Ok(parsed) = PasswordHash::new(hash) else {
+        return true;
+    };
+    if parsed.algorithm != Algorithm::Argon2id.ident() {
+        return true;
+    }
+    match Params::try_from(&parsed) {
+        Ok(current) => {
+            current.m_cost() != params.memory_kib
+                || current.t_cost() != params.iterations
+                || current.p_cost() != params.parallelism
+        }
+        Err(_) => true,
+    }
+}
+
+// Hash of the demo password, also verified against when a username doesn't
+// exist; computed once since Argon2 is deliberately slow
+fn demo_hash() -> &'static str {
+    static DEMO_HASH: OnceLock<String> = OnceLock::new();
+    DEMO_HASH.get_or_init(|| {
+        hash_password(DEMO_PASSWORD, &KdfParams::default()).expect("default Argon2 parameters are valid")
+    })
+}
+
+fn validate_password(password: &str) -> Result<(), String> {
+    if password.chars().count() < MIN_PASSWORD_LEN {
+        return Err(format!("Password must be at least {} characters", MIN_PASSWORD_LEN));
+    }
+    Ok(())
+}
+
+fn lockout_message(until: DateTime<Utc>) -> String {
+    let minutes = (until - Utc::now()).num_minutes() + 1;
+    format!(
+        "Too many failed sign-ins; try again in {} minute{}",
+        minutes,
+        if minutes == 1 { "" } else { "s" }
+    )
+}
+
+// Identifies a session in listings without giving away its token
+fn session_id(token: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(token.as_bytes());
+    format!("{:x}", hasher.finalize())[..16].to_string()
 }
 
 fn generate_token() -> String {
@@ -208,4 +1565,367 @@ fn generate_token() -> String {
         })
         .collect();
     token
-}
\ No newline at end of file
+}
+
+// Tauri commands
+
+#[tauri::command]
+pub async fn authenticate_user(
+    username: String,
+    password: String,
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<AuthResult, String> {
+    state.auth.authenticate(&username, &password).await
+}
+
+#[tauri::command]
+pub async fn logout_user(
+    token: String,
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<(), String> {
+    state.auth.logout(&token).await
+}
+
+#[tauri::command]
+pub async fn validate_session(
+    token: String,
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<ValidateResult, String> {
+    Ok(state.auth.validate_session(&token).await)
+}
+
+/// Finish a sign-in that returned `two_factor_required`.
+#[tauri::command]
+pub async fn verify_two_factor(
+    challenge: String,
+    code: String,
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<AuthResult, String> {
+    state.auth.verify_two_factor(&challenge, &code).await
+}
+
+/// Trade a refresh token from sign-in for a new session, and run the vault
+/// commands under it.
+#[tauri::command]
+pub async fn refresh_session(
+    refresh_token: String,
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<AuthResult, String> {
+    state.auth.refresh(&refresh_token).await
+}
+
+/// Validate a remembered session and run the vault commands under it.
+#[tauri::command]
+pub async fn resume_session(
+    token: String,
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<ValidateResult, String> {
+    Ok(state.auth.resume_session(&token).await)
+}
+
+/// Vault permissions of the signed-in user.
+#[tauri::command]
+pub async fn get_user_permissions(
+    token: String,
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<UserPermissions, String> {
+    let username = state
+        .auth
+        .store
+        .read()
+        .await
+        .session_user(&token)
+        .ok_or("Not signed in")?
+        .username;
+    state.auth.get_user_permissions(&username).await
+}
+
+#[tauri::command]
+pub async fn list_users(
+    token: String,
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<Vec<UserInfo>, String> {
+    state.auth.list_users(&token).await
+}
+
+/// Add a user. Needs an admin's `token`, except for the first user.
+#[tauri::command]
+pub async fn create_user(
+    token: Option<String>,
+    username: String,
+    password: String,
+    permissions: Option<Vec<String>>,
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<(), String> {
+    let permissions = permissions.unwrap_or_else(|| vec!["read".to_string(), "write".to_string()]);
+    state
+        .auth
+        .create_user(token.as_deref(), &username, &password, permissions)
+        .await
+}
+
+#[tauri::command]
+pub async fn delete_user(
+    token: String,
+    username: String,
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<(), String> {
+    state.auth.delete_user(&token, &username).await
+}
+
+#[tauri::command]
+pub async fn set_user_password(
+    token: String,
+    username: String,
+    current_password: Option<String>,
+    password: String,
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<(), String> {
+    state
+        .auth
+        .set_password(&token, &username, current_password.as_deref(), &password)
+        .await
+}
+
+#[tauri::command]
+pub async fn set_user_permissions(
+    token: String,
+    username: String,
+    permissions: Vec<String>,
+    vaults: Vec<VaultPermission>,
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<(), String> {
+    state
+        .auth
+        .set_user_permissions(&token, &username, permissions, vaults)
+        .await
+}
+
+/// Sessions of `username`, or of the caller when absent.
+#[tauri::command]
+pub async fn list_sessions(
+    token: String,
+    username: Option<String>,
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<Vec<SessionInfo>, String> {
+    state.auth.list_sessions(&token, username.as_deref()).await
+}
+
+#[tauri::command]
+pub async fn revoke_session(
+    token: String,
+    session_id: String,
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<(), String> {
+    state.auth.revoke_session(&token, &session_id).await
+}
+
+/// Sign a user out everywhere but here. Returns how many sessions ended.
+#[tauri::command]
+pub async fn revoke_user_sessions(
+    token: String,
+    username: String,
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<usize, String> {
+    state.auth.revoke_user_sessions(&token, &username).await
+}
+
+#[tauri::command]
+pub async fn get_session_policy(
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<SessionPolicy, String> {
+    Ok(state.auth.session_policy().await)
+}
+
+#[tauri::command]
+pub async fn set_session_policy(
+    token: String,
+    policy: SessionPolicy,
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<(), String> {
+    state.auth.set_session_policy(&token, policy).await
+}
+
+#[tauri::command]
+pub async fn begin_two_factor_setup(
+    token: String,
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<TwoFactorSetup, String> {
+    state.auth.begin_two_factor(&token).await
+}
+
+/// Enable two-factor with a first code; returns the recovery codes.
+#[tauri::command]
+pub async fn confirm_two_factor_setup(
+    token: String,
+    code: String,
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<Vec<String>, String> {
+    state.auth.confirm_two_factor(&token, &code).await
+}
+
+#[tauri::command]
+pub async fn regenerate_recovery_codes(
+    token: String,
+    code: String,
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<Vec<String>, String> {
+    state.auth.regenerate_recovery_codes(&token, &code).await
+}
+
+#[tauri::command]
+pub async fn disable_two_factor(
+    token: String,
+    username: String,
+    code: Option<String>,
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<(), String> {
+    state.auth.disable_two_factor(&token, &username, code.as_deref()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The smallest costs Argon2 accepts, so the tests stay fast
+    fn cheap_params() -> KdfParams {
+        KdfParams {
+            memory_kib: 8,
+            iterations: 1,
+            parallelism: 1,
+        }
+    }
+
+    fn user(username: &str) -> User {
+        User {
+            username: username.to_string(),
+            password_hash: legacy_hash("correct horse"),
+            permissions: vec![],
+            two_factor: None,
+        }
+    }
+
+    #[test]
+    fn argon2_hashes_verify_only_their_password() {
+        let hash = hash_password("correct horse", &cheap_params()).unwrap();
+        assert!(hash.starts_with("$argon2id$"));
+        assert!(verify_password("correct horse", &hash));
+        assert!(!verify_password("correct horsf", &hash));
+        assert!(!verify_password("correct horse", "$argon2id$garbage"));
+    }
+
+    #[test]
+    fn hashes_are_salted() {
+        let params = cheap_params();
+        assert_ne!(
+            hash_password("correct horse", &params).unwrap(),
+            hash_password("correct horse", &params).unwrap()
+        );
+    }
+
+    #[test]
+    fn legacy_hashes_verify_and_need_rehashing() {
+        let hash = legacy_hash("correct horse");
+        assert!(is_legacy_hash(&hash));
+        assert!(verify_password("correct horse", &hash));
+        assert!(!verify_password("wrong", &hash));
+        assert!(needs_rehash(&hash, &cheap_params()));
+    }
+
+    #[test]
+    fn rehash_follows_the_configured_costs() {
+        let params = cheap_params();
+        let hash = hash_password("correct horse", &params).unwrap();
+        assert!(!needs_rehash(&hash, &params));
+        assert!(needs_rehash(&hash, &KdfParams { iterations: 2, ..params }));
+        assert!(needs_rehash(&hash, &KdfParams { memory_kib: 16, ..params }));
+    }
+
+    #[test]
+    fn constant_time_eq_compares_whole_values() {
+        assert!(constant_time_eq(b"abc", b"abc"));
+        assert!(!constant_time_eq(b"abc", b"abd"));
+        assert!(!constant_time_eq(b"abc", b"abcd"));
+    }
+
+    #[test]
+    fn glob_star_stays_within_a_folder() {
+        let regex = glob_regex("Journal/*.md");
+        assert!(regex.is_match("Journal/2024-01-01.md"));
+        assert!(!regex.is_match("Journal/2024/01.md"));
+        assert!(!regex.is_match("Other/Journal/a.md"));
+    }
+
+    #[test]
+    fn glob_double_star_spans_folders() {
+        let regex = glob_regex("**/*.md");
+        assert!(regex.is_match("a.md"));
+        assert!(regex.is_match("x/y/a.md"));
+        assert!(!regex.is_match("x/a.png"));
+
+        let regex = glob_regex("Archive/**");
+        assert!(regex.is_match("Archive"));
+        assert!(regex.is_match("Archive/old/a.md"));
+        assert!(!regex.is_match("Archived/a.md"));
+    }
+
+    #[test]
+    fn glob_escapes_regex_characters() {
+        let regex = glob_regex("/Notes (old)/a+b?.md");
+        assert!(regex.is_match("Notes (old)/a+b1.md"));
+        assert!(!regex.is_match("Notes old/aab1.md"));
+        assert!(!regex.is_match("Notes (old)/a+b/.md"));
+    }
+
+    #[test]
+    fn glob_case_follows_the_file_system() {
+        assert_eq!(glob_regex("Private/**").is_match("private/a.md"), CASE_INSENSITIVE_FS);
+    }
+
+    #[test]
+    fn vault_relative_stays_inside_the_vault() {
+        let root = Path::new("/vault");
+        assert_eq!(vault_relative(root, Path::new("/vault/a/b.md")).as_deref(), Some("a/b.md"));
+        assert_eq!(vault_relative(root, Path::new("./a/../b.md")).as_deref(), Some("b.md"));
+        assert_eq!(vault_relative(root, Path::new("../x.md")), None);
+        assert_eq!(vault_relative(root, Path::new("/vault/../x.md")), None);
+        assert_eq!(vault_relative(root, Path::new("/elsewhere/x.md")), None);
+    }
+
+    #[test]
+    fn failures_lock_an_account_after_the_limit() {
+        let mut store = AuthStore::default();
+        store.users.insert("ada".to_string(), user("ada"));
+        let limit = store.session_policy.max_failed_attempts;
+
+        for _ in 1..limit {
+            assert!(!store.record_failure("ada"));
+        }
+        assert!(store.locked_until("ada").is_none());
+        assert!(store.record_failure("ada"));
+        assert!(store.locked_until("ada").is_some());
+    }
+
+    #[test]
+    fn failures_for_unknown_users_are_not_tracked() {
+        let mut store = AuthStore::default();
+        store.users.insert("ada".to_string(), user("ada"));
+        assert!(!store.record_failure("nobody"));
+        assert!(store.failed_logins.is_empty());
+    }
+
+    #[test]
+    fn recovery_codes_work_once() {
+        let mut two_factor = TwoFactor {
+            secret: totp::generate_secret(),
+            enabled: true,
+            recovery_codes: vec![],
+            last_step: 0,
+        };
+        let codes = two_factor.reset_recovery_codes();
+        assert!(two_factor.accept(&codes[0].to_uppercase()));
+        assert!(!two_factor.accept(&codes[0]));
+        assert!(two_factor.accept(&codes[1]));
+        assert!(!two_factor.accept("aaaaa-aaaaa"));
+    }
+}
@@ -0,0 +1,319 @@
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+use crate::auth::VaultAction;
+use crate::conversations::{ConversationMessage, ConversationStore};
+use crate::vault::{relative_path, Vault};
+
+const DEFAULT_FOLDER: &str = "Chat History";
+const DEFAULT_TAG: &str = "ai-chat";
+const MAX_FILE_STEM_CHARS: usize = 100;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ChatExportOptions {
+    pub title: Option<String>,
+    /// Vault-relative folder for new notes; defaults to `Chat History`
+    pub folder: Option<String>,
+    /// File name for a new note; taken from the title or date when absent
+    pub filename: Option<String>,
+    /// Recorded when the messages don't say which model answered
+    pub model: Option<String>,
+    pub provider: Option<String>,
+    pub tags: Vec<String>,
+    /// Notes the chat used as context, linked from the exported note
+    pub context_notes: Vec<String>,
+    /// Vault-relative note to append the chat to instead of creating one
+    pub append_to: Option<String>,
+}
+
+// Double-quoted so titles with `:` or `#` stay valid YAML
+fn yaml_string(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// Drops characters that are invalid in file names on some platform or that
+// break wiki-links
+fn sanitize_file_stem(name: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' | '#' | '^' | '[' | ']' => ' ',
+            c if c.is_control() => ' ',
+            c => c,
+        })
+        .collect();
+    let collapsed = cleaned.split_whitespace().collect::<Vec<_>>().join(" ");
+    collapsed
+        .trim_matches('.')
+        .trim()
+        .chars()
+        .take(MAX_FILE_STEM_CHARS)
+        .collect()
+}
+
+// `name.md`, or `name 2.md`, `name 3.md`... if that's taken
+fn unique_file(dir: &Path, stem: &str) -> PathBuf {
+    let mut candidate = dir.join(format!("{}.md", stem));
+    let mut n = 2;
+    while candidate.exists() {
+        candidate = dir.join(format!("{} {}.md", stem, n));
+        n += 1;
+    }
+    candidate
+}
+
+// The link text for a context note: its vault-relative path without `.md`
+fn wiki_link(vault: &Vault, note: &str) -> String {
+    let path = Path::new(note);
+    let relative = path.strip_prefix(vault.path()).unwrap_or(path);
+    let target = relative.to_string_lossy().replace('\\', "/");
+    let target = target.strip_suffix(".md").unwrap_or(&target);
+    format!("[[{}]]", target)
+}
+
+fn role_label(role: &str) -> String {
+    match role {
+        "user" => "You".to_string(),
+        "assistant" => "Assistant".to_string(),
+        other => {
+            let mut chars = other.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => "Message".to_string(),
+            }
+        }
+    }
+}
+
+fn format_time(timestamp: &str) -> Option<String> {
+    chrono::DateTime::parse_from_rfc3339(timestamp)
+        .ok()
+        .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+}
+
+// Each message as a heading at `level` followed by its content
+fn render_messages(messages: &[ConversationMessage], level: usize) -> String {
+    let hashes = "#".repeat(level);
+    let mut out = String::new();
+    for message in messages {
+        let mut heading = format!("{} {}", hashes, role_label(&message.role));
+        if let Some(time) = format_time(&message.timestamp) {
+            heading.push_str(&format!(" · {}", time));
+        }
+        if let Some(call) = &message.tool_call {
+            heading.push_str(&format!(" `{}` ({})", call.name, call.status));
+        }
+        if let Some(model) = message.model.as_deref().filter(|_| message.role == "assistant") {
+            heading.push_str(&format!(" ({})", model));
+        }
+        out.push_str(&heading);
+        out.push_str("\n\n");
+        out.push_str(message.content.trim_end());
+        out.push_str("\n\n");
+    }
+    out
+}
+
+fn render_sources(links: &[String]) -> String {
+    if links.is_empty() {
+        String::new()
+    } else {
+        format!("> Context: {}\n\n", links.join(", "))
+    }
+}
+
+// The models that answered, in order of first appearance, or the one given
+fn models_used(messages: &[ConversationMessage], fallback: Option<&str>) -> Vec<String> {
+    let mut models: Vec<String> = Vec::new();
+    for model in messages.iter().filter_map(|m| m.model.as_deref()) {
+        if !models.iter().any(|m| m == model) {
+            models.push(model.to_string());
+        }
+    }
+    if models.is_empty() {
+        models.extend(fallback.map(str::to_string));
+    }
+    models
+}
+
+fn render_note(
+    title: &str,
+    messages: &[ConversationMessage],
+    options: &ChatExportOptions,
+    links: &[String],
+) -> String {
+    let mut out = String::from("---\n");
+    out.push_str(&format!("title: {}\n", yaml_string(title)));
+    out.push_str(&format!("date: {}\n", chrono::Local::now().to_rfc3339()));
+
+    let models = models_used(messages, options.model.as_deref());
+    match models.as_slice() {
+        [] => {}
+        [model] => out.push_str(&format!("model: {}\n", yaml_string(model))),
+        models => {
+            out.push_str("models:\n");
+            for model in models {
+                out.push_str(&format!("  - {}\n", yaml_string(model)));
+            }
+        }
+    }
+    let provider = options
+        .provider
+        .as_deref()
+        .or_else(|| messages.iter().find_map(|m| m.provider.as_deref()));
+    if let Some(provider) = provider {
+        out.push_str(&format!("provider: {}\n", yaml_string(provider)));
+    }
+
+    out.push_str("tags:\n");
+    let mut tags = vec![DEFAULT_TAG.to_string()];
+    for tag in &options.tags {
+        let tag = tag.trim().trim_start_matches('#');
+        if !tag.is_empty() && !tags.iter().any(|t| t == tag) {
+            tags.push(tag.to_string());
+        }
+    }
+    for tag in tags {
+        out.push_str(&format!("  - {}\n", yaml_string(&tag)));
+    }
+
+    if !links.is_empty() {
+        out.push_str("sources:\n");
+        for link in links {
+            out.push_str(&format!("  - {}\n", yaml_string(link)));
+        }
+    }
+    out.push_str("---\n\n");
+
+    out.push_str(&format!("# {}\n\n", title));
+    out.push_str(&render_sources(links));
+    out.push_str(&render_messages(messages, 2));
+    out
+}
+
+/// Where `export` will write: the note to append to, or a new note in the
+/// folder named after the file name, title or date, numbered if taken.
+pub fn destination(vault: &Vault, options: &ChatExportOptions) -> Result<PathBuf, String> {
+    if let Some(append_to) = &options.append_to {
+        return Ok(vault.path().join(relative_path(append_to)?));
+    }
+
+    let folder = relative_path(options.folder.as_deref().unwrap_or(DEFAULT_FOLDER))?;
+    let requested = options
+        .filename
+        .as_deref()
+        .map(|name| name.trim().strip_suffix(".md").unwrap_or(name.trim()))
+        .map(sanitize_file_stem)
+        .filter(|stem| !stem.is_empty());
+    let stem = requested
+        .or_else(|| options.title.as_deref().map(sanitize_file_stem).filter(|s| !s.is_empty()))
+        .unwrap_or_else(|| format!("chat-{}", chrono::Local::now().format("%Y-%m-%d_%H-%M-%S")));
+    Ok(unique_file(&vault.path().join(folder), &stem))
+}
+
+/// Write a chat to the vault as a markdown note at `path`, from
+/// `destination`, appending if it's an existing note.
+pub fn export(
+    vault: &Vault,
+    messages: &[ConversationMessage],
+    options: &ChatExportOptions,
+    path: &Path,
+) -> Result<(), String> {
+    if messages.is_empty() {
+        return Err("No messages to export".to_string());
+    }
+
+    let now = chrono::Local::now();
+    let title = options
+        .title
+        .as_deref()
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("Chat {}", now.format("%Y-%m-%d %H:%M")));
+    let links: Vec<String> = options
+        .context_notes
+        .iter()
+        .map(|note| wiki_link(vault, note))
+        .collect();
+
+    if options.append_to.is_some() && path.exists() {
+        let existing = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+
+        let mut content = existing.trim_end().to_string();
+        content.push_str(&format!("\n\n## {}\n\n", title));
+        content.push_str(&render_sources(&links));
+        content.push_str(&render_messages(messages, 3));
+
+        std::fs::write(path, content.trim_end().to_string() + "\n")
+            .map_err(|e| format!("Failed to write chat file: {}", e))?;
+        println!("💾 Appended chat to {:?}", path);
+        return Ok(());
+    }
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    let content = render_note(&title, messages, options, &links);
+    std::fs::write(path, content).map_err(|e| format!("Failed to write chat file: {}", e))?;
+
+    println!("💾 Exported chat to {:?}", path);
+    Ok(())
+}
+
+// Work out the note the chat goes in, which the user must be able to write,
+// then write it
+async fn export_authorized(
+    state: &crate::AppState,
+    vault: &Vault,
+    messages: &[ConversationMessage],
+    options: &ChatExportOptions,
+) -> Result<String, String> {
+    let path = destination(vault, options)?;
+    state.auth.authorize(vault.path(), &path, VaultAction::Write).await?;
+    export(vault, messages, options, &path)?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+/// Render a chat as a markdown note in the vault, or append it to an
+/// existing note with `options.append_to`. Returns the note's full path.
+#[tauri::command]
+pub async fn export_chat_to_vault(
+    messages: Vec<ConversationMessage>,
+    options: Option<ChatExportOptions>,
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<String, String> {
+    let vault_lock = state.vault.lock().await;
+    let Some(vault) = &*vault_lock else {
+        return Err("No vault is currently open".to_string());
+    };
+
+    export_authorized(&state, vault, &messages, &options.unwrap_or_default()).await
+}
+
+/// Export a stored conversation, taking its title and context notes unless
+/// `options` gives others.
+#[tauri::command]
+pub async fn export_conversation_to_vault(
+    id: String,
+    options: Option<ChatExportOptions>,
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<String, String> {
+    let owner = state.auth.current_user().await?;
+    let vault_lock = state.vault.lock().await;
+    let Some(vault) = &*vault_lock else {
+        return Err("No vault is currently open".to_string());
+    };
+
+    let conversation = ConversationStore::new(vault, owner).load(&id)?;
+    let mut options = options.unwrap_or_default();
+    options.title.get_or_insert(conversation.title);
+    if options.context_notes.is_empty() {
+        options.context_notes = conversation.context_notes;
+    }
+
+    export_authorized(&state, vault, &conversation.messages, &options).await
+}
@@ -0,0 +1,457 @@
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::vault::Vault;
+
+const CONVERSATIONS_DIR: &str = "conversations";
+const DEFAULT_TITLE: &str = "New conversation";
+const TITLE_MAX_CHARS: usize = 60;
+const SNIPPET_RADIUS: usize = 60;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationMessage {
+    pub role: String,
+    pub content: String,
+    /// RFC 3339; filled in when the message is appended without one
+    #[serde(default)]
+    pub timestamp: String,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub provider: Option<String>,
+    /// Set on `tool` messages recording a call the model made
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call: Option<ToolCallRecord>,
+}
+
+/// A tool call made during an agent chat; the message content holds its result.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCallRecord {
+    pub name: String,
+    pub arguments: serde_json::Value,
+    /// "ok", "error" or "declined"
+    pub status: String,
+}
+
+/// A chat and everything needed to pick it up again.
+///
+/// Stored as one JSON file per conversation under
+/// `<vault>/.aura/conversations/`, so it travels with the vault.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Conversation {
+    pub id: String,
+    pub title: String,
+    pub created_at: String,
+    pub updated_at: String,
+    /// Vault-relative paths of notes attached as context
+    #[serde(default)]
+    pub context_notes: Vec<String>,
+    #[serde(default)]
+    pub messages: Vec<ConversationMessage>,
+    /// The conversation this one was forked from
+    #[serde(default)]
+    pub forked_from: Option<String>,
+    /// The user it belongs to. Conversations started before the vault had
+    /// users have none, and are hidden once it does.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConversationSummary {
+    pub id: String,
+    pub title: String,
+    pub created_at: String,
+    pub updated_at: String,
+    pub message_count: usize,
+    pub context_notes: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConversationSearchHit {
+    pub id: String,
+    pub title: String,
+    /// Index of the matching message; `None` when only the title matched
+    pub message_index: Option<usize>,
+    pub snippet: String,
+}
+
+fn now() -> String {
+    chrono::Local::now().to_rfc3339()
+}
+
+fn new_id() -> String {
+    format!(
+        "{}-{:08x}",
+        chrono::Local::now().format("%Y%m%d-%H%M%S"),
+        rand::random::<u32>()
+    )
+}
+
+// Ids become file names, so anything that could leave the directory is refused
+fn validate_id(id: &str) -> Result<(), String> {
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(format!("Invalid conversation id: {}", id));
+    }
+    Ok(())
+}
+
+// A title from the first line of the opening message
+fn title_from(content: &str) -> String {
+    let line = content.lines().map(str::trim).find(|l| !l.is_empty()).unwrap_or("");
+    if line.is_empty() {
+        return DEFAULT_TITLE.to_string();
+    }
+    let mut title: String = line.chars().take(TITLE_MAX_CHARS).collect();
+    if line.chars().count() > TITLE_MAX_CHARS {
+        title.push('…');
+    }
+    title
+}
+
+// Text around the first case-insensitive match of `query`, on char boundaries
+fn snippet(text: &str, query: &str) -> Option<String> {
+    let lower = text.to_lowercase();
+    // Lowercasing can change byte lengths; fall back to the start when it does
+    let start = lower.find(query)?;
+    let (start, end) = if lower.len() == text.len() {
+        (start, start + query.len())
+    } else {
+        (0, 0)
+    };
+
+    let mut from = start.saturating_sub(SNIPPET_RADIUS);
+    while !text.is_char_boundary(from) {
+        from -= 1;
+    }
+    let mut to = (end + SNIPPET_RADIUS).min(text.len());
+    while !text.is_char_boundary(to) {
+        to += 1;
+    }
+
+    let mut snippet = text[from..to].replace('\n', " ");
+    if from > 0 {
+        snippet.insert(0, '…');
+    }
+    if to < text.len() {
+        snippet.push('…');
+    }
+    Some(snippet)
+}
+
+impl Conversation {
+    fn summary(&self) -> ConversationSummary {
+        ConversationSummary {
+            id: self.id.clone(),
+            title: self.title.clone(),
+            created_at: self.created_at.clone(),
+            updated_at: self.updated_at.clone(),
+            message_count: self.messages.len(),
+            context_notes: self.context_notes.clone(),
+        }
+    }
+
+    fn search(&self, query: &str) -> Option<ConversationSearchHit> {
+        let hit = |message_index, snippet| ConversationSearchHit {
+            id: self.id.clone(),
+            title: self.title.clone(),
+            message_index,
+            snippet,
+        };
+
+        if self.title.to_lowercase().contains(query) {
+            return Some(hit(None, self.title.clone()));
+        }
+        self.messages
+            .iter()
+            .enumerate()
+            .find_map(|(i, m)| snippet(&m.content, query).map(|s| hit(Some(i), s)))
+    }
+}
+
+/// The conversations saved in a vault, as seen by one user.
+pub struct ConversationStore {
+    dir: PathBuf,
+    // `None` sees every conversation, for vaults used without signing in
+    owner: Option<String>,
+}
+
+impl ConversationStore {
+    pub fn new(vault: &Vault, owner: Option<String>) -> Self {
+        Self {
+            dir: vault.path().join(".aura").join(CONVERSATIONS_DIR),
+            owner,
+        }
+    }
+
+    fn visible(&self, conversation: &Conversation) -> bool {
+        self.owner.is_none() || conversation.owner == self.owner
+    }
+
+    fn file(&self, id: &str) -> Result<PathBuf, String> {
+        validate_id(id)?;
+        Ok(self.dir.join(format!("{}.json", id)))
+    }
+
+    fn read(path: &Path) -> io::Result<Conversation> {
+        let content = std::fs::read_to_string(path)?;
+        serde_json::from_str(&content).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Every readable conversation, most recently updated first.
+    pub fn all(&self) -> Vec<Conversation> {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+
+        let mut conversations: Vec<Conversation> = entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().map_or(false, |ext| ext == "json"))
+            .filter_map(|path| match Self::read(&path) {
+                Ok(conversation) => self.visible(&conversation).then_some(conversation),
+                Err(e) => {
+                    println!("⚠️ Skipping unreadable conversation {:?}: {}", path, e);
+                    None
+                }
+            })
+            .collect();
+
+        // RFC 3339 timestamps from one clock sort correctly as strings
+        conversations.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+        conversations
+    }
+
+    pub fn load(&self, id: &str) -> Result<Conversation, String> {
+        let path = self.file(id)?;
+        if !path.exists() {
+            return Err(format!("Conversation not found: {}", id));
+        }
+        let conversation = Self::read(&path).map_err(|e| format!("Failed to read conversation: {}", e))?;
+        // Someone else's conversation is reported like a missing one
+        if !self.visible(&conversation) {
+            return Err(format!("Conversation not found: {}", id));
+        }
+        Ok(conversation)
+    }
+
+    pub fn save(&self, conversation: &Conversation) -> Result<(), String> {
+        let path = self.file(&conversation.id)?;
+        std::fs::create_dir_all(&self.dir)
+            .map_err(|e| format!("Failed to create conversations directory: {}", e))?;
+
+        let content = serde_json::to_string_pretty(conversation)
+            .map_err(|e| format!("Failed to serialize conversation: {}", e))?;
+
+        // Write to a temp file first so a crash never leaves a half-written conversation
+        let tmp_path = path.with_extension("json.tmp");
+        std::fs::write(&tmp_path, content)
+            .and_then(|_| std::fs::rename(&tmp_path, &path))
+            .map_err(|e| format!("Failed to save conversation: {}", e))
+    }
+
+    pub fn delete(&self, id: &str) -> Result<(), String> {
+        self.load(id)?;
+        std::fs::remove_file(self.file(id)?).map_err(|e| format!("Failed to delete conversation: {}", e))
+    }
+
+    pub fn create(&self, title: Option<String>, context_notes: Vec<String>) -> Result<Conversation, String> {
+        let timestamp = now();
+        let conversation = Conversation {
+            id: new_id(),
+            title: title
+                .filter(|t| !t.trim().is_empty())
+                .unwrap_or_else(|| DEFAULT_TITLE.to_string()),
+            created_at: timestamp.clone(),
+            updated_at: timestamp,
+            context_notes,
+            messages: Vec::new(),
+            forked_from: None,
+            owner: self.owner.clone(),
+        };
+        self.save(&conversation)?;
+        Ok(conversation)
+    }
+
+    /// Add a message, titling an untitled conversation after its first user message.
+    pub fn append(&self, id: &str, mut message: ConversationMessage) -> Result<Conversation, String> {
+        let mut conversation = self.load(id)?;
+        if message.timestamp.is_empty() {
+            message.timestamp = now();
+        }
+        if conversation.title == DEFAULT_TITLE && message.role == "user" {
+            conversation.title = title_from(&message.content);
+        }
+        conversation.updated_at = message.timestamp.clone();
+        conversation.messages.push(message);
+        self.save(&conversation)?;
+        Ok(conversation)
+    }
+
+    /// Copy a conversation as a new one, keeping messages up to and
+    /// including `through` (all of them when `None`).
+    pub fn fork(&self, id: &str, through: Option<usize>, title: Option<String>) -> Result<Conversation, String> {
+        let source = self.load(id)?;
+        let keep = through.map_or(source.messages.len(), |i| (i + 1).min(source.messages.len()));
+        let timestamp = now();
+
+        let fork = Conversation {
+            id: new_id(),
+            title: title
+                .filter(|t| !t.trim().is_empty())
+                .unwrap_or_else(|| format!("{} (fork)", source.title)),
+            created_at: timestamp.clone(),
+            updated_at: timestamp,
+            context_notes: source.context_notes.clone(),
+            messages: source.messages[..keep].to_vec(),
+            forked_from: Some(source.id),
+            owner: self.owner.clone(),
+        };
+        self.save(&fork)?;
+        Ok(fork)
+    }
+}
+
+// Runs `f` on the signed-in user's conversations
+async fn with_store<T>(
+    state: &crate::AppState,
+    f: impl FnOnce(&ConversationStore) -> Result<T, String>,
+) -> Result<T, String> {
+    let owner = state.auth.current_user().await?;
+    match &*state.vault.lock().await {
+        Some(vault) => f(&ConversationStore::new(vault, owner)),
+        None => Err("No vault opened".to_string()),
+    }
+}
+
+#[tauri::command]
+pub async fn list_conversations(
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<Vec<ConversationSummary>, String> {
+    with_store(&state, |store| {
+        Ok(store.all().iter().map(Conversation::summary).collect())
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn load_conversation(
+    id: String,
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<Conversation, String> {
+    with_store(&state, |store| store.load(&id)).await
+}
+
+#[tauri::command]
+pub async fn create_conversation(
+    title: Option<String>,
+    context_notes: Option<Vec<String>>,
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<Conversation, String> {
+    with_store(&state, |store| {
+        let conversation = store.create(title, context_notes.unwrap_or_default())?;
+        println!("💬 Created conversation {}", conversation.id);
+        Ok(conversation)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn append_conversation_message(
+    id: String,
+    message: ConversationMessage,
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<Conversation, String> {
+    with_store(&state, |store| store.append(&id, message)).await
+}
+
+/// Replace the notes attached to a conversation as context.
+#[tauri::command]
+pub async fn set_conversation_context(
+    id: String,
+    context_notes: Vec<String>,
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<Conversation, String> {
+    with_store(&state, |store| {
+        let mut conversation = store.load(&id)?;
+        conversation.context_notes = context_notes;
+        conversation.updated_at = now();
+        store.save(&conversation)?;
+        Ok(conversation)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn rename_conversation(
+    id: String,
+    title: String,
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<Conversation, String> {
+    let title = title.trim().to_string();
+    if title.is_empty() {
+        return Err("Title cannot be empty".to_string());
+    }
+
+    with_store(&state, |store| {
+        let mut conversation = store.load(&id)?;
+        conversation.title = title;
+        conversation.updated_at = now();
+        store.save(&conversation)?;
+        Ok(conversation)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn fork_conversation(
+    id: String,
+    through_message: Option<usize>,
+    title: Option<String>,
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<Conversation, String> {
+    with_store(&state, |store| {
+        let fork = store.fork(&id, through_message, title)?;
+        println!("💬 Forked conversation {} as {}", id, fork.id);
+        Ok(fork)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn delete_conversation(
+    id: String,
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<(), String> {
+    with_store(&state, |store| {
+        store.delete(&id)?;
+        println!("🗑️ Deleted conversation {}", id);
+        Ok(())
+    })
+    .await
+}
+
+/// Conversations whose title or messages contain `query`, most recent first,
+/// with the first match in each.
+#[tauri::command]
+pub async fn search_conversations(
+    query: String,
+    limit: Option<usize>,
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<Vec<ConversationSearchHit>, String> {
+    let query = query.trim().to_lowercase();
+    if query.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    with_store(&state, |store| {
+        Ok(store
+            .all()
+            .iter()
+            .filter_map(|conversation| conversation.search(&query))
+            .take(limit.unwrap_or(50))
+            .collect())
+    })
+    .await
+}
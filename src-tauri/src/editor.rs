@@ -1,9 +1,14 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use tauri::State;
 use tokio::sync::Mutex;
 
+use crate::auth::VaultAction;
+use crate::embeds::{self, EmbeddedBlock};
+use crate::vault;
+use crate::wikilinks::{self, NoteResolution};
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct EditorPreferences {
     pub theme: String,
@@ -77,6 +82,11 @@ impl EditorManager {
         }
     }
 
+    /// A snapshot of the open file, cursor and selection.
+    pub async fn current_state(&self) -> EditorState {
+        self.state.lock().await.clone()
+    }
+
     pub async fn save_preferences(&self) -> Result<(), Box<dyn std::error::Error>> {
         let config_path = self.get_config_path()?;
         
@@ -182,7 +192,13 @@ pub async fn save_file(
     state: State<'_, crate::AppState>,
 ) -> Result<(), String> {
     let manager = &state.editor;
-    let file_path = PathBuf::from(path);
+    // Full paths, which must lie in the open vault
+    let vault = state.vault.lock().await.clone().ok_or("No vault opened")?;
+    let relative = Path::new(&path)
+        .strip_prefix(vault.path())
+        .map_err(|_| format!("{} is outside the vault", path))?;
+    let file_path = vault.path().join(vault::relative_path(&relative.to_string_lossy())?);
+    state.auth.authorize(vault.path(), &file_path, VaultAction::Write).await?;
     
     // Ensure the parent directory exists
     if let Some(parent) = file_path.parent() {
@@ -205,7 +221,12 @@ pub async fn read_file(
     state: State<'_, crate::AppState>,
 ) -> Result<String, String> {
     let manager = &state.editor;
-    let file_path = PathBuf::from(&path);
+    let vault = state.vault.lock().await.clone().ok_or("No vault opened")?;
+    let relative = Path::new(&path)
+        .strip_prefix(vault.path())
+        .map_err(|_| format!("{} is outside the vault", path))?;
+    let file_path = vault.path().join(vault::relative_path(&relative.to_string_lossy())?);
+    state.auth.authorize(vault.path(), &file_path, VaultAction::Read).await?;
     
     if !file_path.exists() {
         return Err("File does not exist".to_string());
@@ -235,19 +256,66 @@ pub async fn get_initial_file(
 #[tauri::command]
 pub async fn open_note(
     title: String,
+    source_path: Option<String>,
+    create_if_missing: Option<bool>,
+    template: Option<String>,
     state: State<'_, crate::AppState>,
-) -> Result<(), String> {
-    let _manager = &state.editor;
-    // For now, just log the request
+) -> Result<NoteResolution, String> {
     println!("Opening note: {}", title);
     
-    // In a full implementation, this would:
-    // 1. Search for the note file
-    // 2. Create the note if it doesn't exist
-    // 3. Open the note in the editor
-    // 4. Update the navigation history
+    let vault_lock = state.vault.lock().await;
+    let Some(vault) = &*vault_lock else {
+        return Err("No vault opened".to_string());
+    };
     
-    Ok(())
+    let target = wikilinks::parse_link_target(&title);
+    
+    // The link graph already knows every note and alias; fall back to a vault scan
+    let graph_lock = state.link_graph.lock().await;
+    let candidates = match &*graph_lock {
+        Some(graph) => graph.resolve(&target.note, source_path.as_deref()),
+        None => wikilinks::resolve_link(vault, &target.note, source_path.as_deref())
+            .map_err(|e| format!("Failed to resolve note: {}", e))?,
+    };
+    drop(graph_lock);
+    
+    // Pick from the notes the user can read, and only offer those. When
+    // every match is unreadable, opening the best one reports why.
+    let readable = state.auth.readable(vault.path(), candidates.clone()).await;
+    if readable.is_empty() {
+        if let Some(path) = candidates.first() {
+            state.auth.authorize(vault.path(), Path::new(path), VaultAction::Read).await?;
+        }
+    }
+    let candidates = readable;
+    
+    let (path, created) = match candidates.first() {
+        Some(path) => (Some(path.clone()), false),
+        None if create_if_missing.unwrap_or(true) => {
+            let new_path = wikilinks::new_note_path(&target.note)?;
+            state.auth.authorize(vault.path(), &new_path, VaultAction::Write).await?;
+            if let Some(template) = template.as_deref() {
+                state.auth.authorize(vault.path(), Path::new(template), VaultAction::Read).await?;
+            }
+            let path = wikilinks::create_note(vault, &target.note, template.as_deref())?;
+            (Some(path), true)
+        }
+        None => (None, false),
+    };
+    
+    // Track the resolved note as the current file
+    if let Some(path) = &path {
+        let mut editor_state = state.editor.state.lock().await;
+        editor_state.current_file = Some(vault.path().join(path));
+    }
+    
+    Ok(NoteResolution {
+        path,
+        created,
+        candidates: if candidates.len() > 1 { candidates } else { vec![] },
+        heading: target.heading,
+        block_id: target.block_id,
+    })
 }
 
 #[tauri::command]
@@ -255,34 +323,55 @@ pub async fn search_by_tag(
     tag: String,
     state: State<'_, crate::AppState>,
 ) -> Result<Vec<String>, String> {
-    let _manager = &state.editor;
-    // For now, just log the request
     println!("Searching by tag: {}", tag);
     
-    // In a full implementation, this would:
-    // 1. Search through all markdown files
-    // 2. Find files containing the tag
-    // 3. Return the list of matching files
-    
-    Ok(vec![])
+    let index_lock = state.tag_index.lock().await;
+    match &*index_lock {
+        Some(index) => Ok(state.auth.readable(index.vault_path(), index.notes_with_tag(&tag)).await),
+        None => Err("No vault opened".to_string()),
+    }
 }
 
 #[tauri::command]
 pub async fn get_embedded_block(
     note_title: String,
     block_id: Option<String>,
+    source_path: Option<String>,
     state: State<'_, crate::AppState>,
-) -> Result<Option<String>, String> {
-    let _manager = &state.editor;
-    // For now, just log the request
+) -> Result<Option<EmbeddedBlock>, String> {
     println!("Getting embedded block: {} {:?}", note_title, block_id);
     
-    // In a full implementation, this would:
-    // 1. Find the note file
-    // 2. If block_id is provided, find the specific block
-    // 3. Return the block content
+    let vault_lock = state.vault.lock().await;
+    let Some(vault) = &*vault_lock else {
+        return Err("No vault opened".to_string());
+    };
+    
+    let mut target = wikilinks::parse_link_target(&note_title);
+    if let Some(block_id) = block_id.filter(|b| !b.is_empty()) {
+        target.block_id = Some(block_id);
+    }
+    
+    let graph_lock = state.link_graph.lock().await;
+    let resolve_any = |name: &str, source: Option<&str>| match &*graph_lock {
+        Some(graph) => graph.resolve(name, source),
+        None => wikilinks::resolve_link(vault, name, source).unwrap_or_default(),
+    };
+    // Notes the user can't read don't resolve, so nested embeds of them are
+    // left as links
+    let access = state.auth.vault_access(vault.path()).await;
+    let resolve = |name: &str, source: Option<&str>| -> Vec<String> {
+        resolve_any(name, source)
+            .into_iter()
+            .filter(|path| access.allows(Path::new(path), VaultAction::Read))
+            .collect()
+    };
+    if resolve(&target.note, source_path.as_deref()).is_empty() {
+        if let Some(path) = resolve_any(&target.note, source_path.as_deref()).first() {
+            access.check(Path::new(path), VaultAction::Read)?;
+        }
+    }
     
-    Ok(Some("Sample embedded content".to_string()))
+    Ok(embeds::get_embed(vault, &resolve, &target, source_path.as_deref()))
 }
 
 #[tauri::command]
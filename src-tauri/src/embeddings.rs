@@ -0,0 +1,675 @@
+// embeddings.rs - On-disk vector index of note chunks for retrieval-augmented chat
+
+use base64::{engine::general_purpose, Engine as _};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use tauri::{AppHandle, Manager, State};
+
+use crate::ai_provider::{self, AiProvider};
+use crate::ai_settings::{get_ai_settings, AISettings};
+use crate::auth::{VaultAccess, VaultAction};
+use crate::frontmatter;
+use crate::markdown::{heading_level, mask_code};
+use crate::vault::{self, Vault};
+
+const INDEX_FILE: &str = "embeddings.json";
+const INDEX_VERSION: u32 = 2;
+
+// Sections longer than this are split at paragraph breaks
+const MAX_CHUNK_CHARS: usize = 2000;
+const EMBED_BATCH_SIZE: usize = 32;
+
+/// A heading section (or part of one) with its embedding.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NoteChunk {
+    /// Heading trail such as `Setup > Install`; `None` before the first heading
+    pub heading: Option<String>,
+    /// 1-based line where the chunk starts
+    pub start_line: usize,
+    pub text: String,
+    // Stored as base64 little-endian f32s; JSON number arrays are several times larger
+    #[serde(serialize_with = "serialize_vector", deserialize_with = "deserialize_vector")]
+    vector: Vec<f32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct IndexedNote {
+    modified: i64,
+    /// SHA-256 of the content; a note that was only touched isn't re-embedded
+    hash: String,
+    chunks: Vec<NoteChunk>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChunkHit {
+    pub path: String,
+    pub heading: Option<String>,
+    pub start_line: usize,
+    pub text: String,
+    pub score: f32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RelatedNote {
+    pub path: String,
+    pub score: f32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmbeddingSyncStats {
+    pub embedded_notes: usize,
+    pub removed_notes: usize,
+    pub total_chunks: usize,
+    /// Notes that couldn't be read, left out until they can
+    pub unreadable_notes: Vec<String>,
+}
+
+// What refreshing one note did
+enum Refreshed {
+    Embedded,
+    Unchanged,
+    Unreadable,
+}
+
+/// Embeddings of every note in a vault, chunked by heading.
+///
+/// Stored under `<vault>/.aura/embeddings.json`. Vectors from different
+/// models can't be compared, so switching model empties the index.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmbeddingIndex {
+    version: u32,
+    #[serde(skip)]
+    vault_path: PathBuf,
+    /// Provider, endpoint and model the vectors came from
+    model: String,
+    notes: HashMap<String, IndexedNote>,
+}
+
+fn serialize_vector<S: Serializer>(vector: &[f32], serializer: S) -> Result<S::Ok, S::Error> {
+    let bytes: Vec<u8> = vector.iter().flat_map(|v| v.to_le_bytes()).collect();
+    serializer.serialize_str(&general_purpose::STANDARD.encode(bytes))
+}
+
+fn deserialize_vector<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<f32>, D::Error> {
+    let encoded = String::deserialize(deserializer)?;
+    let bytes = general_purpose::STANDARD
+        .decode(encoded)
+        .map_err(serde::de::Error::custom)?;
+    Ok(bytes
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect())
+}
+
+fn modified_time(path: &Path) -> i64 {
+    std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// Hex SHA-256 of a note's text, to tell when it has changed
+pub fn content_hash(content: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(content.as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() || a.is_empty() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b: f32 = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
+
+// Split an over-long section at blank lines, keeping each piece's start line
+fn split_section(text: &str, start_line: usize) -> Vec<(usize, String)> {
+    if text.len() <= MAX_CHUNK_CHARS {
+        return vec![(start_line, text.to_string())];
+    }
+
+    let mut pieces = Vec::new();
+    let mut current = String::new();
+    let mut current_line = start_line;
+    let mut line = start_line;
+    for paragraph in text.split("\n\n") {
+        if !current.is_empty() && current.len() + paragraph.len() > MAX_CHUNK_CHARS {
+            pieces.push((current_line, std::mem::take(&mut current)));
+            current_line = line;
+        }
+        if !current.is_empty() {
+            current.push_str("\n\n");
+        }
+        current.push_str(paragraph);
+        line += paragraph.matches('\n').count() + 2;
+    }
+    if !current.trim().is_empty() {
+        pieces.push((current_line, current));
+    }
+    pieces
+}
+
+/// Split a note body into heading sections: `(heading trail, start line, text)`.
+pub fn chunk_note(content: &str) -> Vec<(Option<String>, usize, String)> {
+    let body_start = frontmatter::locate(content).map_or(0, |block| block.body_start);
+    let masked = mask_code(content);
+
+    let mut chunks = Vec::new();
+    let mut trail: Vec<(usize, String)> = Vec::new();
+    let mut section = String::new();
+    let mut section_line = 1;
+    let mut offset = 0;
+
+    let mut flush = |trail: &[(usize, String)], section: &mut String, line: usize| {
+        if !section.trim().is_empty() {
+            let heading = (!trail.is_empty()).then(|| {
+                trail.iter().map(|(_, h)| h.as_str()).collect::<Vec<_>>().join(" > ")
+            });
+            for (start, text) in split_section(section.trim_end(), line) {
+                chunks.push((heading.clone(), start, text));
+            }
+        }
+        section.clear();
+    };
+
+    for (index, line) in content.split_inclusive('\n').enumerate() {
+        let line_start = offset;
+        offset += line.len();
+        if line_start < body_start {
+            continue;
+        }
+
+        if let Some((level, text)) = heading_level(&masked[line_start..offset]) {
+            flush(&trail, &mut section, section_line);
+            trail.retain(|(l, _)| *l < level);
+            trail.push((level, text.to_string()));
+            section_line = index + 1;
+        } else if section.is_empty() && line.trim().is_empty() {
+            section_line = index + 2;
+            continue;
+        }
+        section.push_str(line);
+    }
+    flush(&trail, &mut section, section_line);
+
+    chunks
+}
+
+impl EmbeddingIndex {
+    fn empty(vault_path: PathBuf) -> Self {
+        Self {
+            version: INDEX_VERSION,
+            vault_path,
+            model: String::new(),
+            notes: HashMap::new(),
+        }
+    }
+
+    /// Load the persisted index for a vault. Nothing is embedded here; that
+    /// needs a provider and happens in `sync`.
+    pub fn open(vault: &Vault) -> Self {
+        let vault_path = vault.path().to_path_buf();
+        let index_path = vault_path.join(".aura").join(INDEX_FILE);
+
+        let mut index = match std::fs::read_to_string(&index_path) {
+            Ok(content) => match serde_json::from_str::<EmbeddingIndex>(&content) {
+                Ok(index) if index.version == INDEX_VERSION => index,
+                Ok(_) => {
+                    println!("🧠 Embedding index format changed, starting over");
+                    Self::empty(vault_path.clone())
+                }
+                Err(e) => {
+                    println!("⚠️ Embedding index is corrupt, starting over: {}", e);
+                    Self::empty(vault_path.clone())
+                }
+            },
+            Err(_) => Self::empty(vault_path.clone()),
+        };
+        index.vault_path = vault_path;
+
+        println!("🧠 Embedding index loaded: {} notes", index.notes.len());
+        index
+    }
+
+    pub fn vault_path(&self) -> &Path {
+        &self.vault_path
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let aura_dir = self.vault_path.join(".aura");
+        std::fs::create_dir_all(&aura_dir)?;
+
+        let content = serde_json::to_string(self)
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+
+        // Write to a temp file first so a crash never leaves a half-written index
+        let tmp_path = aura_dir.join(format!("{}.tmp", INDEX_FILE));
+        std::fs::write(&tmp_path, content)?;
+        std::fs::rename(tmp_path, aura_dir.join(INDEX_FILE))
+    }
+
+    /// Embed new and changed notes and drop deleted ones. Progress is saved
+    /// even if the provider fails part way. Only notes `embeddable` accepts
+    /// are sent to the provider; others keep what they had.
+    pub async fn sync(
+        &mut self,
+        vault: &Vault,
+        provider: &dyn AiProvider,
+        model: &str,
+        embeddable: impl Fn(&str) -> bool,
+    ) -> Result<EmbeddingSyncStats, String> {
+        if self.model != model {
+            if !self.notes.is_empty() {
+                println!("🧠 Embedding model changed, re-embedding all notes");
+            }
+            self.notes.clear();
+            self.model = model.to_string();
+        }
+
+        let notes: Vec<PathBuf> = vault
+            .list_markdown_files()
+            .map_err(|e| format!("Failed to list files: {}", e))?
+            .into_iter()
+            .filter(|p| vault::is_note(p))
+            .collect();
+
+        let mut seen = HashSet::new();
+        let mut stale = Vec::new();
+        for note in notes {
+            let Ok(relative) = note.strip_prefix(&self.vault_path) else { continue };
+            let key = relative.to_string_lossy().to_string();
+            // Only notes whose mtime moved are read and hashed
+            let modified = modified_time(&note);
+            if self.notes.get(&key).map_or(true, |n| n.modified != modified) && embeddable(&key) {
+                stale.push((key.clone(), note));
+            }
+            seen.insert(key);
+        }
+
+        let before = self.notes.len();
+        self.notes.retain(|key, _| seen.contains(key));
+        let mut stats = EmbeddingSyncStats {
+            embedded_notes: 0,
+            removed_notes: before - self.notes.len(),
+            total_chunks: 0,
+            unreadable_notes: Vec::new(),
+        };
+
+        let mut result = Ok(());
+        let checked = stale.len();
+        for (key, path) in stale {
+            match self.refresh_note(provider, key.clone(), &path).await {
+                Ok(Refreshed::Embedded) => stats.embedded_notes += 1,
+                Ok(Refreshed::Unchanged) => {}
+                Ok(Refreshed::Unreadable) => stats.unreadable_notes.push(key),
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+
+        if checked > 0 || stats.removed_notes > 0 {
+            println!(
+                "🧠 Embedded {} notes, removed {}",
+                stats.embedded_notes, stats.removed_notes
+            );
+            if let Err(e) = self.save() {
+                println!("⚠️ Failed to save embedding index: {}", e);
+            }
+        }
+
+        stats.total_chunks = self.notes.values().map(|n| n.chunks.len()).sum();
+        result.map(|_| stats)
+    }
+
+    /// Re-embed notes the file watcher reported as changed and drop deleted
+    /// ones. Returns how many notes were re-embedded or removed.
+    pub async fn update_paths(
+        &mut self,
+        paths: &[PathBuf],
+        provider: &dyn AiProvider,
+        model: &str,
+        embeddable: impl Fn(&str) -> bool,
+    ) -> Result<usize, String> {
+        // Vectors from another model can't be mixed in; the next sync starts over
+        if self.model != model {
+            return Ok(0);
+        }
+
+        let mut updated = 0;
+        let mut result = Ok(());
+        for path in paths {
+            if !vault::is_note(path) {
+                continue;
+            }
+            let Ok(relative) = path.strip_prefix(&self.vault_path) else { continue };
+            let key = relative.to_string_lossy().to_string();
+
+            if path.is_file() {
+                if !embeddable(&key) {
+                    continue;
+                }
+                match self.refresh_note(provider, key, path).await {
+                    Ok(Refreshed::Embedded | Refreshed::Unreadable) => updated += 1,
+                    Ok(Refreshed::Unchanged) => {}
+                    Err(e) => {
+                        result = Err(e);
+                        break;
+                    }
+                }
+            } else if self.notes.remove(&key).is_some() {
+                updated += 1;
+            }
+        }
+
+        if updated > 0 {
+            if let Err(e) = self.save() {
+                println!("⚠️ Failed to save embedding index: {}", e);
+            }
+        }
+        result.map(|_| updated)
+    }
+
+    // Embeds the note unless its content hash is unchanged. A note that
+    // can't be read loses its old chunks rather than stopping the others;
+    // only provider errors are returned
+    async fn refresh_note(&mut self, provider: &dyn AiProvider, key: String, path: &Path) -> Result<Refreshed, String> {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) => {
+                println!("⚠️ Skipping unreadable note {:?}: {}", path, e);
+                self.notes.remove(&key);
+                return Ok(Refreshed::Unreadable);
+            }
+        };
+        let modified = modified_time(path);
+        let hash = content_hash(&content);
+
+        if let Some(note) = self.notes.get_mut(&key) {
+            if note.hash == hash {
+                note.modified = modified;
+                return Ok(Refreshed::Unchanged);
+            }
+        }
+
+        let chunks = self.embed_note(provider, path, &content).await?;
+        self.notes.insert(key, IndexedNote { modified, hash, chunks });
+        Ok(Refreshed::Embedded)
+    }
+
+    async fn embed_note(&self, provider: &dyn AiProvider, path: &Path, content: &str) -> Result<Vec<NoteChunk>, String> {
+        let title = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+
+        let sections = chunk_note(content);
+        // The title and heading give short sections enough context to match
+        let inputs: Vec<String> = sections
+            .iter()
+            .map(|(heading, _, text)| match heading {
+                Some(heading) => format!("{}\n{}\n\n{}", title, heading, text),
+                None => format!("{}\n\n{}", title, text),
+            })
+            .collect();
+
+        let mut vectors = Vec::with_capacity(inputs.len());
+        for batch in inputs.chunks(EMBED_BATCH_SIZE) {
+            vectors.extend(provider.embed(batch).await?);
+        }
+        if vectors.len() != sections.len() {
+            return Err(format!(
+                "Expected {} embeddings, got {}",
+                sections.len(),
+                vectors.len()
+            ));
+        }
+
+        Ok(sections
+            .into_iter()
+            .zip(vectors)
+            .map(|((heading, start_line, text), vector)| NoteChunk {
+                heading,
+                start_line,
+                text,
+                vector,
+            })
+            .collect())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.notes.is_empty()
+    }
+
+    /// The `limit` notes whose content is closest to the note at `path`,
+    /// comparing the mean of each note's chunk vectors.
+    pub fn related(&self, path: &str, limit: usize, visible: impl Fn(&str) -> bool) -> Vec<RelatedNote> {
+        let Some(target) = self.notes.get(path).and_then(note_vector) else {
+            return Vec::new();
+        };
+
+        let mut related: Vec<RelatedNote> = self
+            .notes
+            .iter()
+            .filter(|(other, _)| other.as_str() != path && visible(other))
+            .filter_map(|(other, note)| {
+                note_vector(note).map(|vector| RelatedNote {
+                    path: other.clone(),
+                    score: cosine_similarity(&target, &vector),
+                })
+            })
+            .collect();
+
+        related.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+        related.truncate(limit);
+        related
+    }
+
+    /// The `limit` chunks most similar to a query vector, from notes
+    /// `visible` accepts.
+    pub fn search(&self, query: &[f32], limit: usize, visible: impl Fn(&str) -> bool) -> Vec<ChunkHit> {
+        let mut scored: Vec<(f32, &String, &NoteChunk)> = self
+            .notes
+            .iter()
+            .filter(|(path, _)| visible(path))
+            .flat_map(|(path, note)| {
+                note.chunks
+                    .iter()
+                    .map(move |chunk| (cosine_similarity(query, &chunk.vector), path, chunk))
+            })
+            .collect();
+
+        scored.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+        scored
+            .into_iter()
+            .take(limit)
+            .map(|(score, path, chunk)| ChunkHit {
+                path: path.clone(),
+                heading: chunk.heading.clone(),
+                start_line: chunk.start_line,
+                text: chunk.text.clone(),
+                score,
+            })
+            .collect()
+    }
+}
+
+// The mean of a note's unit-length chunk vectors, so long sections don't
+// outweigh short ones
+fn note_vector(note: &IndexedNote) -> Option<Vec<f32>> {
+    let dimensions = note.chunks.first()?.vector.len();
+    let mut sum = vec![0.0f32; dimensions];
+    for chunk in note.chunks.iter().filter(|c| c.vector.len() == dimensions) {
+        let norm = chunk.vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+        if norm == 0.0 {
+            continue;
+        }
+        for (total, x) in sum.iter_mut().zip(&chunk.vector) {
+            *total += x / norm;
+        }
+    }
+    Some(sum)
+}
+
+// Identifies where vectors came from; the same model name on another server
+// may be a different model
+fn model_key(settings: &AISettings) -> Result<String, String> {
+    let model = settings
+        .embedding_model()
+        .ok_or_else(ai_provider::no_embedding_model)?;
+    Ok(format!("{:?}|{}|{}", settings.provider, settings.endpoint, model))
+}
+
+// The open vault's index, reloading it if another vault has been opened since
+fn index_for<'a>(slot: &'a mut Option<EmbeddingIndex>, vault: &Vault) -> &'a mut EmbeddingIndex {
+    if slot.as_ref().map_or(false, |index| index.vault_path() != vault.path()) {
+        *slot = None;
+    }
+    slot.get_or_insert_with(|| EmbeddingIndex::open(vault))
+}
+
+// What embedding the open vault needs: the active profile's provider and
+// model, and what the user may send to it
+struct Embedder {
+    vault: Vault,
+    provider: Box<dyn AiProvider>,
+    model: String,
+    access: VaultAccess,
+}
+
+impl Embedder {
+    async fn load(app: &AppHandle, state: &crate::AppState) -> Result<Self, String> {
+        // Settings read the vault lock themselves, so fetch them before taking it
+        let settings = get_ai_settings(app.clone())
+            .await?
+            .ok_or("No AI settings configured")?;
+        let model = model_key(&settings)?;
+        let vault = state.vault.lock().await.clone().ok_or("No vault opened")?;
+        Ok(Self {
+            access: state.auth.vault_access(vault.path()).await,
+            vault,
+            provider: ai_provider::provider_for(settings),
+            model,
+        })
+    }
+
+    // Notes the user can't read are never sent to the provider, which may
+    // be a hosted service
+    fn embeddable(&self, path: &str) -> bool {
+        self.access.allows(Path::new(path), VaultAction::Read)
+    }
+
+    async fn sync(&self, index: &mut EmbeddingIndex) -> Result<EmbeddingSyncStats, String> {
+        index
+            .sync(&self.vault, self.provider.as_ref(), &self.model, |path| self.embeddable(path))
+            .await
+    }
+}
+
+/// Bring the index up to date and return the `limit` chunks closest to `query`.
+pub async fn retrieve(
+    app: &AppHandle,
+    state: &crate::AppState,
+    query: &str,
+    limit: usize,
+) -> Result<Vec<ChunkHit>, String> {
+    let embedder = Embedder::load(app, state).await?;
+    let mut index_lock = state.embedding_index.lock().await;
+    let index = index_for(&mut index_lock, &embedder.vault);
+    embedder.sync(index).await?;
+
+    let query_vector = embedder
+        .provider
+        .embed(&[query.to_string()])
+        .await?
+        .into_iter()
+        .next()
+        .ok_or("No embedding returned for the query")?;
+
+    // Excerpts only come from notes the user can read
+    Ok(index.search(&query_vector, limit, |path| embedder.embeddable(path)))
+}
+
+/// Re-embed notes changed on disk, for the file watcher. Runs in the
+/// background, and only for vaults that have been embedded before, so editing
+/// never needs the AI provider unless embeddings are in use.
+pub async fn refresh_paths(app: AppHandle, paths: Vec<PathBuf>) {
+    let state = app.state::<crate::AppState>();
+    let has_embeddings = state
+        .embedding_index
+        .lock()
+        .await
+        .as_ref()
+        .map_or(false, |index| !index.is_empty());
+    if !has_embeddings {
+        return;
+    }
+
+    let embedder = match Embedder::load(&app, &state).await {
+        Ok(embedder) => embedder,
+        Err(e) => {
+            println!("⚠️ Skipping embedding update: {}", e);
+            return;
+        }
+    };
+
+    let mut index_lock = state.embedding_index.lock().await;
+    let Some(index) = index_lock
+        .as_mut()
+        .filter(|index| index.vault_path() == embedder.vault.path())
+    else {
+        return;
+    };
+    match index
+        .update_paths(&paths, embedder.provider.as_ref(), &embedder.model, |path| {
+            embedder.embeddable(path)
+        })
+        .await
+    {
+        Ok(0) => {}
+        Ok(updated) => println!("🧠 Updated embeddings for {} notes", updated),
+        Err(e) => println!("⚠️ Failed to update embeddings: {}", e),
+    }
+}
+
+/// Embed new and changed notes in the open vault with the active AI profile.
+#[tauri::command]
+pub async fn index_vault_embeddings(
+    app: AppHandle,
+    state: State<'_, crate::AppState>,
+) -> Result<EmbeddingSyncStats, String> {
+    let embedder = Embedder::load(&app, &state).await?;
+    let mut index_lock = state.embedding_index.lock().await;
+    embedder.sync(index_for(&mut index_lock, &embedder.vault)).await
+}
+
+/// Notes most similar in meaning to the note at `file_path` (relative to the
+/// vault), best first. Brings the index up to date first, so with a local
+/// Ollama this works entirely offline.
+#[tauri::command]
+pub async fn get_related_notes(
+    app: AppHandle,
+    file_path: String,
+    limit: Option<usize>,
+    state: State<'_, crate::AppState>,
+) -> Result<Vec<RelatedNote>, String> {
+    let embedder = Embedder::load(&app, &state).await?;
+    let vault_root = embedder.vault.path().to_path_buf();
+    state.auth.authorize(&vault_root, Path::new(&file_path), VaultAction::Read).await?;
+    let mut index_lock = state.embedding_index.lock().await;
+    let index = index_for(&mut index_lock, &embedder.vault);
+    embedder.sync(index).await?;
+
+    Ok(index.related(&file_path, limit.unwrap_or(10), |path| embedder.embeddable(path)))
+}
@@ -0,0 +1,284 @@
+// embeds.rs - Resolving ![[transclusions]] to heading sections and ^block anchors
+
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+use std::path::Path;
+
+use crate::frontmatter;
+use crate::markdown::{heading_level, mask_code};
+use crate::vault::Vault;
+use crate::wikilinks::{self, LinkKind, LinkTarget};
+
+// How many levels of nested embeds are expanded
+pub const MAX_EMBED_DEPTH: usize = 5;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SourceRange {
+    /// Byte offsets into the source note
+    pub start: usize,
+    pub end: usize,
+    /// 1-based, inclusive
+    pub start_line: usize,
+    pub end_line: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmbeddedBlock {
+    /// Vault-relative path of the embedded note
+    pub path: String,
+    /// The exact markdown slice from the source note
+    pub content: String,
+    pub range: SourceRange,
+    /// `content` with nested note embeds expanded in place
+    pub expanded: String,
+    /// Nested embeds left unexpanded because they would recurse, as `A -> B -> A` chains
+    pub cycles: Vec<String>,
+    /// Whether nested embeds were cut off at `MAX_EMBED_DEPTH`
+    pub depth_limited: bool,
+}
+
+struct Expansion {
+    text: String,
+    cycles: Vec<String>,
+    depth_limited: bool,
+}
+
+/// Resolves a link target to candidate note paths, best first.
+pub type Resolver<'a> = dyn Fn(&str, Option<&str>) -> Vec<String> + 'a;
+
+// Byte ranges of each line (without the newline), with code blanked for matching
+fn line_spans(content: &str) -> Vec<Range<usize>> {
+    let mut spans = Vec::new();
+    let mut start = 0;
+    for line in content.split_inclusive('\n') {
+        let end = start + line.trim_end_matches(['\n', '\r']).len();
+        spans.push(start..end);
+        start += line.len();
+    }
+    spans
+}
+
+fn find_heading_section(content: &str, body_start: usize, heading: &str) -> Option<Range<usize>> {
+    let masked = mask_code(content);
+    let spans = line_spans(content);
+    let first_body_line = spans.iter().position(|s| s.start >= body_start).unwrap_or(spans.len());
+
+    // `Note#Parent#Child` narrows through nested headings
+    let mut search_from = first_body_line;
+    let mut found: Option<(usize, usize)> = None;
+    for part in heading.split('#').map(str::trim).filter(|p| !p.is_empty()) {
+        let min_level = found.map_or(0, |(_, level)| level);
+        let (index, level) = spans
+            .iter()
+            .enumerate()
+            .skip(search_from)
+            .find_map(|(i, span)| {
+                let (level, text) = heading_level(&masked[span.clone()])?;
+                (level > min_level && text.eq_ignore_ascii_case(part)).then_some((i, level))
+            })?;
+        found = Some((index, level));
+        search_from = index + 1;
+    }
+
+    let (index, level) = found?;
+    let end_line = spans
+        .iter()
+        .enumerate()
+        .skip(index + 1)
+        .find(|(_, span)| matches!(heading_level(&masked[(*span).clone()]), Some((l, _)) if l <= level))
+        .map_or(spans.len(), |(i, _)| i);
+
+    let start = spans[index].start;
+    let end = spans[end_line - 1].end;
+    Some(start..trim_trailing_blank(content, start, end))
+}
+
+fn trim_trailing_blank(content: &str, start: usize, end: usize) -> usize {
+    start + content[start..end].trim_end().len()
+}
+
+fn is_list_item(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    trimmed.starts_with("- ")
+        || trimmed.starts_with("* ")
+        || trimmed.starts_with("+ ")
+        || trimmed.split_once(". ").map_or(false, |(n, _)| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+}
+
+fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+fn find_block(content: &str, body_start: usize, block_id: &str) -> Option<Range<usize>> {
+    let masked = mask_code(content);
+    let spans = line_spans(content);
+    let marker = format!("^{}", block_id);
+
+    let index = spans.iter().position(|span| {
+        span.start >= body_start && {
+            let line = masked[span.clone()].trim_end();
+            line == marker || line.ends_with(&format!(" {}", marker))
+        }
+    })?;
+    let line = &content[spans[index].clone()];
+    let is_blank = |i: usize| content[spans[i].clone()].trim().is_empty();
+
+    // A marker on its own line refers to the block just above it
+    let (first, last) = if line.trim() == marker {
+        let last = (0..index).rev().find(|&i| !is_blank(i))?;
+        let first = (0..=last).rev().take_while(|&i| !is_blank(i)).last()?;
+        (first, last)
+    } else if is_list_item(line) {
+        // A list item owns its more deeply indented children
+        let indent = indent_of(line);
+        let last = (index + 1..spans.len())
+            .take_while(|&i| !is_blank(i) && indent_of(&content[spans[i].clone()]) > indent)
+            .last()
+            .unwrap_or(index);
+        (index, last)
+    } else {
+        let first = (0..=index)
+            .rev()
+            .take_while(|&i| {
+                !is_blank(i) && spans[i].start >= body_start && (i == index || heading_level(&masked[spans[i].clone()]).is_none())
+            })
+            .last()
+            .unwrap_or(index);
+        (first, index)
+    };
+
+    Some(spans[first].start..spans[last].end)
+}
+
+fn source_range(content: &str, range: Range<usize>) -> SourceRange {
+    let line_at = |offset: usize| content[..offset].matches('\n').count() + 1;
+    SourceRange {
+        start_line: line_at(range.start),
+        end_line: line_at(range.end.max(range.start)),
+        start: range.start,
+        end: range.end,
+    }
+}
+
+/// Locate the slice of a note that an embed points at: a `#heading` section,
+/// a `^block`, or the whole note body.
+pub fn locate_target(content: &str, target: &LinkTarget) -> Option<Range<usize>> {
+    let body_start = frontmatter::locate(content).map_or(0, |block| block.body_start);
+
+    if let Some(block_id) = &target.block_id {
+        return find_block(content, body_start, block_id);
+    }
+    if let Some(heading) = &target.heading {
+        return find_heading_section(content, body_start, heading);
+    }
+    Some(body_start..trim_trailing_blank(content, body_start, content.len()))
+}
+
+fn embed_key(path: &str, target: &LinkTarget) -> String {
+    let mut key = path.to_string();
+    if let Some(heading) = &target.heading {
+        key.push('#');
+        key.push_str(&heading.to_lowercase());
+    }
+    if let Some(block_id) = &target.block_id {
+        key.push_str("#^");
+        key.push_str(block_id);
+    }
+    key
+}
+
+// Replace nested note embeds with their content, tracking the chain of embeds
+// above this one so cycles are caught
+fn expand(
+    vault: &Vault,
+    resolve: &Resolver,
+    source: &str,
+    text: &str,
+    chain: &mut Vec<String>,
+) -> Expansion {
+    let mut expansion = Expansion {
+        text: String::with_capacity(text.len()),
+        cycles: vec![],
+        depth_limited: false,
+    };
+
+    let mut last = 0;
+    for link in wikilinks::find_links(text) {
+        if link.kind != LinkKind::Embed || !wikilinks::is_note_target(&link.target.note) {
+            continue;
+        }
+        let Some(path) = resolve(&link.target.note, Some(source)).into_iter().next() else {
+            continue;
+        };
+
+        let key = embed_key(&path, &link.target);
+        if chain.contains(&key) {
+            let mut cycle = chain.clone();
+            cycle.push(key);
+            expansion.cycles.push(cycle.join(" -> "));
+            continue;
+        }
+        if chain.len() >= MAX_EMBED_DEPTH {
+            expansion.depth_limited = true;
+            continue;
+        }
+
+        let Ok(content) = vault.read_file(Path::new(&path)) else { continue };
+        let Some(range) = locate_target(&content, &link.target) else { continue };
+
+        chain.push(key);
+        let nested = expand(vault, resolve, &path, &content[range], chain);
+        chain.pop();
+
+        expansion.text.push_str(&text[last..link.range.start]);
+        expansion.text.push_str(&nested.text);
+        expansion.cycles.extend(nested.cycles);
+        expansion.depth_limited |= nested.depth_limited;
+        last = link.range.end;
+    }
+    expansion.text.push_str(&text[last..]);
+
+    expansion
+}
+
+/// Resolve an embed to its markdown slice, expanding nested embeds.
+///
+/// `source` is the note containing the embed, so an embed of the note itself
+/// is reported as a cycle. Returns `None` when the note or anchor is missing.
+pub fn get_embed(
+    vault: &Vault,
+    resolve: &Resolver,
+    target: &LinkTarget,
+    source: Option<&str>,
+) -> Option<EmbeddedBlock> {
+    let path = resolve(&target.note, source).into_iter().next()?;
+    let content = vault.read_file(Path::new(&path)).ok()?;
+    let range = locate_target(&content, target)?;
+
+    let mut chain: Vec<String> = source.map(|s| s.to_string()).into_iter().collect();
+    let key = embed_key(&path, target);
+    if chain.contains(&key) {
+        chain.push(key);
+        return Some(EmbeddedBlock {
+            path,
+            content: String::new(),
+            range: source_range(&content, range),
+            expanded: String::new(),
+            cycles: vec![chain.join(" -> ")],
+            depth_limited: false,
+        });
+    }
+
+    chain.push(key);
+    let slice = &content[range.clone()];
+    let expansion = expand(vault, resolve, &path, slice, &mut chain);
+
+    Some(EmbeddedBlock {
+        content: slice.to_string(),
+        range: source_range(&content, range),
+        expanded: expansion.text,
+        cycles: expansion.cycles,
+        depth_limited: expansion.depth_limited,
+        path,
+    })
+}
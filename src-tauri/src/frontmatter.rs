@@ -0,0 +1,333 @@
+// frontmatter.rs - Minimal YAML frontmatter helpers for notes
+//
+// Only the subset of YAML that notes actually use is understood: top-level
+// `key: value` scalars and lists written either inline (`[a, b]` / `a, b`)
+// or as a block of `- item` lines.
+
+use std::ops::Range;
+
+/// Byte ranges of a note's frontmatter block.
+#[derive(Debug, Clone)]
+pub struct FrontmatterBlock {
+    /// The YAML between the `---` fences
+    pub yaml: Range<usize>,
+    /// Where the note body starts, after the closing fence
+    pub body_start: usize,
+}
+
+/// Locate the frontmatter at the very start of a note, if there is one.
+pub fn locate(content: &str) -> Option<FrontmatterBlock> {
+    let first_line_end = content.find('\n')?;
+    if content[..first_line_end].trim_end() != "---" {
+        return None;
+    }
+
+    let yaml_start = first_line_end + 1;
+    let mut line_start = yaml_start;
+    while line_start <= content.len() {
+        let line_end = content[line_start..]
+            .find('\n')
+            .map(|i| line_start + i)
+            .unwrap_or(content.len());
+        let line = content[line_start..line_end].trim_end();
+
+        if line == "---" || line == "..." {
+            return Some(FrontmatterBlock {
+                yaml: yaml_start..line_start,
+                body_start: (line_end + 1).min(content.len()),
+            });
+        }
+
+        if line_end == content.len() {
+            break;
+        }
+        line_start = line_end + 1;
+    }
+
+    None
+}
+
+/// The frontmatter YAML of a note, or an empty string.
+pub fn yaml(content: &str) -> &str {
+    locate(content).map_or("", |block| &content[block.yaml])
+}
+
+// Find the line for a top-level key, returning the range of its value
+fn key_value_range(yaml: &str, key: &str) -> Option<(Range<usize>, usize)> {
+    let mut offset = 0;
+    for line in yaml.split_inclusive('\n') {
+        let line_end = offset + line.len();
+        if let Some(rest) = line.strip_prefix(key) {
+            if let Some(value) = rest.strip_prefix(':') {
+                let value_start = offset + key.len() + 1;
+                let leading = value.len() - value.trim_start().len();
+                let value_end = offset + line.trim_end().len();
+                return Some(((value_start + leading).min(value_end)..value_end, line_end));
+            }
+        }
+        offset = line_end;
+    }
+    None
+}
+
+fn unquote_range(yaml: &str, range: Range<usize>) -> Range<usize> {
+    let text = &yaml[range.clone()];
+    let trimmed = text.trim();
+    let mut start = range.start + (text.len() - text.trim_start().len());
+    let mut end = start + trimmed.len();
+
+    let bytes = trimmed.as_bytes();
+    if bytes.len() >= 2
+        && (bytes[0] == b'"' || bytes[0] == b'\'')
+        && bytes[bytes.len() - 1] == bytes[0]
+    {
+        start += 1;
+        end -= 1;
+    }
+    start..end
+}
+
+// A scalar as written, trimmed, with its quotes removed and escapes undone
+fn parse_scalar(text: &str) -> String {
+    let text = text.trim();
+    if text.len() >= 2 && text.starts_with('"') && text.ends_with('"') {
+        let mut value = String::new();
+        let mut chars = text[1..text.len() - 1].chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                value.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('n') => value.push('\n'),
+                Some('t') => value.push('\t'),
+                Some(escaped) => value.push(escaped),
+                None => value.push('\\'),
+            }
+        }
+        return value;
+    }
+    if text.len() >= 2 && text.starts_with('\'') && text.ends_with('\'') {
+        return text[1..text.len() - 1].replace("''", "'");
+    }
+    text.to_string()
+}
+
+/// Byte ranges (within `yaml`) of every item of a list-valued key.
+///
+/// Quotes are excluded from the ranges so callers can rewrite items in place.
+pub fn list_item_ranges(yaml: &str, key: &str) -> Vec<Range<usize>> {
+    raw_list_item_ranges(yaml, key)
+        .into_iter()
+        .map(|range| unquote_range(yaml, range))
+        .filter(|range| !range.is_empty())
+        .collect()
+}
+
+// List item ranges as written, quotes and surrounding spaces included
+fn raw_list_item_ranges(yaml: &str, key: &str) -> Vec<Range<usize>> {
+    let Some((value, next_line)) = key_value_range(yaml, key) else {
+        return vec![];
+    };
+
+    let mut items = Vec::new();
+
+    if !value.is_empty() {
+        // Inline form: `key: [a, b]` or `key: a, b`
+        let text = &yaml[value.clone()];
+        let (inner_start, inner_end) = if text.starts_with('[') && text.ends_with(']') {
+            (value.start + 1, value.end - 1)
+        } else {
+            (value.start, value.end)
+        };
+
+        let mut item_start = inner_start;
+        for (i, c) in yaml[inner_start..inner_end].char_indices() {
+            if c == ',' {
+                items.push(item_start..inner_start + i);
+                item_start = inner_start + i + 1;
+            }
+        }
+        items.push(item_start..inner_end);
+    } else {
+        // Block form: following `- item` lines
+        let mut offset = next_line;
+        for line in yaml[next_line..].split_inclusive('\n') {
+            let trimmed = line.trim_start();
+            let Some(item) = trimmed.strip_prefix("- ").or_else(|| {
+                (trimmed.trim_end() == "-").then_some("")
+            }) else {
+                break;
+            };
+            let item_start = offset + (line.len() - item.len());
+            items.push(item_start..item_start + item.trim_end().len());
+            offset += line.len();
+        }
+    }
+
+    items
+}
+
+/// Read a scalar value for a top-level key, without its quotes.
+pub fn get_value(yaml: &str, key: &str) -> Option<String> {
+    let (value, _) = key_value_range(yaml, key)?;
+    let value = parse_scalar(&yaml[value]);
+    (!value.is_empty()).then_some(value)
+}
+
+/// Read a list value for a top-level key, each item unquoted and trimmed.
+pub fn get_list(yaml: &str, key: &str) -> Vec<String> {
+    raw_list_item_ranges(yaml, key)
+        .into_iter()
+        .map(|range| parse_scalar(&yaml[range]))
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+/// A value to write with `set_field`.
+pub enum FieldValue<'a> {
+    /// Written double-quoted, so any text is safe
+    Text(&'a str),
+    /// Written as a block of `- item` lines
+    List(&'a [String]),
+}
+
+fn quote(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', " ");
+    format!("\"{}\"", escaped)
+}
+
+// Plain list items are kept readable; anything YAML could read as something
+// else, such as `#x` as a comment, is quoted
+fn list_item(value: &str) -> String {
+    let plain = value
+        .chars()
+        .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '/' | '.' | ' '))
+        && !value.starts_with(['-', ' ', '.'])
+        && !value.ends_with(' ');
+    if plain && !value.is_empty() {
+        value.to_string()
+    } else {
+        quote(value)
+    }
+}
+
+fn render_entry(key: &str, value: &FieldValue) -> String {
+    match value {
+        FieldValue::Text(text) => format!("{}: {}\n", key, quote(text)),
+        FieldValue::List([]) => format!("{}: []\n", key),
+        FieldValue::List(items) => {
+            let mut entry = format!("{}:\n", key);
+            for item in items.iter() {
+                entry.push_str(&format!("  - {}\n", list_item(item)));
+            }
+            entry
+        }
+    }
+}
+
+// The whole entry for a top-level key: its line plus the indented or
+// `- item` lines that continue it
+fn entry_range(yaml: &str, key: &str) -> Option<Range<usize>> {
+    let (value, next_line) = key_value_range(yaml, key)?;
+    let start = yaml[..value.start].rfind('\n').map_or(0, |i| i + 1);
+
+    let mut end = next_line;
+    for line in yaml[next_line..].split_inclusive('\n') {
+        if !(line.starts_with(' ') || line.starts_with('\t') || line.starts_with('-')) {
+            break;
+        }
+        end += line.len();
+    }
+    Some(start..end)
+}
+
+/// Set a top-level key, replacing any existing value and adding a
+/// frontmatter block if the note has none.
+pub fn set_field(content: &str, key: &str, value: FieldValue) -> String {
+    let entry = render_entry(key, &value);
+    let Some(block) = locate(content) else {
+        return format!("---\n{}---\n\n{}", entry, content);
+    };
+
+    let range = match entry_range(&content[block.yaml.clone()], key) {
+        Some(range) => block.yaml.start + range.start..block.yaml.start + range.end,
+        None => block.yaml.end..block.yaml.end,
+    };
+    format!("{}{}{}", &content[..range.start], entry, &content[range.end..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locate_finds_the_block_between_fences() {
+        let content = "---\ntitle: A\n---\nBody";
+        let block = locate(content).unwrap();
+        assert_eq!(&content[block.yaml], "title: A\n");
+        assert_eq!(&content[block.body_start..], "Body");
+        assert!(locate("title: A\n---\nBody").is_none());
+        assert!(locate("---\ntitle: A\nno closing fence").is_none());
+    }
+
+    #[test]
+    fn values_are_unquoted() {
+        let yaml = "title: \"A \\\"quoted\\\" title\"\nsummary: 'it''s here'\nplain:  text  \nempty:\n";
+        assert_eq!(get_value(yaml, "title").as_deref(), Some("A \"quoted\" title"));
+        assert_eq!(get_value(yaml, "summary").as_deref(), Some("it's here"));
+        assert_eq!(get_value(yaml, "plain").as_deref(), Some("text"));
+        assert_eq!(get_value(yaml, "empty"), None);
+        assert_eq!(get_value(yaml, "missing"), None);
+    }
+
+    #[test]
+    fn lists_are_read_in_every_form() {
+        assert_eq!(get_list("tags: [a, \"b c\", 'd']\n", "tags"), ["a", "b c", "d"]);
+        assert_eq!(get_list("tags: a, b\n", "tags"), ["a", "b"]);
+        assert_eq!(
+            get_list("tags:\n  - one\n  - \"#two\"\n  -\nnext: x\n", "tags"),
+            ["one", "#two"]
+        );
+        assert!(get_list("title: x\n", "tags").is_empty());
+    }
+
+    #[test]
+    fn list_item_ranges_exclude_quotes() {
+        let yaml = "tags: [a, \"b c\"]\n";
+        let items: Vec<&str> = list_item_ranges(yaml, "tags").into_iter().map(|r| &yaml[r]).collect();
+        assert_eq!(items, ["a", "b c"]);
+    }
+
+    #[test]
+    fn set_field_adds_a_block_when_there_is_none() {
+        assert_eq!(
+            set_field("Body\n", "title", FieldValue::Text("Hi")),
+            "---\ntitle: \"Hi\"\n---\n\nBody\n"
+        );
+    }
+
+    #[test]
+    fn set_field_replaces_only_its_entry() {
+        let content = "---\ntitle: Old\ntags:\n  - a\n  - b\ndate: 2024\n---\nBody";
+        let tags = ["x".to_string(), "#y".to_string(), "- z".to_string()];
+        let updated = set_field(content, "tags", FieldValue::List(&tags));
+        assert_eq!(
+            updated,
+            "---\ntitle: Old\ntags:\n  - x\n  - \"#y\"\n  - \"- z\"\ndate: 2024\n---\nBody"
+        );
+        assert_eq!(get_list(yaml(&updated), "tags"), tags);
+    }
+
+    #[test]
+    fn set_field_appends_new_keys_and_round_trips_text() {
+        let content = "---\ntitle: A\n---\nBody";
+        let updated = set_field(content, "summary", FieldValue::Text("Says \"hi\" \\ bye"));
+        assert_eq!(updated, "---\ntitle: A\nsummary: \"Says \\\"hi\\\" \\\\ bye\"\n---\nBody");
+        assert_eq!(get_value(yaml(&updated), "summary").as_deref(), Some("Says \"hi\" \\ bye"));
+        assert_eq!(set_field(content, "tags", FieldValue::List(&[])), "---\ntitle: A\ntags: []\n---\nBody");
+    }
+}
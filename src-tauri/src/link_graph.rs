@@ -0,0 +1,658 @@
+// link_graph.rs - Bidirectional link graph between vault notes
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use crate::auth::VaultAction;
+use crate::markdown::mask_code;
+use crate::vault::{self, Vault};
+use crate::wikilinks::{self, LinkKind, RawLink};
+
+// Lines of context shown on each side of a backlink or mention
+const CONTEXT_LINES: usize = 1;
+
+struct NoteEntry {
+    aliases: Vec<String>,
+    links: Vec<RawLink>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LinkReference {
+    /// Vault-relative path of the note containing the reference
+    pub source: String,
+    pub line: usize,
+    pub context: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OutgoingLink {
+    pub target: String,
+    pub kind: LinkKind,
+    /// Resolved vault-relative path, if the target exists
+    pub path: Option<String>,
+    pub line: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GraphNode {
+    pub id: String,
+    pub label: String,
+    /// False for link targets that have no note yet
+    pub exists: bool,
+    pub backlink_count: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GraphEdge {
+    pub source: String,
+    pub target: String,
+    pub kind: LinkKind,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GraphExport {
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+/// Link graph for a vault, rebuilt incrementally from watcher events.
+pub struct LinkGraph {
+    vault_path: PathBuf,
+    notes: HashMap<String, NoteEntry>,
+    // lowercase file stem -> note paths
+    by_stem: HashMap<String, Vec<String>>,
+    // lowercase alias -> note paths
+    by_alias: HashMap<String, Vec<String>>,
+    // lowercase name a link could resolve through -> notes with such links
+    link_names: HashMap<String, BTreeSet<String>>,
+    outgoing: HashMap<String, BTreeSet<String>>,
+    backlinks: HashMap<String, BTreeSet<String>>,
+}
+
+fn stem_of(path: &str) -> String {
+    let name = path.rsplit(['/', '\\']).next().unwrap_or(path);
+    name.strip_suffix(".md").unwrap_or(name).to_lowercase()
+}
+
+// Names a link is looked up by: the target's file name, or the whole target as an alias
+fn link_keys(link: &RawLink) -> [String; 2] {
+    let target = link.target.note.trim();
+    [stem_of(target), target.to_lowercase()]
+}
+
+// Drop `path` from a name lookup, and the name once nothing has it
+fn remove_path(map: &mut HashMap<String, Vec<String>>, name: &str, path: &str) {
+    if let Some(paths) = map.get_mut(name) {
+        paths.retain(|p| p != path);
+        if paths.is_empty() {
+            map.remove(name);
+        }
+    }
+}
+
+fn read_entry(path: &Path) -> io::Result<NoteEntry> {
+    let content = std::fs::read_to_string(path)?;
+    Ok(NoteEntry {
+        aliases: wikilinks::note_aliases(&content),
+        links: wikilinks::find_links(&content),
+    })
+}
+
+/// Resolve `..` and `.` in a vault-relative path without touching the disk.
+pub fn normalize_relative(path: &Path) -> Option<String> {
+    let mut parts: Vec<String> = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_string_lossy().to_string()),
+            Component::ParentDir => {
+                parts.pop()?;
+            }
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    Some(parts.join(std::path::MAIN_SEPARATOR_STR))
+}
+
+fn context_around(lines: &[&str], line: usize) -> String {
+    let index = line.saturating_sub(1);
+    let to = (index + CONTEXT_LINES + 1).min(lines.len());
+    // The note may have shrunk since the graph last saw it
+    let from = index.saturating_sub(CONTEXT_LINES).min(to);
+    lines[from..to].join("\n")
+}
+
+impl LinkGraph {
+    pub fn build(vault: &Vault) -> io::Result<Self> {
+        let mut graph = Self {
+            vault_path: vault.path().to_path_buf(),
+            notes: HashMap::new(),
+            by_stem: HashMap::new(),
+            by_alias: HashMap::new(),
+            link_names: HashMap::new(),
+            outgoing: HashMap::new(),
+            backlinks: HashMap::new(),
+        };
+
+        for path in vault.list_markdown_files()? {
+            if !vault::is_note(&path) {
+                continue;
+            }
+            let Some(key) = graph.relative_key(&path) else { continue };
+            // A note that can't be read is left out rather than losing the graph
+            match read_entry(&path) {
+                Ok(entry) => {
+                    graph.notes.insert(key, entry);
+                }
+                Err(e) => println!("⚠️ Failed to parse links in {:?}: {}", path, e),
+            }
+        }
+        let keys: Vec<String> = graph.notes.keys().cloned().collect();
+        for key in &keys {
+            graph.index_note(key);
+        }
+        for key in &keys {
+            graph.resolve_edges(key);
+        }
+
+        let edge_count: usize = graph.outgoing.values().map(|t| t.len()).sum();
+        println!("🕸️ Link graph ready: {} notes, {} links", graph.notes.len(), edge_count);
+        Ok(graph)
+    }
+
+    /// Re-parse the given absolute paths after a file system change. Only
+    /// the changed notes' links are resolved again, plus links elsewhere
+    /// that could point at a name the change added or removed.
+    pub fn update_paths(&mut self, paths: &[PathBuf]) -> bool {
+        let mut affected = BTreeSet::new();
+        let mut touched_names = HashSet::new();
+
+        for path in paths.iter().filter(|p| vault::is_note(p)) {
+            let Some(key) = self.relative_key(path) else { continue };
+            let entry = if path.is_file() {
+                match read_entry(path) {
+                    Ok(entry) => Some(entry),
+                    Err(e) => {
+                        println!("⚠️ Failed to parse links in {:?}: {}", path, e);
+                        continue;
+                    }
+                }
+            } else if self.notes.contains_key(&key) {
+                None
+            } else {
+                continue;
+            };
+
+            let old_names = self.names_of(&key);
+            self.unindex_note(&key);
+            match entry {
+                Some(entry) => {
+                    self.notes.insert(key.clone(), entry);
+                    self.index_note(&key);
+                }
+                None => {
+                    self.notes.remove(&key);
+                }
+            }
+            let new_names = self.names_of(&key);
+            touched_names.extend(old_names.symmetric_difference(&new_names).cloned());
+            affected.insert(key);
+        }
+
+        for name in &touched_names {
+            if let Some(sources) = self.link_names.get(name) {
+                affected.extend(sources.iter().cloned());
+            }
+        }
+        for source in &affected {
+            self.resolve_edges(source);
+        }
+        !affected.is_empty()
+    }
+
+    /// Candidate notes for a link target, best first: file name or path, then aliases.
+    pub fn resolve(&self, target: &str, source: Option<&str>) -> Vec<String> {
+        let candidates = self
+            .by_stem
+            .get(&stem_of(target.trim()))
+            .map(|paths| wikilinks::match_notes(paths, target, source))
+            .unwrap_or_default();
+        if !candidates.is_empty() {
+            return candidates;
+        }
+
+        self.by_alias
+            .get(&target.trim().to_lowercase())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// The note a link in `source` points to, if it exists.
+    pub fn resolve_link(&self, source: &str, link: &RawLink) -> Option<String> {
+        if !wikilinks::is_note_target(&link.target.note) {
+            return None;
+        }
+        if link.target.note.is_empty() {
+            // `[[#Heading]]` links within the same note
+            return Some(source.to_string());
+        }
+
+        if link.kind == LinkKind::Markdown {
+            // Markdown links are relative to the linking note's folder
+            let folder = Path::new(source).parent().unwrap_or(Path::new(""));
+            let mut relative = folder.join(&link.target.note);
+            if relative.extension().is_none() {
+                relative.set_extension("md");
+            }
+            if let Some(path) = normalize_relative(&relative) {
+                if self.notes.contains_key(&path) {
+                    return Some(path);
+                }
+            }
+        }
+
+        self.resolve(&link.target.note, Some(source)).into_iter().next()
+    }
+
+    /// The links parsed from a note.
+    pub fn links_of(&self, path: &str) -> &[RawLink] {
+        self.notes.get(path).map_or(&[], |entry| entry.links.as_slice())
+    }
+
+    pub fn backlink_sources(&self, path: &str) -> Vec<String> {
+        self.backlinks
+            .get(path)
+            .map(|s| s.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Links in `source` that resolve to `target`.
+    pub fn links_between(&self, source: &str, target: &str) -> Vec<&RawLink> {
+        self.notes
+            .get(source)
+            .map(|entry| {
+                entry
+                    .links
+                    .iter()
+                    .filter(|link| self.resolve_link(source, link).as_deref() == Some(target))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Links out of a note; targets `visible` rejects show as unresolved.
+    pub fn outgoing_links(&self, path: &str, visible: impl Fn(&str) -> bool) -> Vec<OutgoingLink> {
+        self.notes
+            .get(path)
+            .map(|entry| {
+                entry
+                    .links
+                    .iter()
+                    .map(|link| OutgoingLink {
+                        target: link.target.note.clone(),
+                        kind: link.kind,
+                        path: self.resolve_link(path, link).filter(|p| visible(p)),
+                        line: link.line,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Notes with no links in or out.
+    pub fn orphans(&self) -> Vec<String> {
+        let mut orphans: Vec<String> = self
+            .notes
+            .keys()
+            .filter(|path| {
+                self.outgoing.get(*path).map_or(true, |t| t.is_empty())
+                    && self.backlinks.get(*path).map_or(true, |s| s.is_empty())
+            })
+            .cloned()
+            .collect();
+        orphans.sort();
+        orphans
+    }
+
+    /// Names a note can be mentioned by: its file name and aliases.
+    pub fn names_for(&self, path: &str) -> Vec<String> {
+        let Some(entry) = self.notes.get(path) else {
+            return vec![];
+        };
+        let stem = Path::new(path)
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("")
+            .to_string();
+        std::iter::once(stem)
+            .chain(entry.aliases.iter().cloned())
+            .filter(|name| !name.trim().is_empty())
+            .collect()
+    }
+
+    pub fn note_paths(&self) -> impl Iterator<Item = &String> {
+        self.notes.keys()
+    }
+
+    pub fn export(&self, include_unresolved: bool, visible: impl Fn(&str) -> bool) -> GraphExport {
+        let mut nodes: BTreeMap<String, GraphNode> = self
+            .notes
+            .keys()
+            .filter(|path| visible(path))
+            .map(|path| {
+                (
+                    path.clone(),
+                    GraphNode {
+                        id: path.clone(),
+                        label: Path::new(path)
+                            .file_stem()
+                            .and_then(|s| s.to_str())
+                            .unwrap_or(path)
+                            .to_string(),
+                        exists: true,
+                        backlink_count: self
+                            .backlinks
+                            .get(path)
+                            .map_or(0, |s| s.iter().filter(|source| visible(source)).count()),
+                    },
+                )
+            })
+            .collect();
+
+        let mut edges = Vec::new();
+        let mut seen = BTreeSet::new();
+
+        for (source, entry) in self.notes.iter().filter(|(source, _)| visible(source)) {
+            for link in &entry.links {
+                if !wikilinks::is_note_target(&link.target.note) || link.target.note.is_empty() {
+                    continue;
+                }
+                let target = match self.resolve_link(source, link) {
+                    Some(path) if visible(&path) => path,
+                    Some(_) => continue,
+                    None if include_unresolved && link.kind != LinkKind::Markdown => {
+                        let id = format!("unresolved:{}", link.target.note.to_lowercase());
+                        let node = nodes.entry(id.clone()).or_insert_with(|| GraphNode {
+                            id: id.clone(),
+                            label: link.target.note.clone(),
+                            exists: false,
+                            backlink_count: 0,
+                        });
+                        node.backlink_count += 1;
+                        id
+                    }
+                    None => continue,
+                };
+                if target != *source && seen.insert((source.clone(), target.clone(), link.kind)) {
+                    edges.push(GraphEdge {
+                        source: source.clone(),
+                        target,
+                        kind: link.kind,
+                    });
+                }
+            }
+        }
+
+        edges.sort_by(|a, b| (&a.source, &a.target).cmp(&(&b.source, &b.target)));
+        GraphExport {
+            nodes: nodes.into_values().collect(),
+            edges,
+        }
+    }
+
+    fn relative_key(&self, path: &Path) -> Option<String> {
+        path.strip_prefix(&self.vault_path)
+            .ok()
+            .map(|p| p.to_string_lossy().to_string())
+    }
+
+    // Lowercase file name and aliases a note can be linked by; empty when
+    // the note isn't in the graph
+    fn names_of(&self, path: &str) -> HashSet<String> {
+        let Some(entry) = self.notes.get(path) else {
+            return HashSet::new();
+        };
+        std::iter::once(stem_of(path))
+            .chain(entry.aliases.iter().map(|alias| alias.trim().to_lowercase()))
+            .collect()
+    }
+
+    // Add a note to the name lookups
+    fn index_note(&mut self, path: &str) {
+        let Some(entry) = self.notes.get(path) else { return };
+        self.by_stem.entry(stem_of(path)).or_default().push(path.to_string());
+        for alias in &entry.aliases {
+            self.by_alias
+                .entry(alias.trim().to_lowercase())
+                .or_default()
+                .push(path.to_string());
+        }
+        for link in &entry.links {
+            for key in link_keys(link) {
+                self.link_names.entry(key).or_default().insert(path.to_string());
+            }
+        }
+    }
+
+    // Take a note out of the name lookups, before it changes or goes
+    fn unindex_note(&mut self, path: &str) {
+        let Some(entry) = self.notes.get(path) else { return };
+        remove_path(&mut self.by_stem, &stem_of(path), path);
+        for alias in &entry.aliases {
+            remove_path(&mut self.by_alias, &alias.trim().to_lowercase(), path);
+        }
+        for link in &entry.links {
+            for key in link_keys(link) {
+                if let Some(sources) = self.link_names.get_mut(&key) {
+                    sources.remove(path);
+                    if sources.is_empty() {
+                        self.link_names.remove(&key);
+                    }
+                }
+            }
+        }
+    }
+
+    // Resolve a note's links again, replacing its edges; a note no longer
+    // in the graph just loses them
+    fn resolve_edges(&mut self, source: &str) {
+        for target in self.outgoing.remove(source).unwrap_or_default() {
+            if let Some(sources) = self.backlinks.get_mut(&target) {
+                sources.remove(source);
+                if sources.is_empty() {
+                    self.backlinks.remove(&target);
+                }
+            }
+        }
+
+        let Some(entry) = self.notes.get(source) else { return };
+        let targets: BTreeSet<String> = entry
+            .links
+            .iter()
+            .filter_map(|link| self.resolve_link(source, link))
+            .filter(|target| target != source)
+            .collect();
+        for target in &targets {
+            self.backlinks
+                .entry(target.clone())
+                .or_default()
+                .insert(source.to_string());
+        }
+        if !targets.is_empty() {
+            self.outgoing.insert(source.to_string(), targets);
+        }
+    }
+}
+
+fn with_graph<T>(
+    graph: &Option<LinkGraph>,
+    f: impl FnOnce(&LinkGraph) -> T,
+) -> Result<T, String> {
+    match graph {
+        Some(graph) => Ok(f(graph)),
+        None => Err("No vault opened".to_string()),
+    }
+}
+
+#[tauri::command]
+pub async fn get_backlinks(
+    file_path: String,
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<Vec<LinkReference>, String> {
+    let vault_lock = state.vault.lock().await;
+    let Some(vault) = &*vault_lock else {
+        return Err("No vault opened".to_string());
+    };
+    let graph_lock = state.link_graph.lock().await;
+
+    let sources = with_graph(&graph_lock, |graph| {
+        graph
+            .backlink_sources(&file_path)
+            .into_iter()
+            .map(|source| {
+                let lines: Vec<usize> = graph
+                    .links_between(&source, &file_path)
+                    .iter()
+                    .map(|link| link.line)
+                    .collect();
+                (source, lines)
+            })
+            .collect::<Vec<_>>()
+    })?;
+
+    let mut references = Vec::new();
+    for (source, line_numbers) in sources {
+        // Quoting a note the user can't read would leak its text
+        if !state.auth.allows(vault.path(), Path::new(&source), VaultAction::Read).await {
+            continue;
+        }
+        let Ok(content) = vault.read_file(Path::new(&source)) else { continue };
+        let lines: Vec<&str> = content.lines().collect();
+
+        let mut seen_lines = BTreeSet::new();
+        for line in line_numbers.into_iter().filter(|l| seen_lines.insert(*l)) {
+            references.push(LinkReference {
+                source: source.clone(),
+                line,
+                context: context_around(&lines, line),
+            });
+        }
+    }
+
+    Ok(references)
+}
+
+#[tauri::command]
+pub async fn get_outgoing_links(
+    file_path: String,
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<Vec<OutgoingLink>, String> {
+    let vault_lock = state.vault.lock().await;
+    let Some(vault) = &*vault_lock else {
+        return Err("No vault opened".to_string());
+    };
+    state.auth.authorize(vault.path(), Path::new(&file_path), VaultAction::Read).await?;
+
+    let access = state.auth.vault_access(vault.path()).await;
+    let graph_lock = state.link_graph.lock().await;
+    with_graph(&graph_lock, |graph| {
+        graph.outgoing_links(&file_path, |path| access.allows(Path::new(path), VaultAction::Read))
+    })
+}
+
+#[tauri::command]
+pub async fn get_unlinked_mentions(
+    file_path: String,
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<Vec<LinkReference>, String> {
+    let vault_lock = state.vault.lock().await;
+    let Some(vault) = &*vault_lock else {
+        return Err("No vault opened".to_string());
+    };
+    let graph_lock = state.link_graph.lock().await;
+
+    let (names, notes) = with_graph(&graph_lock, |graph| {
+        let notes: Vec<String> = graph
+            .note_paths()
+            .filter(|p| **p != file_path)
+            .cloned()
+            .collect();
+        (graph.names_for(&file_path), notes)
+    })?;
+    if names.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let pattern = names
+        .iter()
+        .map(|name| regex::escape(name.trim()))
+        .collect::<Vec<_>>()
+        .join("|");
+    let mention_regex = Regex::new(&format!(r"(?i)\b(?:{})\b", pattern))
+        .map_err(|e| format!("Failed to build mention pattern: {}", e))?;
+
+    let mut mentions = Vec::new();
+    for source in notes {
+        // Mentions quote the note, so it must be readable
+        if !state.auth.allows(vault.path(), Path::new(&source), VaultAction::Read).await {
+            continue;
+        }
+        let Ok(content) = vault.read_file(Path::new(&source)) else { continue };
+
+        // Text inside code or existing links is not a mention
+        let mut masked = mask_code(&content);
+        for link in wikilinks::find_links(&content) {
+            masked.replace_range(link.range.clone(), &" ".repeat(link.range.len()));
+        }
+
+        let lines: Vec<&str> = content.lines().collect();
+        for (index, line) in masked.lines().enumerate() {
+            if mention_regex.is_match(line) {
+                mentions.push(LinkReference {
+                    source: source.clone(),
+                    line: index + 1,
+                    context: context_around(&lines, index + 1),
+                });
+            }
+        }
+    }
+
+    mentions.sort_by(|a, b| a.source.cmp(&b.source).then(a.line.cmp(&b.line)));
+    Ok(mentions)
+}
+
+#[tauri::command]
+pub async fn get_orphan_notes(
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<Vec<String>, String> {
+    let vault_lock = state.vault.lock().await;
+    let Some(vault) = &*vault_lock else {
+        return Err("No vault opened".to_string());
+    };
+    let orphans = with_graph(&*state.link_graph.lock().await, |graph| graph.orphans())?;
+    Ok(state.auth.readable(vault.path(), orphans).await)
+}
+
+#[tauri::command]
+pub async fn export_link_graph(
+    include_unresolved: Option<bool>,
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<GraphExport, String> {
+    let vault_lock = state.vault.lock().await;
+    let Some(vault) = &*vault_lock else {
+        return Err("No vault opened".to_string());
+    };
+    state.auth.authorize(vault.path(), vault.path(), VaultAction::Export).await?;
+
+    // Only notes the user can read appear, and only links between them
+    let access = state.auth.vault_access(vault.path()).await;
+    let graph_lock = state.link_graph.lock().await;
+    with_graph(&graph_lock, |graph| {
+        graph.export(include_unresolved.unwrap_or(false), |path| {
+            access.allows(Path::new(path), VaultAction::Read)
+        })
+    })
+}
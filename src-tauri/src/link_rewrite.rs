@@ -0,0 +1,522 @@
+// link_rewrite.rs - Keeping links intact when notes, attachments and folders move
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::link_graph::{normalize_relative, LinkGraph};
+use crate::vault::{relative_path, Vault};
+use crate::wikilinks::{self, LinkKind, RawLink};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MovedPath {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinkEdit {
+    pub line: usize,
+    pub old_text: String,
+    pub new_text: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FileEdits {
+    /// Vault-relative path of the edited note, after the move
+    pub path: String,
+    pub edits: Vec<LinkEdit>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RelocationReport {
+    pub moved: Vec<MovedPath>,
+    pub changed_files: Vec<FileEdits>,
+    /// False for dry runs, where nothing on disk was touched
+    pub applied: bool,
+}
+
+struct PlannedFile {
+    new_path: String,
+    original: String,
+    updated: String,
+    edits: Vec<LinkEdit>,
+}
+
+fn relative_string(path: &Path) -> String {
+    path.to_string_lossy().to_string()
+}
+
+fn forward_slashes(path: &str) -> String {
+    path.replace('\\', "/")
+}
+
+// A caller's path as the graph keys notes, whichever slashes it used and
+// with any `./` dropped
+fn vault_key(path: &str) -> Result<PathBuf, String> {
+    let slashed = forward_slashes(path.trim());
+    let cleaned: PathBuf = Path::new(&slashed)
+        .components()
+        .filter(|c| !matches!(c, Component::CurDir))
+        .collect();
+    relative_path(&cleaned.to_string_lossy())
+}
+
+// Escapes what would end or confuse a markdown link target
+fn escape_url_segment(segment: &str) -> String {
+    let mut out = String::with_capacity(segment.len());
+    for c in segment.chars() {
+        match c {
+            ' ' => out.push_str("%20"),
+            '%' => out.push_str("%25"),
+            '(' => out.push_str("%28"),
+            ')' => out.push_str("%29"),
+            '#' => out.push_str("%23"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+// Every file affected by moving `old` to `new`; folders move all their contents
+fn collect_moves(vault: &Vault, old: &Path, new: &Path) -> Vec<(String, String)> {
+    let old_full = vault.path().join(old);
+    if !old_full.is_dir() {
+        return vec![(relative_string(old), relative_string(new))];
+    }
+
+    WalkDir::new(&old_full)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().is_file())
+        .filter_map(|e| {
+            let inner = e.path().strip_prefix(&old_full).ok()?;
+            Some((relative_string(&old.join(inner)), relative_string(&new.join(inner))))
+        })
+        .collect()
+}
+
+// Which moved file (by old path) a link points at, if any
+fn moved_target(
+    graph: &LinkGraph,
+    source: &str,
+    link: &RawLink,
+    moves: &HashMap<String, String>,
+) -> Option<String> {
+    if wikilinks::is_note_target(&link.target.note) {
+        return graph.resolve_link(source, link);
+    }
+
+    // Attachments are not in the graph; match them against the moved files
+    let folder = Path::new(source).parent().unwrap_or(Path::new(""));
+    let relative = normalize_relative(&folder.join(&link.target.note));
+    let wanted = forward_slashes(&link.target.note).to_lowercase();
+
+    moves.keys().find(|old| {
+        let old_slashed = forward_slashes(old).to_lowercase();
+        match link.kind {
+            LinkKind::Markdown => relative.as_deref() == Some(old.as_str()),
+            _ => old_slashed == wanted || old_slashed.ends_with(&format!("/{}", wanted)),
+        }
+    }).cloned()
+}
+
+// Path from one note's folder to a target, as written in a markdown link
+fn relative_url(from_note: &str, to: &str) -> String {
+    let from_parts: Vec<Component> = Path::new(from_note)
+        .parent()
+        .map(|p| p.components().collect())
+        .unwrap_or_default();
+    let to_parts: Vec<Component> = Path::new(to).components().collect();
+
+    let common = from_parts
+        .iter()
+        .zip(&to_parts)
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let mut segments: Vec<String> = vec!["..".to_string(); from_parts.len() - common];
+    segments.extend(
+        to_parts[common..]
+            .iter()
+            .map(|c| escape_url_segment(&c.as_os_str().to_string_lossy())),
+    );
+    segments.join("/")
+}
+
+fn stem(path: &str) -> String {
+    Path::new(path)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("")
+        .to_lowercase()
+}
+
+fn new_link_text(
+    link: &RawLink,
+    written: &str,
+    source_after: &str,
+    target_after: &str,
+    stem_counts: &HashMap<String, usize>,
+) -> String {
+    if link.kind == LinkKind::Markdown {
+        return relative_url(source_after, target_after);
+    }
+
+    let is_note = wikilinks::is_note_target(&link.target.note);
+    let full = forward_slashes(target_after);
+    let full = if is_note && !written.to_lowercase().ends_with(".md") {
+        full.strip_suffix(".md").unwrap_or(&full).to_string()
+    } else {
+        full
+    };
+
+    // Keep bare `[[Name]]` links bare unless the name becomes ambiguous
+    let ambiguous = is_note && stem_counts.get(&stem(target_after)).copied().unwrap_or(0) > 1;
+    if written.contains('/') || ambiguous {
+        full
+    } else {
+        full.rsplit('/').next().unwrap_or(&full).to_string()
+    }
+}
+
+fn plan(vault: &Vault, graph: &LinkGraph, moves: &HashMap<String, String>) -> Result<Vec<PlannedFile>, String> {
+    // File names as they will be after the move, to spot new ambiguities
+    let mut stem_counts: HashMap<String, usize> = HashMap::new();
+    for note in graph.note_paths() {
+        let after = moves.get(note).unwrap_or(note);
+        *stem_counts.entry(stem(after)).or_default() += 1;
+    }
+
+    let mut sources: Vec<&String> = graph
+        .note_paths()
+        .filter(|source| {
+            moves.contains_key(*source)
+                || graph
+                    .links_of(source)
+                    .iter()
+                    .any(|link| moved_target(graph, source, link, moves).map_or(false, |t| moves.contains_key(&t)))
+        })
+        .collect();
+    sources.sort();
+
+    let mut planned = Vec::new();
+    for source in sources {
+        let source_after = moves.get(source).unwrap_or(source);
+        let original = vault
+            .read_file(Path::new(source))
+            .map_err(|e| format!("Failed to read {}: {}", source, e))?;
+
+        let mut updated = original.clone();
+        let mut edits = Vec::new();
+
+        // Work backwards so earlier byte ranges stay valid
+        let mut links = wikilinks::find_links(&original);
+        links.reverse();
+        for link in links {
+            let Some(target) = moved_target(graph, source, &link, moves) else { continue };
+            let target_after = match moves.get(&target) {
+                Some(after) => after.clone(),
+                // Relative markdown links break when the linking note itself moves
+                None if link.kind == LinkKind::Markdown && source_after != source => target,
+                None => continue,
+            };
+
+            let written = &original[link.target_range.clone()];
+            let new_text = new_link_text(&link, written, source_after, &target_after, &stem_counts);
+            if new_text == written {
+                continue;
+            }
+
+            updated.replace_range(link.target_range.clone(), &new_text);
+            edits.push(LinkEdit {
+                line: link.line,
+                old_text: original[link.range.clone()].to_string(),
+                new_text: format!(
+                    "{}{}{}",
+                    &original[link.range.start..link.target_range.start],
+                    new_text,
+                    &original[link.target_range.end..link.range.end]
+                ),
+            });
+        }
+
+        if !edits.is_empty() {
+            edits.reverse();
+            planned.push(PlannedFile {
+                new_path: source_after.clone(),
+                original,
+                updated,
+                edits,
+            });
+        }
+    }
+
+    Ok(planned)
+}
+
+// Write through a temporary file so a note is never left half-written
+fn write_atomic(path: &Path, content: &str) -> std::io::Result<()> {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let tmp_path = path.with_file_name(format!(".{}.tmp", file_name));
+    std::fs::write(&tmp_path, content)?;
+    std::fs::rename(&tmp_path, path)
+}
+
+fn apply(vault: &Vault, old_full: &Path, new_full: &Path, planned: &[PlannedFile]) -> Result<(), String> {
+    std::fs::rename(old_full, new_full).map_err(|e| e.to_string())?;
+
+    for (i, file) in planned.iter().enumerate() {
+        let path = vault.path().join(&file.new_path);
+        if let Err(e) = write_atomic(&path, &file.updated) {
+            println!("❌ Failed to update links in {}, rolling back: {}", file.new_path, e);
+
+            // Restore every note touched so far, then move the file back
+            for done in &planned[..i] {
+                let _ = write_atomic(&vault.path().join(&done.new_path), &done.original);
+            }
+            let _ = std::fs::rename(new_full, old_full);
+            return Err(format!("Failed to update links in {}: {}", file.new_path, e));
+        }
+    }
+
+    Ok(())
+}
+
+// Whether two paths name the same file, as in a case-only rename on a
+// case-insensitive file system
+fn same_file(a: &Path, b: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        match (std::fs::metadata(a), std::fs::metadata(b)) {
+            (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+            _ => false,
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        matches!((std::fs::canonicalize(a), std::fs::canonicalize(b)), (Ok(a), Ok(b)) if a == b)
+    }
+}
+
+/// A move or rename that has been worked out but not carried out yet, so
+/// callers can check every file it touches first.
+pub struct Relocation {
+    old_full: PathBuf,
+    new_full: PathBuf,
+    moves: Vec<(String, String)>,
+    planned: Vec<PlannedFile>,
+    action: String,
+}
+
+/// Work out moving or renaming a vault path and, with `plan_links`, the
+/// edits to every link that points at it (or, for folders, at anything
+/// inside it). Nothing on disk is touched.
+pub fn prepare(
+    vault: &Vault,
+    graph: Option<&LinkGraph>,
+    old_path: &str,
+    new_path: &str,
+    plan_links: bool,
+    action: &str,
+) -> Result<Relocation, String> {
+    let old_path = vault_key(old_path)?;
+    let new_path = vault_key(new_path)?;
+    let old_full = vault.path().join(&old_path);
+    let new_full = vault.path().join(&new_path);
+
+    if !old_full.exists() {
+        return Err(format!("Failed to {} file: {} does not exist", action, old_path.display()));
+    }
+    // Renaming would silently replace it, and links would point at the wrong note
+    if new_full.exists() && !same_file(&old_full, &new_full) {
+        return Err(format!("Failed to {} file: {} already exists", action, new_path.display()));
+    }
+
+    let moves = collect_moves(vault, &old_path, &new_path);
+    let moves_map: HashMap<String, String> = moves.iter().cloned().collect();
+
+    let planned = if plan_links {
+        let mut built_graph = None;
+        let graph: &LinkGraph = match graph {
+            Some(graph) => graph,
+            None => built_graph.insert(
+                LinkGraph::build(vault).map_err(|e| format!("Failed to scan links: {}", e))?,
+            ),
+        };
+        plan(vault, graph, &moves_map)?
+    } else {
+        vec![]
+    };
+
+    Ok(Relocation {
+        old_full,
+        new_full,
+        moves,
+        planned,
+        action: action.to_string(),
+    })
+}
+
+impl Relocation {
+    /// What the move would do, as a dry run reports it
+    pub fn report(&self) -> RelocationReport {
+        RelocationReport {
+            moved: self
+                .moves
+                .iter()
+                .map(|(from, to)| MovedPath { from: from.clone(), to: to.clone() })
+                .collect(),
+            changed_files: self
+                .planned
+                .iter()
+                .map(|f| FileEdits {
+                    path: f.new_path.clone(),
+                    edits: f.edits.clone(),
+                })
+                .collect(),
+            applied: false,
+        }
+    }
+
+    /// Carry out the move and the planned link edits, exactly as prepared
+    pub fn apply(self, vault: &Vault, graph: Option<&mut LinkGraph>) -> Result<RelocationReport, String> {
+        let Relocation { old_full, new_full, moves, planned, action } = self;
+        println!("📁 Moving {:?} -> {:?} ({} notes to update)", old_full, new_full, planned.len());
+
+        // Create parent directories if they don't exist
+        if let Some(parent) = new_full.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create parent directory: {}", e))?;
+        }
+
+        apply(vault, &old_full, &new_full, &planned).map_err(|e| {
+            println!("❌ Failed to {} file: {}", action, e);
+            format!("Failed to {} file: {}", action, e)
+        })?;
+
+        // Bring the graph up to date now rather than waiting for the watcher
+        if let Some(graph) = graph {
+            let mut touched: Vec<PathBuf> = Vec::new();
+            for (old, new) in &moves {
+                touched.push(vault.path().join(old));
+                touched.push(vault.path().join(new));
+            }
+            touched.extend(planned.iter().map(|f| vault.path().join(&f.new_path)));
+            graph.update_paths(&touched);
+        }
+
+        Ok(RelocationReport {
+            moved: moves
+                .into_iter()
+                .map(|(from, to)| MovedPath { from, to })
+                .collect(),
+            changed_files: planned
+                .into_iter()
+                .map(|f| FileEdits {
+                    path: f.new_path,
+                    edits: f.edits,
+                })
+                .collect(),
+            applied: true,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A throwaway vault under the system temp folder
+    struct TestVault {
+        vault: Vault,
+    }
+
+    impl TestVault {
+        fn new(files: &[(&str, &str)]) -> Self {
+            let root = std::env::temp_dir().join(format!("aura-link-rewrite-{:016x}", rand::random::<u64>()));
+            for (path, content) in files {
+                let full = root.join(path);
+                std::fs::create_dir_all(full.parent().unwrap()).unwrap();
+                std::fs::write(full, content).unwrap();
+            }
+            Self { vault: Vault::new(root).unwrap() }
+        }
+
+        fn read(&self, path: &str) -> String {
+            std::fs::read_to_string(self.vault.path().join(path)).unwrap()
+        }
+    }
+
+    impl Drop for TestVault {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(self.vault.path());
+        }
+    }
+
+    #[test]
+    fn relative_urls_climb_out_of_the_source_folder() {
+        assert_eq!(relative_url("a.md", "b.md"), "b.md");
+        assert_eq!(relative_url("Notes/Daily/x.md", "Notes/Img/a b.png"), "../Img/a%20b.png");
+        assert_eq!(relative_url("Notes/x.md", "Notes/y.md"), "y.md");
+    }
+
+    #[test]
+    fn urls_escape_characters_that_end_a_link() {
+        assert_eq!(relative_url("a.md", "C (1)#2 100%.md"), "C%20%281%29%232%20100%25.md");
+    }
+
+    #[test]
+    fn caller_paths_are_keyed_like_the_graph() {
+        assert_eq!(vault_key("./Notes/a.md"), Ok(PathBuf::from("Notes/a.md")));
+        assert_eq!(vault_key("Notes\\a.md"), Ok(PathBuf::from("Notes/a.md")));
+        assert!(vault_key("../a.md").is_err());
+        assert!(vault_key(".").is_err());
+    }
+
+    #[test]
+    fn plan_rewrites_every_kind_of_link() {
+        let test = TestVault::new(&[
+            ("Notes/B.md", "target"),
+            ("A.md", "Link [[B]], [md](Notes/B.md) and [[Notes/B#Part|shown]].\n"),
+        ]);
+        let relocation = prepare(&test.vault, None, "./Notes/B.md", "Archive\\C (1).md", true, "move").unwrap();
+        let report = relocation.report();
+
+        assert_eq!(report.moved.len(), 1);
+        assert_eq!(report.moved[0].from, "Notes/B.md");
+        assert_eq!(report.moved[0].to, "Archive/C (1).md");
+        assert!(!report.applied);
+
+        assert_eq!(report.changed_files.len(), 1);
+        let edits: Vec<&str> = report.changed_files[0].edits.iter().map(|e| e.new_text.as_str()).collect();
+        assert_eq!(
+            edits,
+            ["[[C (1)]]", "[md](Archive/C%20%281%29.md)", "[[Archive/C (1)#Part|shown]]"]
+        );
+        // Planning touches nothing
+        assert!(test.vault.path().join("Notes/B.md").exists());
+    }
+
+    #[test]
+    fn apply_moves_the_file_and_writes_the_plan() {
+        let test = TestVault::new(&[("Notes/B.md", "target"), ("Notes/A.md", "[up](../Top.md) [[B]]\n"), ("Top.md", "")]);
+        let relocation = prepare(&test.vault, None, "Notes/A.md", "Archive/Old/A.md", true, "move").unwrap();
+        let report = relocation.apply(&test.vault, None).unwrap();
+
+        assert!(report.applied);
+        assert!(!test.vault.path().join("Notes/A.md").exists());
+        // The moved note's own relative link is fixed; the bare wiki-link still resolves
+        assert_eq!(test.read("Archive/Old/A.md"), "[up](../../Top.md) [[B]]\n");
+        assert_eq!(report.changed_files[0].path, "Archive/Old/A.md");
+    }
+
+    #[test]
+    fn prepare_refuses_to_overwrite() {
+        let test = TestVault::new(&[("a.md", ""), ("b.md", "")]);
+        assert!(prepare(&test.vault, None, "a.md", "b.md", true, "rename").is_err());
+        assert!(prepare(&test.vault, None, "missing.md", "c.md", true, "rename").is_err());
+    }
+}
@@ -3,7 +3,8 @@
     windows_subsystem = "windows"
 )]
 
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use tauri::{State, Manager, Emitter};
@@ -17,14 +18,70 @@ mod vault;
 mod editor;
 mod pdf_export;
 mod auth;
+mod totp;
 mod ai_settings;
 mod ai_stream;
+mod ai_budget;
+mod ai_provider;
+mod ai_tools;
+mod ai_edit;
+mod ai_metadata;
+mod openai;
+mod anthropic;
+mod ollama;
+mod search_index;
+mod frontmatter;
+mod tags;
+mod wikilinks;
+mod markdown;
+mod link_graph;
+mod embeds;
+mod link_rewrite;
+mod embeddings;
+mod conversations;
+mod chat_export;
+mod prompt_templates;
 
 use vault::Vault;
 use editor::EditorManager;
 use pdf_export::{PdfExporter, ExportOptions};
-use ai_settings::{save_ai_settings, get_ai_settings, test_ai_connection};
-use ai_stream::{send_ai_chat, search_notes_by_name, test_messages, debug_send_ai_chat};
+use ai_settings::{
+    save_ai_settings, get_ai_settings, test_ai_connection, list_ai_models, list_ai_profiles,
+    get_ai_profile, save_ai_profile, delete_ai_profile, switch_ai_profile, clear_vault_ai_profile,
+};
+use ollama::list_ollama_models;
+use ai_stream::{
+    send_ai_chat, send_ai_chat_with_context, send_ai_agent_chat, confirm_ai_tool_call, cancel_ai_chat,
+    search_notes_by_name, test_messages, debug_send_ai_chat,
+};
+use embeddings::{EmbeddingIndex, index_vault_embeddings, get_related_notes};
+use conversations::{
+    list_conversations, load_conversation, create_conversation, append_conversation_message,
+    set_conversation_context, rename_conversation, fork_conversation, delete_conversation,
+    search_conversations,
+};
+use chat_export::{export_chat_to_vault, export_conversation_to_vault};
+use ai_budget::estimate_ai_chat;
+use ai_edit::ai_edit_selection;
+use auth::{
+    authenticate_user, begin_two_factor_setup, confirm_two_factor_setup, create_user,
+    delete_user, disable_two_factor, get_session_policy, get_user_permissions, list_sessions,
+    list_users, logout_user, refresh_session, regenerate_recovery_codes, resume_session,
+    revoke_session, revoke_user_sessions, set_session_policy, set_user_password,
+    set_user_permissions, validate_session, verify_two_factor, AuthManager, VaultAction,
+};
+use prompt_templates::{list_prompt_templates, run_prompt_template};
+use ai_metadata::{
+    apply_note_metadata, discard_metadata_proposals, list_metadata_proposals,
+    propose_note_metadata,
+};
+use search_index::{SearchIndex, search_vault};
+use tags::{TagIndex, get_tag_counts, get_tag_hierarchy, get_note_tags, rename_tag};
+use link_graph::{
+    LinkGraph, get_backlinks, get_outgoing_links, get_unlinked_mentions, get_orphan_notes,
+    export_link_graph,
+};
+use link_rewrite::RelocationReport;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct NoteSearchResult {
@@ -36,6 +93,15 @@ pub struct AppState {
     vault: Arc<Mutex<Option<Vault>>>,
     editor: EditorManager,
     watcher: Arc<Mutex<Option<notify::RecommendedWatcher>>>,
+    search_index: Arc<Mutex<Option<SearchIndex>>>,
+    tag_index: Arc<Mutex<Option<TagIndex>>>,
+    link_graph: Arc<Mutex<Option<LinkGraph>>>,
+    embedding_index: Arc<Mutex<Option<EmbeddingIndex>>>,
+    // Cancel handles for in-flight AI chats, keyed by request id
+    ai_requests: Arc<Mutex<HashMap<String, tokio::sync::oneshot::Sender<()>>>>,
+    // Agent tool calls waiting for the user's approval, keyed by confirmation id
+    tool_confirmations: Arc<Mutex<HashMap<String, tokio::sync::oneshot::Sender<bool>>>>,
+    auth: AuthManager,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -73,6 +139,8 @@ async fn open_vault(path: String, state: State<'_, AppState>) -> Result<VaultInf
         return Err("Path is not a directory".to_string());
     }
     
+    state.auth.authorize(&vault_path, &vault_path, VaultAction::Read).await?;
+    
     let vault = Vault::new(vault_path.clone())
         .map_err(|e| format!("Failed to open vault: {}", e))?;
     
@@ -84,12 +152,72 @@ async fn open_vault(path: String, state: State<'_, AppState>) -> Result<VaultInf
             .to_string(),
     };
     
+    let search_index = match SearchIndex::open(&vault) {
+        Ok(index) => Some(index),
+        Err(e) => {
+            println!("⚠️ Failed to build search index: {}", e);
+            None
+        }
+    };
+    
+    let tag_index = match TagIndex::build(&vault) {
+        Ok(index) => Some(index),
+        Err(e) => {
+            println!("⚠️ Failed to build tag index: {}", e);
+            None
+        }
+    };
+    
+    let link_graph = match LinkGraph::build(&vault) {
+        Ok(graph) => Some(graph),
+        Err(e) => {
+            println!("⚠️ Failed to build link graph: {}", e);
+            None
+        }
+    };
+    
+    // Only loads what's on disk; embedding needs the AI provider and waits for first use
+    let embedding_index = EmbeddingIndex::open(&vault);
+    
     let mut vault_lock = state.vault.lock().await;
     *vault_lock = Some(vault);
     
+    *state.search_index.lock().await = search_index;
+    *state.tag_index.lock().await = tag_index;
+    *state.link_graph.lock().await = link_graph;
+    *state.embedding_index.lock().await = Some(embedding_index);
+    
     Ok(vault_info)
 }
 
+// Bring the vault indexes up to date with files that changed on disk
+fn update_vault_indexes(app: &tauri::AppHandle, paths: &[PathBuf]) {
+    let state = app.state::<AppState>();
+    
+    tauri::async_runtime::block_on(async {
+        let mut index_lock = state.search_index.lock().await;
+        if let Some(index) = index_lock.as_mut() {
+            if index.update_paths(paths) {
+                if let Err(e) = index.save() {
+                    println!("⚠️ Failed to save search index: {}", e);
+                }
+            }
+        }
+        drop(index_lock);
+        
+        if let Some(index) = state.tag_index.lock().await.as_mut() {
+            index.update_paths(paths);
+        }
+        
+        if let Some(graph) = state.link_graph.lock().await.as_mut() {
+            graph.update_paths(paths);
+        }
+    });
+    
+    // Embedding goes through the AI provider and can be slow, so don't hold up the watcher
+    tauri::async_runtime::spawn(embeddings::refresh_paths(app.clone(), paths.to_vec()));
+}
+
 #[tauri::command]
 async fn start_file_watcher(vault_path: String, app: tauri::AppHandle, state: State<'_, AppState>) -> Result<(), String> {
     // Stop any existing watcher
@@ -99,7 +227,7 @@ async fn start_file_watcher(vault_path: String, app: tauri::AppHandle, state: St
     let path = PathBuf::from(&vault_path);
     
     // Set up file watcher for the vault
-    let (tx, rx) = channel();
+    let (tx, rx) = channel::<notify::Result<notify::Event>>();
     let app_handle = app.clone();
     
     // Spawn a thread to handle file system events
@@ -107,12 +235,22 @@ async fn start_file_watcher(vault_path: String, app: tauri::AppHandle, state: St
         use std::time::Instant;
         let mut last_emit_time = Instant::now();
         let mut pending_event = false;
+        let mut changed_paths: HashSet<PathBuf> = HashSet::new();
         
         loop {
             // Check for events with a timeout
             match rx.recv_timeout(Duration::from_millis(100)) {
                 Ok(Ok(event)) => {
+                    // Ignore our own writes to the .aura directory (indexes, caches)
+                    let is_internal = event.paths.iter().all(|p| {
+                        p.components().any(|c| c.as_os_str() == ".aura")
+                    });
+                    if is_internal {
+                        continue;
+                    }
+                    
                     println!("📁 File system event: {:?}", event);
+                    changed_paths.extend(event.paths);
                     pending_event = true;
                     last_emit_time = Instant::now();
                 }
@@ -120,6 +258,9 @@ async fn start_file_watcher(vault_path: String, app: tauri::AppHandle, state: St
                 Err(_) => {
                     // Timeout - check if we should emit
                     if pending_event && last_emit_time.elapsed() > Duration::from_millis(300) {
+                        let paths: Vec<PathBuf> = changed_paths.drain().collect();
+                        update_vault_indexes(&app_handle, &paths);
+                        
                         println!("📢 Emitting vault-files-changed event");
                         let _ = app_handle.emit("vault-files-changed", ());
                         pending_event = false;
@@ -315,6 +456,11 @@ async fn get_file_tree(state: State<'_, AppState>) -> Result<FileTree, String> {
                 
                 println!("📍 Relative path: {:?}", relative_path);
                 
+                // Notes the user can't read are left out of the tree
+                if !state.auth.allows(vault.path(), relative_path, VaultAction::Read).await {
+                    continue;
+                }
+                
                 // Calculate depth and parent
                 let path_str = relative_path.to_string_lossy().to_string();
                 let components: Vec<_> = relative_path.components().collect();
@@ -388,6 +534,7 @@ async fn read_file_content(file_path: String, state: State<'_, AppState>) -> Res
             let path = std::path::Path::new(&file_path);
             println!("📁 Vault path: {:?}", vault.path());
             println!("📄 Reading relative path: {:?}", path);
+            state.auth.authorize(vault.path(), path, VaultAction::Read).await?;
 
             vault.read_file(path)
                 .map_err(|e| {
@@ -406,6 +553,7 @@ async fn write_file_content(file_path: String, content: String, state: State<'_,
     match &*vault_lock {
         Some(vault) => {
             let path = std::path::Path::new(&file_path);
+            state.auth.authorize(vault.path(), path, VaultAction::Write).await?;
             vault.write_file(path, &content)
                 .map_err(|e| format!("Failed to write file: {}", e))
         }
@@ -450,6 +598,7 @@ async fn create_new_file(file_name: String, state: State<'_, AppState>) -> Resul
             let path = std::path::Path::new(&file_name);
             println!("📁 Vault path: {:?}", vault.path());
             println!("📄 Creating file: {:?}", path);
+            state.auth.authorize(vault.path(), path, VaultAction::Write).await?;
 
             // Create default content for new file
             let default_content = format!("# {}",
@@ -478,6 +627,7 @@ async fn create_new_folder(folder_name: String, state: State<'_, AppState>) -> R
         Some(vault) => {
             let folder_path = vault.path().join(&folder_name);
             println!("📁 Creating folder at: {:?}", folder_path);
+            state.auth.authorize(vault.path(), &folder_path, VaultAction::Write).await?;
 
             std::fs::create_dir_all(&folder_path)
                 .map_err(|e| {
@@ -499,6 +649,7 @@ async fn delete_file(file_path: String, state: State<'_, AppState>) -> Result<()
         Some(vault) => {
             let path = vault.path().join(&file_path);
             println!("📁 Deleting file at: {:?}", path);
+            state.auth.authorize(vault.path(), &path, VaultAction::Delete).await?;
 
             if path.is_file() {
                 std::fs::remove_file(&path)
@@ -515,58 +666,66 @@ async fn delete_file(file_path: String, state: State<'_, AppState>) -> Result<()
 }
 
 #[tauri::command]
-async fn move_file(old_path: String, new_path: String, state: State<'_, AppState>) -> Result<(), String> {
+async fn move_file(
+    old_path: String,
+    new_path: String,
+    update_links: Option<bool>,
+    dry_run: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<RelocationReport, String> {
     println!("📦 move_file called: {} -> {}", old_path, new_path);
-
-    let vault_lock = state.vault.lock().await;
-
-    match &*vault_lock {
-        Some(vault) => {
-            let old_full_path = vault.path().join(&old_path);
-            let new_full_path = vault.path().join(&new_path);
-            
-            println!("📁 Moving file: {:?} -> {:?}", old_full_path, new_full_path);
-
-            // Create parent directories if they don't exist
-            if let Some(parent) = new_full_path.parent() {
-                std::fs::create_dir_all(parent)
-                    .map_err(|e| format!("Failed to create parent directory: {}", e))?;
-            }
-
-            std::fs::rename(&old_full_path, &new_full_path)
-                .map_err(|e| {
-                    println!("❌ Failed to move file: {}", e);
-                    format!("Failed to move file: {}", e)
-                })
-        }
-        None => Err("No vault opened".to_string()),
-    }
+    relocate_path(&state, &old_path, &new_path, update_links, dry_run, "move").await
 }
 
 #[tauri::command]
-async fn rename_file(old_path: String, new_path: String, state: State<'_, AppState>) -> Result<(), String> {
+async fn rename_file(
+    old_path: String,
+    new_path: String,
+    update_links: Option<bool>,
+    dry_run: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<RelocationReport, String> {
     println!("✏️ rename_file called: {} -> {}", old_path, new_path);
+    relocate_path(&state, &old_path, &new_path, update_links, dry_run, "rename").await
+}
 
+async fn relocate_path(
+    state: &AppState,
+    old_path: &str,
+    new_path: &str,
+    update_links: Option<bool>,
+    dry_run: Option<bool>,
+    action: &str,
+) -> Result<RelocationReport, String> {
     let vault_lock = state.vault.lock().await;
 
     match &*vault_lock {
         Some(vault) => {
-            let old_full_path = vault.path().join(&old_path);
-            let new_full_path = vault.path().join(&new_path);
+            let mut graph_lock = state.link_graph.lock().await;
+            let update_links = update_links.unwrap_or(false);
             
-            println!("📁 Renaming file: {:?} -> {:?}", old_full_path, new_full_path);
-
-            // Create parent directories if they don't exist
-            if let Some(parent) = new_full_path.parent() {
-                std::fs::create_dir_all(parent)
-                    .map_err(|e| format!("Failed to create parent directory: {}", e))?;
+            state.auth.authorize(vault.path(), Path::new(old_path), VaultAction::Delete).await?;
+            state.auth.authorize(vault.path(), Path::new(new_path), VaultAction::Write).await?;
+            
+            // Plan once and check that plan, so nothing moves unless every
+            // file it touches may change
+            let dry_run = dry_run.unwrap_or(false);
+            let relocation = link_rewrite::prepare(vault, graph_lock.as_ref(), old_path, new_path, update_links || dry_run, action)?;
+            let plan = relocation.report();
+            for moved in &plan.moved {
+                state.auth.authorize(vault.path(), Path::new(&moved.from), VaultAction::Delete).await?;
+                state.auth.authorize(vault.path(), Path::new(&moved.to), VaultAction::Write).await?;
             }
-
-            std::fs::rename(&old_full_path, &new_full_path)
-                .map_err(|e| {
-                    println!("❌ Failed to rename file: {}", e);
-                    format!("Failed to rename file: {}", e)
-                })
+            if update_links {
+                for file in &plan.changed_files {
+                    state.auth.authorize(vault.path(), Path::new(&file.path), VaultAction::Write).await?;
+                }
+            }
+            if dry_run {
+                return Ok(plan);
+            }
+            
+            relocation.apply(vault, graph_lock.as_mut())
         }
         None => Err("No vault opened".to_string()),
     }
@@ -636,17 +795,18 @@ async fn save_pasted_image(
     
     match &*vault_lock {
         Some(vault) => {
-            // Create files directory if it doesn't exist
-            let files_dir = vault.path().join("files");
-            std::fs::create_dir_all(&files_dir)
-                .map_err(|e| format!("Failed to create files directory: {}", e))?;
-            
             // Generate filename with timestamp
+            let files_dir = vault.path().join("files");
             let timestamp = Local::now().format("%Y%m%d%H%M%S").to_string();
             let filename = format!("Pasted image {}.{}", timestamp, extension);
             let file_path = files_dir.join(&filename);
             
             println!("💾 Saving image to: {:?}", file_path);
+            state.auth.authorize(vault.path(), &file_path, VaultAction::Write).await?;
+            
+            // Create files directory if it doesn't exist
+            std::fs::create_dir_all(&files_dir)
+                .map_err(|e| format!("Failed to create files directory: {}", e))?;
             
             // Decode base64 and save file
             let image_bytes = general_purpose::STANDARD.decode(&image_data)
@@ -677,6 +837,7 @@ async fn read_image_as_base64(
         Some(vault) => {
             let full_path = vault.path().join(&file_path);
             println!("📁 Reading image from: {:?}", full_path);
+            state.auth.authorize(vault.path(), &full_path, VaultAction::Read).await?;
             
             // Read the file as bytes
             let image_bytes = std::fs::read(&full_path)
@@ -709,6 +870,7 @@ async fn export_to_pdf(
     markdown_content: String,
     output_path: String,
     options: Option<ExportOptions>,
+    source_path: Option<String>, // Note being exported, for its permissions
     state: State<'_, AppState>
 ) -> Result<(), String> {
     println!("📄 export_to_pdf called with output path: {}", output_path);
@@ -717,6 +879,9 @@ async fn export_to_pdf(
     
     match &*vault_lock {
         Some(vault) => {
+            let source = PathBuf::from(source_path.unwrap_or_default());
+            state.auth.authorize(vault.path(), &source, VaultAction::Export).await?;
+            
             let exporter = PdfExporter::new(vault.path().to_path_buf());
             let export_options = options.unwrap_or_default();
             
@@ -735,6 +900,7 @@ async fn export_to_html(
     markdown_content: String,
     output_path: String,
     options: Option<ExportOptions>,
+    source_path: Option<String>, // Note being exported, for its permissions
     state: State<'_, AppState>
 ) -> Result<(), String> {
     println!("📄 export_to_html called with output path: {}", output_path);
@@ -743,6 +909,9 @@ async fn export_to_html(
     
     match &*vault_lock {
         Some(vault) => {
+            let source = PathBuf::from(source_path.unwrap_or_default());
+            state.auth.authorize(vault.path(), &source, VaultAction::Export).await?;
+            
             let export_options = options.unwrap_or_default();
             
             pdf_export::export_to_html(
@@ -761,6 +930,7 @@ async fn export_to_word(
     markdown_content: String,
     output_path: String,
     options: Option<ExportOptions>,
+    source_path: Option<String>, // Note being exported, for its permissions
     state: State<'_, AppState>
 ) -> Result<(), String> {
     println!("📄 export_to_word called with output path: {}", output_path);
@@ -769,6 +939,9 @@ async fn export_to_word(
     
     match &*vault_lock {
         Some(vault) => {
+            let source = PathBuf::from(source_path.unwrap_or_default());
+            state.auth.authorize(vault.path(), &source, VaultAction::Export).await?;
+            
             let export_options = options.unwrap_or_default();
             
             pdf_export::export_to_word(
@@ -782,43 +955,6 @@ async fn export_to_word(
     }
 }
 
-#[tauri::command]
-async fn export_chat_to_vault(
-    state: State<'_, AppState>,
-    content: String,
-    filename: Option<String>
-) -> Result<String, String> {
-    let vault_lock = state.vault.lock().await;
-    
-    match &*vault_lock {
-        Some(vault) => {
-            let vault_path = vault.path();
-            let chat_history_dir = vault_path.join("Chat History");
-            
-            // Create Chat History directory if it doesn't exist
-            if !chat_history_dir.exists() {
-                std::fs::create_dir(&chat_history_dir)
-                    .map_err(|e| format!("Failed to create Chat History directory: {}", e))?;
-            }
-            
-            // Generate filename with timestamp if not provided
-            let file_name = filename.unwrap_or_else(|| {
-                let now = chrono::Local::now();
-                format!("chat-{}.md", now.format("%Y-%m-%d_%H-%M-%S"))
-            });
-            
-            let file_path = chat_history_dir.join(&file_name);
-            
-            // Write the chat content to the file
-            std::fs::write(&file_path, content)
-                .map_err(|e| format!("Failed to write chat file: {}", e))?;
-            
-            Ok(file_path.to_string_lossy().to_string())
-        }
-        None => Err("No vault is currently open".to_string()),
-    }
-}
-
 #[tauri::command]
 async fn select_export_location(
     app: tauri::AppHandle,
@@ -870,6 +1006,13 @@ fn main() {
         vault: Arc::new(Mutex::new(None)),
         editor: EditorManager::new(),
         watcher: Arc::new(Mutex::new(None)),
+        search_index: Arc::new(Mutex::new(None)),
+        tag_index: Arc::new(Mutex::new(None)),
+        link_graph: Arc::new(Mutex::new(None)),
+        embedding_index: Arc::new(Mutex::new(None)),
+        ai_requests: Arc::new(Mutex::new(HashMap::new())),
+        tool_confirmations: Arc::new(Mutex::new(HashMap::new())),
+        auth: AuthManager::new(),
     };
     
     tauri::Builder::default()
@@ -903,6 +1046,15 @@ fn main() {
             editor::list_theme_files,
             editor::open_note,
             editor::search_by_tag,
+            get_tag_counts,
+            get_tag_hierarchy,
+            get_note_tags,
+            rename_tag,
+            get_backlinks,
+            get_outgoing_links,
+            get_unlinked_mentions,
+            get_orphan_notes,
+            export_link_graph,
             editor::get_embedded_block,
             editor::create_theme_directory,
             editor::update_editor_state,
@@ -911,17 +1063,74 @@ fn main() {
             export_to_html,
             export_to_word,
             export_chat_to_vault,
+            export_conversation_to_vault,
+            list_conversations,
+            load_conversation,
+            create_conversation,
+            append_conversation_message,
+            set_conversation_context,
+            rename_conversation,
+            fork_conversation,
+            delete_conversation,
+            search_conversations,
             select_export_location,
             save_ai_settings,
             get_ai_settings,
             test_ai_connection,
+            list_ai_models,
+            list_ai_profiles,
+            get_ai_profile,
+            save_ai_profile,
+            delete_ai_profile,
+            switch_ai_profile,
+            clear_vault_ai_profile,
+            list_ollama_models,
             send_ai_chat,
+            send_ai_chat_with_context,
+            send_ai_agent_chat,
+            confirm_ai_tool_call,
+            estimate_ai_chat,
+            ai_edit_selection,
+            list_prompt_templates,
+            run_prompt_template,
+            propose_note_metadata,
+            list_metadata_proposals,
+            apply_note_metadata,
+            discard_metadata_proposals,
+            cancel_ai_chat,
+            index_vault_embeddings,
+            get_related_notes,
             search_notes_by_name,
+            search_vault,
+            authenticate_user,
+            verify_two_factor,
+            resume_session,
+            refresh_session,
+            logout_user,
+            validate_session,
+            list_sessions,
+            revoke_session,
+            revoke_user_sessions,
+            get_session_policy,
+            set_session_policy,
+            begin_two_factor_setup,
+            confirm_two_factor_setup,
+            regenerate_recovery_codes,
+            disable_two_factor,
+            get_user_permissions,
+            list_users,
+            create_user,
+            delete_user,
+            set_user_password,
+            set_user_permissions,
             test_messages,
             debug_send_ai_chat,
         ])
-        .setup(|_app| {
-            // Any initial setup can go here
+        .setup(|app| {
+            let config_dir = app.path().app_config_dir()?;
+            let state = app.state::<AppState>();
+            tauri::async_runtime::block_on(state.auth.load(config_dir.join(".aura").join(auth::AUTH_FILE)));
+            auth::spawn_session_sweeper(app.handle().clone());
             Ok(())
         })
         .run(tauri::generate_context!())
@@ -0,0 +1,57 @@
+// markdown.rs - Markdown text helpers shared by the vault indexes
+
+// Replace a character with spaces of the same byte length, keeping newlines
+fn push_blank(masked: &mut String, c: char) {
+    if c == '\n' {
+        masked.push(c);
+    } else {
+        masked.push_str(&" ".repeat(c.len_utf8()));
+    }
+}
+
+/// Blank out fenced code blocks and inline code spans, preserving byte
+/// offsets, so link and tag patterns never match inside code.
+pub fn mask_code(text: &str) -> String {
+    let mut masked = String::with_capacity(text.len());
+    let mut in_fence = false;
+
+    for line in text.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+        let is_fence = trimmed.starts_with("```") || trimmed.starts_with("~~~");
+        if is_fence {
+            in_fence = !in_fence;
+        }
+        if in_fence || is_fence {
+            line.chars().for_each(|c| push_blank(&mut masked, c));
+            continue;
+        }
+
+        let mut in_span = false;
+        for c in line.chars() {
+            if c == '`' {
+                in_span = !in_span;
+                masked.push(' ');
+            } else if in_span {
+                push_blank(&mut masked, c);
+            } else {
+                masked.push(c);
+            }
+        }
+    }
+
+    masked
+}
+
+/// The level and text of an ATX heading line (`## Title`), if it is one.
+pub fn heading_level(line: &str) -> Option<(usize, &str)> {
+    let trimmed = line.trim_start();
+    let level = trimmed.chars().take_while(|&c| c == '#').count();
+    if level == 0 || level > 6 {
+        return None;
+    }
+    let rest = &trimmed[level..];
+    if !rest.is_empty() && !rest.starts_with(' ') && !rest.starts_with('\t') {
+        return None;
+    }
+    Some((level, rest.trim().trim_end_matches('#').trim()))
+}
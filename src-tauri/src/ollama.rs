@@ -0,0 +1,373 @@
+// ollama.rs - Ollama's native API: /api/chat streaming and /api/tags
+
+use futures_util::future::BoxFuture;
+use futures_util::FutureExt;
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+
+use crate::ai_provider::{self, AiProvider, StreamLine};
+use crate::ai_settings::{get_ai_settings, AISettings, ConnectionTestResult};
+use crate::ai_stream::ChatMessage;
+use crate::ai_tools::{self, AgentMessage, AgentTurn, ToolCall, ToolSpec};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OllamaModel {
+    pub name: String,
+    #[serde(default)]
+    pub size: u64,
+    #[serde(default)]
+    pub modified_at: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TagsResponse {
+    #[serde(default)]
+    models: Vec<OllamaModel>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbedResponse {
+    embeddings: Vec<Vec<f32>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatChunkMessage {
+    #[serde(default)]
+    content: String,
+    #[serde(default)]
+    tool_calls: Vec<OllamaToolCall>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaToolCall {
+    function: OllamaFunction,
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaFunction {
+    name: String,
+    #[serde(default)]
+    arguments: serde_json::Value,
+}
+
+/// One line of a streamed `/api/chat` response, or the whole of an unstreamed one.
+#[derive(Debug, Deserialize)]
+struct ChatChunk {
+    message: Option<ChatChunkMessage>,
+    #[serde(default)]
+    done: bool,
+    error: Option<String>,
+}
+
+impl ChatChunk {
+    fn content(&self) -> &str {
+        self.message.as_ref().map_or("", |m| m.content.as_str())
+    }
+}
+
+// Accept `http://host:11434`, with or without a trailing `/api`
+fn api_base(endpoint: &str) -> String {
+    let endpoint = endpoint.trim_end_matches('/');
+    let endpoint = endpoint.strip_suffix("/api").unwrap_or(endpoint);
+    format!("{}/api", endpoint)
+}
+
+/// Models installed on an Ollama server.
+pub async fn list_models(endpoint: &str) -> Result<Vec<OllamaModel>, String> {
+    let client = ai_provider::test_client()?;
+    let response = ai_provider::send_checked(client.get(format!("{}/tags", api_base(endpoint)))).await?;
+
+    let tags: TagsResponse = response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse model list: {}", e))?;
+    Ok(tags.models)
+}
+
+/// Whether `model` names an installed model; a bare name means `:latest`.
+fn has_model(models: &[OllamaModel], model: &str) -> bool {
+    models
+        .iter()
+        .any(|m| m.name == model || m.name == format!("{}:latest", model))
+}
+
+// Ollama takes tool arguments as objects and matches results by tool name
+fn wire_message(message: &AgentMessage) -> serde_json::Value {
+    match message {
+        AgentMessage::Chat(message) => serde_json::json!(message),
+        AgentMessage::ToolCalls { content, calls } => serde_json::json!({
+            "role": "assistant",
+            "content": content,
+            "tool_calls": calls.iter().map(|call| serde_json::json!({
+                "function": { "name": call.name, "arguments": call.arguments }
+            })).collect::<Vec<_>>()
+        }),
+        AgentMessage::ToolResult { name, content, .. } => serde_json::json!({
+            "role": "tool",
+            "tool_name": name,
+            "content": content
+        }),
+    }
+}
+
+fn parse_stream_line(line: &str) -> StreamLine {
+    match serde_json::from_str::<ChatChunk>(line) {
+        Ok(ChatChunk { error: Some(error), .. }) => StreamLine::Error(error),
+        Ok(chunk) if chunk.done => {
+            if chunk.content().is_empty() {
+                StreamLine::Done
+            } else {
+                StreamLine::Last(chunk.content().to_string())
+            }
+        }
+        Ok(chunk) if chunk.content().is_empty() => StreamLine::Skip,
+        Ok(chunk) => StreamLine::Content(chunk.content().to_string()),
+        Err(e) => {
+            println!("Skipping unparseable stream line: {} ({})", line, e);
+            StreamLine::Skip
+        }
+    }
+}
+
+pub struct OllamaProvider {
+    settings: AISettings,
+}
+
+impl OllamaProvider {
+    pub fn new(settings: AISettings) -> Self {
+        Self { settings }
+    }
+
+    fn options(&self) -> serde_json::Value {
+        let mut options = serde_json::json!({
+            "temperature": self.settings.temperature,
+            "num_predict": self.settings.max_tokens
+        });
+        // Without `num_ctx` the server uses its own default window, which
+        // the chat budget assumes
+        if let Some(window) = self.settings.context_window.filter(|w| *w > 0) {
+            options["num_ctx"] = serde_json::json!(window);
+        }
+        options
+    }
+
+    fn chat_request(
+        &self,
+        client: &reqwest::Client,
+        messages: &[ChatMessage],
+        stream: bool,
+    ) -> reqwest::RequestBuilder {
+        let body = serde_json::json!({
+            "model": self.settings.model,
+            "messages": messages,
+            "stream": stream,
+            "options": self.options()
+        });
+        client
+            .post(format!("{}/chat", api_base(&self.settings.endpoint)))
+            .header("Content-Type", "application/json")
+            .json(&body)
+    }
+}
+
+impl AiProvider for OllamaProvider {
+    fn chat<'a>(&'a self, messages: &'a [ChatMessage]) -> BoxFuture<'a, Result<String, String>> {
+        async move {
+            let client = ai_provider::streaming_client()?;
+            let response = ai_provider::send_checked(self.chat_request(&client, messages, false)).await?;
+            let chunk: ChatChunk = response
+                .json()
+                .await
+                .map_err(|e| format!("Failed to parse JSON: {}", e))?;
+
+            match chunk.error {
+                Some(error) => Err(format!("API error: {}", error)),
+                None => Ok(chunk.content().to_string()),
+            }
+        }
+        .boxed()
+    }
+
+    fn stream<'a>(
+        &'a self,
+        messages: &'a [ChatMessage],
+        on_text: &'a mut (dyn FnMut(String) + Send),
+    ) -> BoxFuture<'a, Result<(), String>> {
+        async move {
+            println!("Using Ollama native chat API, streaming NDJSON...");
+            let client = ai_provider::streaming_client()?;
+            let response = ai_provider::send_checked(self.chat_request(&client, messages, true)).await?;
+            ai_provider::read_stream(response, parse_stream_line, on_text).await
+        }
+        .boxed()
+    }
+
+    fn chat_with_tools<'a>(
+        &'a self,
+        messages: &'a [AgentMessage],
+        tools: &'a [ToolSpec],
+    ) -> BoxFuture<'a, Result<AgentTurn, String>> {
+        async move {
+            let body = serde_json::json!({
+                "model": self.settings.model,
+                "messages": messages.iter().map(wire_message).collect::<Vec<_>>(),
+                "tools": ai_tools::function_tools(tools),
+                "stream": false,
+                "options": self.options()
+            });
+
+            let client = ai_provider::streaming_client()?;
+            let request = client
+                .post(format!("{}/chat", api_base(&self.settings.endpoint)))
+                .header("Content-Type", "application/json")
+                .json(&body);
+            let chunk: ChatChunk = ai_provider::send_checked(request)
+                .await?
+                .json()
+                .await
+                .map_err(|e| format!("Failed to parse JSON: {}", e))?;
+
+            if let Some(error) = chunk.error {
+                return Err(format!("API error: {}", error));
+            }
+            let Some(message) = chunk.message else {
+                return Ok(AgentTurn::Reply(String::new()));
+            };
+            if message.tool_calls.is_empty() {
+                return Ok(AgentTurn::Reply(message.content));
+            }
+
+            // Ollama doesn't id its calls; results are matched by position
+            let calls = message
+                .tool_calls
+                .into_iter()
+                .enumerate()
+                .map(|(i, call)| ToolCall {
+                    id: format!("call_{}", i),
+                    name: call.function.name,
+                    arguments: call.function.arguments,
+                })
+                .collect();
+            Ok(AgentTurn::ToolCalls {
+                content: message.content,
+                calls,
+            })
+        }
+        .boxed()
+    }
+
+    fn embed<'a>(&'a self, texts: &'a [String]) -> BoxFuture<'a, Result<Vec<Vec<f32>>, String>> {
+        async move {
+            let model = self.settings.embedding_model().ok_or_else(ai_provider::no_embedding_model)?;
+            let body = serde_json::json!({ "model": model, "input": texts });
+
+            let client = ai_provider::streaming_client()?;
+            let request = client
+                .post(format!("{}/embed", api_base(&self.settings.endpoint)))
+                .header("Content-Type", "application/json")
+                .json(&body);
+            let response: EmbedResponse = ai_provider::send_checked(request)
+                .await?
+                .json()
+                .await
+                .map_err(|e| format!("Failed to parse embeddings: {}", e))?;
+            Ok(response.embeddings)
+        }
+        .boxed()
+    }
+
+    fn list_models(&self) -> BoxFuture<'_, Result<Vec<String>, String>> {
+        async move {
+            let models = list_models(&self.settings.endpoint).await?;
+            Ok(models.into_iter().map(|m| m.name).collect())
+        }
+        .boxed()
+    }
+
+    fn test_connection(&self) -> BoxFuture<'_, ConnectionTestResult> {
+        async move {
+            let mut result = ConnectionTestResult::pending();
+
+            // Ollama's native API has no auth; check the model is installed instead
+            result.auth_status.success = true;
+            result.auth_status.message = "No authentication required".to_string();
+
+            match list_models(&self.settings.endpoint).await {
+                Ok(models) if has_model(&models, &self.settings.model) => {
+                    result.endpoint_status.success = true;
+                    result.endpoint_status.message = "Ollama is running".to_string();
+                    result.model_status.success = true;
+                    result.model_status.message = "Model is available".to_string();
+                    result.finish();
+                }
+                Ok(models) => {
+                    result.endpoint_status.success = true;
+                    result.endpoint_status.message = "Ollama is running".to_string();
+                    result.model_status.message = format!(
+                        "Model '{}' is not installed ({} models available)",
+                        self.settings.model,
+                        models.len()
+                    );
+                    result.overall_status.message = format!("Run `ollama pull {}` first", self.settings.model);
+                }
+                Err(e) => {
+                    result.endpoint_status.message = format!("Ollama API not accessible: {}", e);
+                    result.overall_status.message = "Failed to reach Ollama".to_string();
+                }
+            }
+
+            result
+        }
+        .boxed()
+    }
+}
+
+#[tauri::command]
+pub async fn list_ollama_models(
+    app: AppHandle,
+    endpoint: Option<String>,
+) -> Result<Vec<OllamaModel>, String> {
+    let endpoint = match endpoint {
+        Some(endpoint) => endpoint,
+        None => get_ai_settings(app)
+            .await?
+            .map(|s| s.endpoint)
+            .ok_or("No AI settings configured")?,
+    };
+
+    println!("Listing Ollama models at {}", endpoint);
+    list_models(&endpoint).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stream_lines_carry_message_content() {
+        let line = r#"{"message":{"role":"assistant","content":"Hi"},"done":false}"#;
+        assert_eq!(parse_stream_line(line), StreamLine::Content("Hi".to_string()));
+        let empty = r#"{"message":{"role":"assistant","content":""},"done":false}"#;
+        assert_eq!(parse_stream_line(empty), StreamLine::Skip);
+    }
+
+    #[test]
+    fn the_done_line_may_carry_content() {
+        let last = r#"{"message":{"role":"assistant","content":"!"},"done":true}"#;
+        assert_eq!(parse_stream_line(last), StreamLine::Last("!".to_string()));
+        assert_eq!(parse_stream_line(r#"{"done":true}"#), StreamLine::Done);
+    }
+
+    #[test]
+    fn stream_errors_are_reported() {
+        let line = r#"{"error":"model 'x' not found"}"#;
+        assert_eq!(parse_stream_line(line), StreamLine::Error("model 'x' not found".to_string()));
+        assert_eq!(parse_stream_line("not json"), StreamLine::Skip);
+    }
+
+    #[test]
+    fn api_base_accepts_either_form() {
+        assert_eq!(api_base("http://localhost:11434"), "http://localhost:11434/api");
+        assert_eq!(api_base("http://localhost:11434/api/"), "http://localhost:11434/api");
+    }
+}
@@ -0,0 +1,372 @@
+// openai.rs - OpenAI-compatible /chat/completions backend (OpenAI, LM Studio, Ollama's /v1)
+
+use futures_util::future::BoxFuture;
+use futures_util::FutureExt;
+use serde::Deserialize;
+
+use crate::ai_provider::{self, AiProvider, StreamLine};
+use crate::ai_settings::{AISettings, ConnectionTestResult};
+use crate::ai_stream::ChatMessage;
+use crate::ai_tools::{self, AgentMessage, AgentTurn, ToolCall, ToolSpec};
+
+#[derive(Debug, Deserialize)]
+struct OpenAIStreamChunk {
+    choices: Vec<Choice>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Choice {
+    delta: Delta,
+}
+
+#[derive(Debug, Deserialize)]
+struct Delta {
+    content: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingResponse {
+    data: Vec<EmbeddingData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingData {
+    #[serde(default)]
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+pub struct OpenAIProvider {
+    settings: AISettings,
+}
+
+impl OpenAIProvider {
+    pub fn new(settings: AISettings) -> Self {
+        Self { settings }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/{}", self.settings.endpoint.trim_end_matches('/'), path)
+    }
+
+    fn api_key(&self) -> Option<&str> {
+        self.settings.api_key.as_deref().filter(|key| !key.is_empty())
+    }
+
+    fn with_auth(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match self.api_key() {
+            Some(api_key) => request.header("Authorization", format!("Bearer {}", api_key)),
+            None => request,
+        }
+    }
+
+    fn chat_request(
+        &self,
+        client: &reqwest::Client,
+        messages: &[ChatMessage],
+        stream: bool,
+    ) -> reqwest::RequestBuilder {
+        let body = serde_json::json!({
+            "model": self.settings.model,
+            "messages": messages,
+            "temperature": self.settings.temperature,
+            "max_tokens": self.settings.max_tokens,
+            "stream": stream
+        });
+        self.with_auth(client.post(self.url("chat/completions")))
+            .header("Content-Type", "application/json")
+            .json(&body)
+    }
+}
+
+// OpenAI wants tool arguments as a JSON string and results tied to call ids
+fn wire_message(message: &AgentMessage) -> serde_json::Value {
+    match message {
+        AgentMessage::Chat(message) => serde_json::json!(message),
+        AgentMessage::ToolCalls { content, calls } => serde_json::json!({
+            "role": "assistant",
+            "content": if content.is_empty() { None } else { Some(content) },
+            "tool_calls": calls.iter().map(|call| serde_json::json!({
+                "id": call.id,
+                "type": "function",
+                "function": { "name": call.name, "arguments": call.arguments.to_string() }
+            })).collect::<Vec<_>>()
+        }),
+        AgentMessage::ToolResult { call_id, content, .. } => serde_json::json!({
+            "role": "tool",
+            "tool_call_id": call_id,
+            "content": content
+        }),
+    }
+}
+
+fn parse_tool_calls(message: &serde_json::Value) -> Vec<ToolCall> {
+    let Some(calls) = message.get("tool_calls").and_then(|c| c.as_array()) else {
+        return Vec::new();
+    };
+    calls
+        .iter()
+        .enumerate()
+        .filter_map(|(i, call)| {
+            let function = call.get("function")?;
+            let arguments = function
+                .get("arguments")
+                .and_then(|a| a.as_str())
+                .and_then(|a| serde_json::from_str(a).ok())
+                .unwrap_or(serde_json::Value::Null);
+            Some(ToolCall {
+                id: call
+                    .get("id")
+                    .and_then(|id| id.as_str())
+                    .map(|id| id.to_string())
+                    .unwrap_or_else(|| format!("call_{}", i)),
+                name: function.get("name")?.as_str()?.to_string(),
+                arguments,
+            })
+        })
+        .collect()
+}
+
+fn parse_stream_line(line: &str) -> StreamLine {
+    let Some(data) = ai_provider::sse_data(line) else {
+        return StreamLine::Skip;
+    };
+    if data == "[DONE]" {
+        return StreamLine::Done;
+    }
+
+    match serde_json::from_str::<OpenAIStreamChunk>(data) {
+        Ok(chunk) => {
+            let content: String = chunk
+                .choices
+                .into_iter()
+                .filter_map(|choice| choice.delta.content)
+                .collect();
+            if content.is_empty() {
+                StreamLine::Skip
+            } else {
+                StreamLine::Content(content)
+            }
+        }
+        Err(e) => match serde_json::from_str::<serde_json::Value>(data)
+            .ok()
+            .as_ref()
+            .and_then(ai_provider::api_error_message)
+        {
+            Some(error) => StreamLine::Error(error),
+            None => {
+                println!("Skipping unparseable stream data: {} ({})", data, e);
+                StreamLine::Skip
+            }
+        },
+    }
+}
+
+impl AiProvider for OpenAIProvider {
+    fn chat<'a>(&'a self, messages: &'a [ChatMessage]) -> BoxFuture<'a, Result<String, String>> {
+        async move {
+            let client = ai_provider::streaming_client()?;
+            let response = ai_provider::send_checked(self.chat_request(&client, messages, false)).await?;
+            let json = ai_provider::read_json(response).await?;
+
+            json.get("choices")
+                .and_then(|c| c.get(0))
+                .and_then(|c| c.get("message"))
+                .and_then(|m| m.get("content"))
+                .and_then(|c| c.as_str())
+                .map(|c| c.to_string())
+                .ok_or_else(|| "No content found in response".to_string())
+        }
+        .boxed()
+    }
+
+    fn stream<'a>(
+        &'a self,
+        messages: &'a [ChatMessage],
+        on_text: &'a mut (dyn FnMut(String) + Send),
+    ) -> BoxFuture<'a, Result<(), String>> {
+        async move {
+            println!("Using OpenAI-compatible format, streaming SSE...");
+            let client = ai_provider::streaming_client()?;
+            let response = ai_provider::send_checked(self.chat_request(&client, messages, true)).await?;
+            ai_provider::read_stream(response, parse_stream_line, on_text).await
+        }
+        .boxed()
+    }
+
+    fn chat_with_tools<'a>(
+        &'a self,
+        messages: &'a [AgentMessage],
+        tools: &'a [ToolSpec],
+    ) -> BoxFuture<'a, Result<AgentTurn, String>> {
+        async move {
+            let body = serde_json::json!({
+                "model": self.settings.model,
+                "messages": messages.iter().map(wire_message).collect::<Vec<_>>(),
+                "tools": ai_tools::function_tools(tools),
+                "temperature": self.settings.temperature,
+                "max_tokens": self.settings.max_tokens,
+                "stream": false
+            });
+
+            let client = ai_provider::streaming_client()?;
+            let request = self
+                .with_auth(client.post(self.url("chat/completions")))
+                .header("Content-Type", "application/json")
+                .json(&body);
+            let json = ai_provider::read_json(ai_provider::send_checked(request).await?).await?;
+
+            let message = json
+                .get("choices")
+                .and_then(|c| c.get(0))
+                .and_then(|c| c.get("message"))
+                .ok_or_else(|| "No message found in response".to_string())?;
+            let content = message
+                .get("content")
+                .and_then(|c| c.as_str())
+                .unwrap_or("")
+                .to_string();
+
+            let calls = parse_tool_calls(message);
+            if calls.is_empty() {
+                Ok(AgentTurn::Reply(content))
+            } else {
+                Ok(AgentTurn::ToolCalls { content, calls })
+            }
+        }
+        .boxed()
+    }
+
+    fn embed<'a>(&'a self, texts: &'a [String]) -> BoxFuture<'a, Result<Vec<Vec<f32>>, String>> {
+        async move {
+            let model = self.settings.embedding_model().ok_or_else(ai_provider::no_embedding_model)?;
+            let body = serde_json::json!({ "model": model, "input": texts });
+
+            let client = ai_provider::streaming_client()?;
+            let request = self
+                .with_auth(client.post(self.url("embeddings")))
+                .header("Content-Type", "application/json")
+                .json(&body);
+            let response: EmbeddingResponse = ai_provider::send_checked(request)
+                .await?
+                .json()
+                .await
+                .map_err(|e| format!("Failed to parse embeddings: {}", e))?;
+
+            // Results carry their input index and aren't guaranteed to be in order
+            let mut data = response.data;
+            data.sort_by_key(|d| d.index);
+            Ok(data.into_iter().map(|d| d.embedding).collect())
+        }
+        .boxed()
+    }
+
+    fn list_models(&self) -> BoxFuture<'_, Result<Vec<String>, String>> {
+        async move {
+            let client = ai_provider::test_client()?;
+            let response = ai_provider::send_checked(self.with_auth(client.get(self.url("models")))).await?;
+            let json = ai_provider::read_json(response).await?;
+
+            Ok(json
+                .get("data")
+                .and_then(|d| d.as_array())
+                .map(|models| {
+                    models
+                        .iter()
+                        .filter_map(|m| m.get("id").and_then(|id| id.as_str()))
+                        .map(|id| id.to_string())
+                        .collect()
+                })
+                .unwrap_or_default())
+        }
+        .boxed()
+    }
+
+    fn test_connection(&self) -> BoxFuture<'_, ConnectionTestResult> {
+        async move {
+            let mut result = ConnectionTestResult::pending();
+            let client = match ai_provider::test_client() {
+                Ok(client) => client,
+                Err(e) => {
+                    result.overall_status.message = e;
+                    return result;
+                }
+            };
+
+            if !ai_provider::check_endpoint(&client, &self.settings.endpoint, &mut result).await {
+                return result;
+            }
+
+            // Make a minimal request to test auth
+            if self.api_key().is_some() {
+                let test_body = serde_json::json!({
+                    "model": self.settings.model,
+                    "messages": [{"role": "user", "content": "Hi"}],
+                    "max_tokens": 1,
+                    "stream": false
+                });
+
+                match self
+                    .with_auth(client.post(self.url("chat/completions")))
+                    .header("Content-Type", "application/json")
+                    .json(&test_body)
+                    .send()
+                    .await
+                {
+                    Ok(response) => {
+                        if response.status() == 401 {
+                            result.auth_status.message = "Invalid API key".to_string();
+                            result.overall_status.message = "Authentication failed".to_string();
+                            return result;
+                        } else if response.status().is_success() || response.status() == 400 {
+                            result.auth_status.success = true;
+                            result.auth_status.message = "API key is valid".to_string();
+                            result.model_status.success = true;
+                            result.model_status.message = "Model is available".to_string();
+                        }
+                    }
+                    Err(e) => {
+                        result.auth_status.message = format!("Failed to test authentication: {}", e);
+                    }
+                }
+            } else {
+                result.auth_status.success = true;
+                result.auth_status.message = "No authentication required".to_string();
+            }
+
+            result.finish();
+            result
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stream_lines_carry_delta_content() {
+        let line = r#"data: {"choices":[{"delta":{"content":"Hel"}}]}"#;
+        assert_eq!(parse_stream_line(line), StreamLine::Content("Hel".to_string()));
+        let role_only = r#"data: {"choices":[{"delta":{"role":"assistant"}}]}"#;
+        assert_eq!(parse_stream_line(role_only), StreamLine::Skip);
+    }
+
+    #[test]
+    fn stream_ends_on_done() {
+        assert_eq!(parse_stream_line("data: [DONE]"), StreamLine::Done);
+    }
+
+    #[test]
+    fn stream_errors_are_reported() {
+        let line = r#"data: {"error":{"message":"Rate limited"}}"#;
+        assert_eq!(parse_stream_line(line), StreamLine::Error("Rate limited".to_string()));
+    }
+
+    #[test]
+    fn other_lines_are_skipped() {
+        assert_eq!(parse_stream_line(": keep-alive"), StreamLine::Skip);
+        assert_eq!(parse_stream_line("data: not json"), StreamLine::Skip);
+    }
+}
@@ -0,0 +1,194 @@
+// prompt_templates.rs - Reusable AI prompts kept as markdown files in the vault
+
+use regex::{Captures, Regex};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use tauri::AppHandle;
+
+use crate::ai_edit::EditTarget;
+use crate::ai_stream::{self, ChatMessage, ChatMode};
+use crate::auth::VaultAction;
+use crate::frontmatter;
+use crate::vault::{relative_path, Vault};
+
+/// Vault folder the templates live in
+pub const TEMPLATE_FOLDER: &str = "Prompts";
+
+/// A prompt template: a note under `Prompts/` whose body is the prompt.
+///
+/// ```markdown
+/// ---
+/// name: Action items
+/// description: Pull action items out of meeting notes
+/// variables: [owner]
+/// profile: Work
+/// ---
+/// List the action items for {{owner}} in these meeting notes:
+///
+/// {{selection}}
+/// ```
+#[derive(Debug, Clone, Serialize)]
+pub struct PromptTemplate {
+    /// Vault-relative path of the template note
+    pub path: String,
+    /// From the frontmatter, else the file name
+    pub name: String,
+    pub description: Option<String>,
+    /// Values the user fills in before running, beyond the built-in ones
+    pub variables: Vec<String>,
+    /// AI profile to run with instead of the active one
+    pub profile: Option<String>,
+    pub prompt: String,
+}
+
+fn placeholder() -> &'static Regex {
+    static PLACEHOLDER: OnceLock<Regex> = OnceLock::new();
+    PLACEHOLDER.get_or_init(|| Regex::new(r"\{\{\s*([\w-]+)\s*\}\}").unwrap())
+}
+
+fn parse(path: &Path, content: &str) -> PromptTemplate {
+    let yaml = frontmatter::yaml(content);
+    let body_start = frontmatter::locate(content).map_or(0, |block| block.body_start);
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("Untitled");
+
+    PromptTemplate {
+        path: path.to_string_lossy().to_string(),
+        name: frontmatter::get_value(yaml, "name").unwrap_or_else(|| stem.to_string()),
+        description: frontmatter::get_value(yaml, "description"),
+        variables: frontmatter::get_list(yaml, "variables")
+            .into_iter()
+            .map(|v| v.trim().trim_matches(|c| c == '{' || c == '}').to_string())
+            .filter(|v| !v.is_empty())
+            .collect(),
+        profile: frontmatter::get_value(yaml, "profile"),
+        prompt: content[body_start..].trim().to_string(),
+    }
+}
+
+fn load(vault: &Vault, path: &Path) -> Result<PromptTemplate, String> {
+    let content = vault
+        .read_file(path)
+        .map_err(|e| format!("Failed to read template {}: {}", path.display(), e))?;
+    Ok(parse(path, &content))
+}
+
+// Fill in every placeholder in one pass, so braces in the substituted note
+// text are never expanded themselves
+fn render(template: &PromptTemplate, values: &HashMap<String, String>) -> Result<String, String> {
+    let mut missing = Vec::new();
+    let rendered = placeholder().replace_all(&template.prompt, |caps: &Captures| {
+        let name = &caps[1];
+        match values.get(name) {
+            Some(value) => value.clone(),
+            None => {
+                if template.variables.iter().any(|v| v == name) && !missing.contains(&name.to_string()) {
+                    missing.push(name.to_string());
+                }
+                // Not ours to fill, e.g. an example in the prompt text
+                caps[0].to_string()
+            }
+        }
+    });
+
+    if !missing.is_empty() {
+        return Err(format!("Missing value for {}", missing.join(", ")));
+    }
+    Ok(rendered.into_owned())
+}
+
+/// List the prompt templates in the vault's `Prompts` folder, by name.
+#[tauri::command]
+pub async fn list_prompt_templates(
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<Vec<PromptTemplate>, String> {
+    let vault_lock = state.vault.lock().await;
+    let vault = vault_lock.as_ref().ok_or("No vault opened")?;
+
+    let loaded: Vec<PromptTemplate> = vault
+        .list_markdown_files()
+        .map_err(|e| format!("Failed to list files: {}", e))?
+        .into_iter()
+        .filter_map(|file| file.strip_prefix(vault.path()).ok().map(PathBuf::from))
+        .filter(|path| path.starts_with(TEMPLATE_FOLDER))
+        .filter_map(|path| match load(vault, &path) {
+            Ok(template) => Some(template),
+            Err(e) => {
+                println!("⚠️ {}", e);
+                None
+            }
+        })
+        .collect();
+    let mut templates = Vec::new();
+    for template in loaded {
+        if state.auth.allows(vault.path(), Path::new(&template.path), VaultAction::Read).await {
+            templates.push(template);
+        }
+    }
+    templates.sort_by_key(|t| t.name.to_lowercase());
+    Ok(templates)
+}
+
+/// Run a template against a note and send it with `send_ai_chat`, streaming
+/// the reply as usual. `{{selection}}`, `{{note}}`, `{{title}}`, `{{date}}`
+/// and `{{time}}` come from the note and the clock; the template's own
+/// variables from `variables`. The note is the editor's unless `target`
+/// names another.
+#[tauri::command]
+pub async fn run_prompt_template(
+    app: AppHandle,
+    path: String,
+    target: Option<EditTarget>,
+    variables: Option<HashMap<String, String>>,
+    request_id: Option<String>,
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<String, String> {
+    let template = {
+        let vault_lock = state.vault.lock().await;
+        let vault = vault_lock.as_ref().ok_or("No vault opened")?;
+        let path = relative_path(&path)?;
+        state.auth.authorize(vault.path(), &path, VaultAction::Read).await?;
+        load(vault, &path)?
+    };
+    let note = target.unwrap_or_default().resolve(&state).await?;
+
+    let now = chrono::Local::now();
+    let mut values = variables.unwrap_or_default();
+    let title = note
+        .file_path
+        .as_deref()
+        .and_then(|p| p.file_stem())
+        .and_then(|s| s.to_str())
+        .unwrap_or("Untitled");
+    values.insert("title".to_string(), title.to_string());
+    values.insert("date".to_string(), now.format("%Y-%m-%d").to_string());
+    values.insert("time".to_string(), now.format("%H:%M").to_string());
+    values.insert("note".to_string(), note.text.clone());
+
+    let uses_selection = placeholder()
+        .captures_iter(&template.prompt)
+        .any(|caps| &caps[1] == "selection");
+    match note.selected().filter(|s| !s.trim().is_empty()) {
+        Some(selection) => {
+            values.insert("selection".to_string(), selection.to_string());
+        }
+        None if uses_selection => return Err("This template needs a text selection".to_string()),
+        None => {}
+    }
+
+    let prompt = render(&template, &values)?;
+    let messages = vec![ChatMessage {
+        role: "user".to_string(),
+        content: prompt,
+    }];
+
+    let request_id = request_id.unwrap_or_else(ai_stream::new_request_id);
+    println!("📋 Running prompt template {} ({})", template.name, request_id);
+    let mode = ChatMode::Chat {
+        stream: true,
+        summarize_history: false,
+        profile: template.profile,
+    };
+    ai_stream::run_chat(&app, &state, &request_id, messages, mode).await
+}
@@ -0,0 +1,526 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::auth::VaultAction;
+use crate::vault::{self, Vault};
+
+const INDEX_FILE: &str = "search_index.json";
+const INDEX_VERSION: u32 = 1;
+
+// BM25 tuning parameters
+const BM25_K1: f64 = 1.2;
+const BM25_B: f64 = 0.75;
+
+// Matches in the note's file name count extra
+const TITLE_BOOST: f64 = 2.0;
+
+const SNIPPET_RADIUS: usize = 60;
+const MAX_SNIPPETS: usize = 3;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct IndexedDoc {
+    title: String,
+    modified: i64,
+    length: u32,
+}
+
+/// Full-text index over the markdown notes of a vault.
+///
+/// Stored as JSON under `<vault>/.aura/search_index.json` and kept current
+/// by the file watcher through `update_paths`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SearchIndex {
+    version: u32,
+    #[serde(skip)]
+    vault_path: PathBuf,
+    docs: HashMap<String, IndexedDoc>,
+    // term -> note path -> token positions
+    postings: HashMap<String, HashMap<String, Vec<u32>>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MatchRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SearchSnippet {
+    pub text: String,
+    pub line: usize,
+    /// Character offsets of the matched terms within `text`
+    pub matches: Vec<MatchRange>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SearchHit {
+    pub path: String,
+    pub name: String,
+    pub score: f64,
+    pub snippets: Vec<SearchSnippet>,
+}
+
+#[derive(Debug)]
+enum QueryClause {
+    Term(String),
+    Prefix(String),
+    Phrase(Vec<String>),
+}
+
+struct Token {
+    term: String,
+    start: usize,
+    end: usize,
+}
+
+impl SearchIndex {
+    fn empty(vault_path: PathBuf) -> Self {
+        Self {
+            version: INDEX_VERSION,
+            vault_path,
+            docs: HashMap::new(),
+            postings: HashMap::new(),
+        }
+    }
+
+    /// Load the persisted index for a vault (if any) and bring it up to date
+    /// with the notes currently on disk.
+    pub fn open(vault: &Vault) -> io::Result<Self> {
+        let vault_path = vault.path().to_path_buf();
+        let index_path = vault_path.join(".aura").join(INDEX_FILE);
+
+        let mut index = match std::fs::read_to_string(&index_path) {
+            Ok(content) => match serde_json::from_str::<SearchIndex>(&content) {
+                Ok(index) if index.version == INDEX_VERSION => index,
+                Ok(_) => {
+                    println!("🔎 Search index format changed, rebuilding");
+                    Self::empty(vault_path.clone())
+                }
+                Err(e) => {
+                    println!("⚠️ Search index is corrupt, rebuilding: {}", e);
+                    Self::empty(vault_path.clone())
+                }
+            },
+            Err(_) => Self::empty(vault_path.clone()),
+        };
+        index.vault_path = vault_path;
+
+        let notes: Vec<PathBuf> = vault
+            .list_markdown_files()?
+            .into_iter()
+            .filter(|p| vault::is_note(p))
+            .collect();
+
+        let mut seen = HashSet::new();
+        let mut reindexed = 0;
+        for note in &notes {
+            let Some(key) = index.relative_key(note) else { continue };
+            let modified = modified_time(note);
+            let stale = index.docs.get(&key).map_or(true, |d| d.modified != modified);
+            if stale {
+                // One unreadable note shouldn't cost the whole index; it's
+                // left out, and dropped if it was indexed before
+                if let Err(e) = index.index_file(note) {
+                    println!("⚠️ Failed to index {:?}: {}", note, e);
+                    continue;
+                }
+                reindexed += 1;
+            }
+            seen.insert(key);
+        }
+
+        let removed: Vec<String> = index
+            .docs
+            .keys()
+            .filter(|k| !seen.contains(*k))
+            .cloned()
+            .collect();
+        for key in &removed {
+            index.remove_doc(key);
+        }
+
+        println!(
+            "🔎 Search index ready: {} notes ({} reindexed, {} removed)",
+            index.docs.len(),
+            reindexed,
+            removed.len()
+        );
+
+        if reindexed > 0 || !removed.is_empty() {
+            index.save()?;
+        }
+
+        Ok(index)
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let aura_dir = self.vault_path.join(".aura");
+        std::fs::create_dir_all(&aura_dir)?;
+
+        let content = serde_json::to_string(self)
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+
+        // Write to a temp file first so a crash never leaves a half-written index
+        let tmp_path = aura_dir.join(format!("{}.tmp", INDEX_FILE));
+        std::fs::write(&tmp_path, content)?;
+        std::fs::rename(tmp_path, aura_dir.join(INDEX_FILE))
+    }
+
+    /// Re-index (or drop) the given absolute paths after a file system change.
+    /// Returns true if the index changed.
+    pub fn update_paths(&mut self, paths: &[PathBuf]) -> bool {
+        let mut changed = false;
+
+        for path in paths {
+            if !vault::is_note(path) {
+                continue;
+            }
+            let Some(key) = self.relative_key(path) else { continue };
+
+            if path.is_file() {
+                match self.index_file(path) {
+                    Ok(()) => changed = true,
+                    Err(e) => println!("⚠️ Failed to index {:?}: {}", path, e),
+                }
+            } else if self.docs.contains_key(&key) {
+                self.remove_doc(&key);
+                changed = true;
+            }
+        }
+
+        changed
+    }
+
+    /// All indexed note paths, relative to the vault root.
+    pub fn note_paths(&self) -> impl Iterator<Item = &String> {
+        self.docs.keys()
+    }
+
+    pub fn search(&self, query: &str, limit: usize, visible: impl Fn(&str) -> bool) -> Vec<SearchHit> {
+        let clauses = parse_query(query);
+        if clauses.is_empty() {
+            return vec![];
+        }
+
+        // Every clause must match (AND semantics)
+        let mut candidates: Option<HashMap<String, f64>> = None;
+        for clause in &clauses {
+            let clause_scores = self.score_clause(clause);
+            candidates = Some(match candidates {
+                None => clause_scores,
+                Some(previous) => previous
+                    .into_iter()
+                    .filter_map(|(doc, score)| {
+                        clause_scores.get(&doc).map(|extra| (doc, score + extra))
+                    })
+                    .collect(),
+            });
+        }
+
+        let mut ranked: Vec<(String, f64)> = candidates
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|(doc, _)| visible(doc))
+            .collect();
+        ranked.sort_by(|a, b| {
+            b.1.partial_cmp(&a.1)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then(a.0.cmp(&b.0))
+        });
+        ranked.truncate(limit);
+
+        ranked
+            .into_iter()
+            .map(|(path, score)| {
+                let snippets = std::fs::read_to_string(self.vault_path.join(&path))
+                    .map(|content| build_snippets(&content, &clauses))
+                    .unwrap_or_default();
+                SearchHit {
+                    name: self.docs.get(&path).map(|d| d.title.clone()).unwrap_or_default(),
+                    path,
+                    score,
+                    snippets,
+                }
+            })
+            .collect()
+    }
+
+    fn relative_key(&self, path: &Path) -> Option<String> {
+        path.strip_prefix(&self.vault_path)
+            .ok()
+            .map(|p| p.to_string_lossy().to_string())
+    }
+
+    fn index_file(&mut self, path: &Path) -> io::Result<()> {
+        let Some(key) = self.relative_key(path) else {
+            return Ok(());
+        };
+        let content = std::fs::read_to_string(path)?;
+
+        self.remove_doc(&key);
+
+        let tokens = tokenize(&content);
+        for (position, token) in tokens.iter().enumerate() {
+            self.postings
+                .entry(token.term.clone())
+                .or_default()
+                .entry(key.clone())
+                .or_default()
+                .push(position as u32);
+        }
+
+        let title = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("")
+            .to_string();
+
+        self.docs.insert(
+            key,
+            IndexedDoc {
+                title,
+                modified: modified_time(path),
+                length: tokens.len() as u32,
+            },
+        );
+
+        Ok(())
+    }
+
+    fn remove_doc(&mut self, key: &str) {
+        if self.docs.remove(key).is_none() {
+            return;
+        }
+        self.postings.retain(|_, docs| {
+            docs.remove(key);
+            !docs.is_empty()
+        });
+    }
+
+    fn average_length(&self) -> f64 {
+        if self.docs.is_empty() {
+            return 0.0;
+        }
+        let total: u64 = self.docs.values().map(|d| d.length as u64).sum();
+        total as f64 / self.docs.len() as f64
+    }
+
+    fn bm25(&self, doc: &str, term_frequency: usize, doc_frequency: usize) -> f64 {
+        let n = self.docs.len() as f64;
+        let df = doc_frequency as f64;
+        let idf = ((n - df + 0.5) / (df + 0.5) + 1.0).ln();
+
+        let length = self.docs.get(doc).map(|d| d.length).unwrap_or(0) as f64;
+        let avg = self.average_length().max(1.0);
+        let tf = term_frequency as f64;
+
+        idf * (tf * (BM25_K1 + 1.0)) / (tf + BM25_K1 * (1.0 - BM25_B + BM25_B * length / avg))
+    }
+
+    fn score_term(&self, term: &str, scores: &mut HashMap<String, f64>) {
+        let Some(docs) = self.postings.get(term) else { return };
+        for (doc, positions) in docs {
+            let mut score = self.bm25(doc, positions.len(), docs.len());
+            if self.title_contains(doc, term) {
+                score *= TITLE_BOOST;
+            }
+            let entry = scores.entry(doc.clone()).or_insert(0.0);
+            *entry = entry.max(score);
+        }
+    }
+
+    fn score_clause(&self, clause: &QueryClause) -> HashMap<String, f64> {
+        let mut scores = HashMap::new();
+
+        match clause {
+            QueryClause::Term(term) => self.score_term(term, &mut scores),
+            QueryClause::Prefix(prefix) => {
+                for term in self.postings.keys().filter(|t| t.starts_with(prefix.as_str())) {
+                    self.score_term(term, &mut scores);
+                }
+            }
+            QueryClause::Phrase(terms) => {
+                let Some(first) = terms.first().and_then(|t| self.postings.get(t)) else {
+                    return scores;
+                };
+                for (doc, starts) in first {
+                    let occurrences = starts
+                        .iter()
+                        .filter(|&&start| {
+                            terms.iter().enumerate().skip(1).all(|(offset, term)| {
+                                self.postings
+                                    .get(term)
+                                    .and_then(|docs| docs.get(doc))
+                                    .map_or(false, |positions| {
+                                        positions.binary_search(&(start + offset as u32)).is_ok()
+                                    })
+                            })
+                        })
+                        .count();
+                    if occurrences > 0 {
+                        // Score a phrase like a single rare term, weighted by its length
+                        let score = self.bm25(doc, occurrences, first.len()) * terms.len() as f64;
+                        scores.insert(doc.clone(), score);
+                    }
+                }
+            }
+        }
+
+        scores
+    }
+
+    fn title_contains(&self, doc: &str, term: &str) -> bool {
+        self.docs
+            .get(doc)
+            .map_or(false, |d| tokenize(&d.title).iter().any(|t| t.term == term))
+    }
+}
+
+fn modified_time(path: &Path) -> i64 {
+    std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+// Lowercased alphanumeric runs with their byte ranges in the source text
+fn tokenize(text: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+
+    for (i, c) in text.char_indices() {
+        if c.is_alphanumeric() {
+            if start.is_none() {
+                start = Some(i);
+            }
+        } else if let Some(s) = start.take() {
+            tokens.push(Token { term: text[s..i].to_lowercase(), start: s, end: i });
+        }
+    }
+    if let Some(s) = start {
+        tokens.push(Token { term: text[s..].to_lowercase(), start: s, end: text.len() });
+    }
+
+    tokens
+}
+
+// Supports bare terms, `prefix*` and `"quoted phrases"`
+fn parse_query(query: &str) -> Vec<QueryClause> {
+    let mut clauses = Vec::new();
+
+    for (i, part) in query.split('"').enumerate() {
+        // Odd segments sit between a pair of quotes
+        if i % 2 == 1 {
+            let terms: Vec<String> = tokenize(part).into_iter().map(|t| t.term).collect();
+            match terms.len() {
+                0 => {}
+                1 => clauses.push(QueryClause::Term(terms[0].clone())),
+                _ => clauses.push(QueryClause::Phrase(terms)),
+            }
+            continue;
+        }
+
+        for word in part.split_whitespace() {
+            let is_prefix = word.ends_with('*');
+            let terms: Vec<String> = tokenize(word).into_iter().map(|t| t.term).collect();
+            match (terms.len(), is_prefix) {
+                (0, _) => {}
+                (1, true) => clauses.push(QueryClause::Prefix(terms[0].clone())),
+                (1, false) => clauses.push(QueryClause::Term(terms[0].clone())),
+                // Words like "foo-bar" tokenize into several terms; treat as a phrase
+                _ => clauses.push(QueryClause::Phrase(terms)),
+            }
+        }
+    }
+
+    clauses
+}
+
+fn token_matches(token: &str, clauses: &[QueryClause]) -> bool {
+    clauses.iter().any(|clause| match clause {
+        QueryClause::Term(term) => token == term,
+        QueryClause::Prefix(prefix) => token.starts_with(prefix.as_str()),
+        QueryClause::Phrase(terms) => terms.iter().any(|t| t == token),
+    })
+}
+
+fn build_snippets(content: &str, clauses: &[QueryClause]) -> Vec<SearchSnippet> {
+    let mut snippets = Vec::new();
+
+    for (line_number, line) in content.split('\n').enumerate() {
+        let matched: Vec<Token> = tokenize(line)
+            .into_iter()
+            .filter(|t| token_matches(&t.term, clauses))
+            .collect();
+
+        if let (Some(first), Some(last)) = (matched.first(), matched.last()) {
+            let from = floor_char_boundary(line, first.start.saturating_sub(SNIPPET_RADIUS));
+            let to = ceil_char_boundary(line, (last.end + SNIPPET_RADIUS).min(line.len()));
+            let text = &line[from..to];
+
+            let matches = matched
+                .iter()
+                .filter(|t| t.end <= to)
+                .map(|t| MatchRange {
+                    start: line[from..t.start].chars().count(),
+                    end: line[from..t.end].chars().count(),
+                })
+                .collect();
+
+            snippets.push(SearchSnippet {
+                text: text.trim_end_matches('\r').to_string(),
+                line: line_number + 1,
+                matches,
+            });
+
+            if snippets.len() >= MAX_SNIPPETS {
+                break;
+            }
+        }
+    }
+
+    snippets
+}
+
+fn floor_char_boundary(s: &str, mut index: usize) -> usize {
+    while index > 0 && !s.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+fn ceil_char_boundary(s: &str, mut index: usize) -> usize {
+    while index < s.len() && !s.is_char_boundary(index) {
+        index += 1;
+    }
+    index
+}
+
+#[tauri::command]
+pub async fn search_vault(
+    query: String,
+    limit: Option<usize>,
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<Vec<SearchHit>, String> {
+    let vault_root = match &*state.vault.lock().await {
+        Some(vault) => vault.path().to_path_buf(),
+        None => return Err("No vault opened".to_string()),
+    };
+    let index_lock = state.search_index.lock().await;
+
+    match &*index_lock {
+        Some(index) => {
+            // Notes the user can't read don't show up
+            let access = state.auth.vault_access(&vault_root).await;
+            Ok(index.search(&query, limit.unwrap_or(50), |path| {
+                access.allows(Path::new(path), VaultAction::Read)
+            }))
+        }
+        None => Err("No vault opened".to_string()),
+    }
+}
@@ -0,0 +1,444 @@
+// tags.rs - Tag extraction and the vault-wide tag index
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::auth::VaultAction;
+use crate::frontmatter;
+use crate::markdown::mask_code;
+use crate::vault::{self, Vault};
+
+const FRONTMATTER_KEYS: [&str; 2] = ["tags", "tag"];
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TagCount {
+    pub tag: String,
+    pub count: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TagNode {
+    pub name: String,
+    /// Full nested tag, e.g. `project/alpha`
+    pub tag: String,
+    /// Notes tagged with this tag or any of its descendants
+    pub count: usize,
+    pub children: Vec<TagNode>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TagRenameResult {
+    pub changed_files: Vec<String>,
+    pub occurrences: usize,
+}
+
+// A tag found in a note, with the byte range of its name (without the `#`)
+struct TagOccurrence {
+    tag: String,
+    range: Range<usize>,
+}
+
+// Not after `(` or `[`, where `#` starts an anchor: `[see](#section)`, `[[#Heading]]`
+fn inline_tag_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"(?:^|[\s,;])#([\p{L}\p{N}_\-/]+)").expect("valid tag regex")
+    })
+}
+
+fn normalize(tag: &str) -> String {
+    tag.trim().trim_start_matches('#').trim_matches('/').to_lowercase()
+}
+
+// Tags made only of digits (`#123`) are issue numbers, not tags
+fn is_valid_tag(tag: &str) -> bool {
+    !tag.is_empty() && !tag.chars().all(|c| c.is_ascii_digit() || c == '/')
+}
+
+fn inline_tags(body: &str, offset: usize) -> Vec<TagOccurrence> {
+    let masked = mask_code(body);
+    inline_tag_regex()
+        .captures_iter(&masked)
+        .filter_map(|caps| {
+            let m = caps.get(1)?;
+            let name = m.as_str().trim_end_matches('/');
+            if !is_valid_tag(name) {
+                return None;
+            }
+            let start = offset + m.start();
+            Some(TagOccurrence {
+                tag: name.to_string(),
+                range: start..start + name.len(),
+            })
+        })
+        .collect()
+}
+
+fn frontmatter_tags(content: &str) -> Vec<TagOccurrence> {
+    let Some(block) = frontmatter::locate(content) else {
+        return vec![];
+    };
+    let yaml = &content[block.yaml.clone()];
+
+    FRONTMATTER_KEYS
+        .iter()
+        .flat_map(|key| frontmatter::list_item_ranges(yaml, key))
+        .filter_map(|range| {
+            // Allow `- "#tag"` style entries
+            let hash = yaml[range.clone()].starts_with('#') as usize;
+            let start = block.yaml.start + range.start + hash;
+            let end = block.yaml.start + range.end;
+            let name = content[start..end].trim_end_matches('/');
+            is_valid_tag(name).then(|| TagOccurrence {
+                tag: name.to_string(),
+                range: start..start + name.len(),
+            })
+        })
+        .collect()
+}
+
+// Every tag occurrence in a note, frontmatter first
+fn find_tags(content: &str) -> Vec<TagOccurrence> {
+    let mut tags = frontmatter_tags(content);
+    let body_start = frontmatter::locate(content).map_or(0, |b| b.body_start);
+    tags.extend(inline_tags(&content[body_start..], body_start));
+    tags
+}
+
+/// The distinct tags of a note, in order of first appearance.
+pub fn extract_tags(content: &str) -> Vec<String> {
+    let mut seen = BTreeSet::new();
+    find_tags(content)
+        .into_iter()
+        .filter(|t| seen.insert(normalize(&t.tag)))
+        .map(|t| t.tag)
+        .collect()
+}
+
+fn matches_tag(tag: &str, query: &str) -> bool {
+    let tag = normalize(tag);
+    tag == query || tag.starts_with(&format!("{}/", query))
+}
+
+/// Tag index for a vault: which notes carry which tags.
+pub struct TagIndex {
+    vault_path: PathBuf,
+    // note path -> tags as written in the note
+    note_tags: HashMap<String, Vec<String>>,
+    // normalized tag -> note paths
+    tag_notes: BTreeMap<String, BTreeSet<String>>,
+    // normalized tag -> spelling used for display
+    display: HashMap<String, String>,
+}
+
+impl TagIndex {
+    pub fn build(vault: &Vault) -> io::Result<Self> {
+        let mut index = Self {
+            vault_path: vault.path().to_path_buf(),
+            note_tags: HashMap::new(),
+            tag_notes: BTreeMap::new(),
+            display: HashMap::new(),
+        };
+
+        for path in vault.list_markdown_files()? {
+            if !vault::is_note(&path) {
+                continue;
+            }
+            // Leave out a note that can't be read rather than losing every tag
+            if let Err(e) = index.index_file(&path) {
+                println!("⚠️ Failed to index tags for {:?}: {}", path, e);
+            }
+        }
+
+        println!(
+            "🏷️ Tag index ready: {} tags across {} notes",
+            index.tag_notes.len(),
+            index.note_tags.len()
+        );
+        Ok(index)
+    }
+
+    /// Re-index (or drop) the given absolute paths after a file system change.
+    pub fn update_paths(&mut self, paths: &[PathBuf]) -> bool {
+        let mut changed = false;
+
+        for path in paths.iter().filter(|p| vault::is_note(p)) {
+            if path.is_file() {
+                match self.index_file(path) {
+                    Ok(()) => changed = true,
+                    Err(e) => println!("⚠️ Failed to index tags for {:?}: {}", path, e),
+                }
+            } else if let Some(key) = self.relative_key(path) {
+                changed |= self.remove_note(&key);
+            }
+        }
+
+        changed
+    }
+
+    /// Notes tagged with `tag` or any tag nested below it.
+    pub fn notes_with_tag(&self, tag: &str) -> Vec<String> {
+        let query = normalize(tag);
+        let mut notes = BTreeSet::new();
+        for (_, paths) in self
+            .tag_notes
+            .range(query.clone()..)
+            .take_while(|(t, _)| t.starts_with(&query))
+            .filter(|(t, _)| matches_tag(t, &query))
+        {
+            notes.extend(paths.iter().cloned());
+        }
+        notes.into_iter().collect()
+    }
+
+    pub fn tags_for_note(&self, path: &str) -> Vec<String> {
+        self.note_tags.get(path).cloned().unwrap_or_default()
+    }
+
+    pub fn vault_path(&self) -> &Path {
+        &self.vault_path
+    }
+
+    pub fn note_paths(&self) -> impl Iterator<Item = &String> {
+        self.note_tags.keys()
+    }
+
+    /// Tags by how many of the `readable` notes carry them.
+    pub fn tag_counts(&self, readable: &HashSet<String>) -> Vec<TagCount> {
+        let mut counts: Vec<TagCount> = self
+            .tag_notes
+            .iter()
+            .map(|(tag, notes)| TagCount {
+                tag: self.display_name(tag),
+                count: notes.iter().filter(|n| readable.contains(*n)).count(),
+            })
+            .filter(|t| t.count > 0)
+            .collect();
+        counts.sort_by(|a, b| b.count.cmp(&a.count).then(a.tag.cmp(&b.tag)));
+        counts
+    }
+
+    /// Nested tags on the `readable` notes, counting only those notes.
+    pub fn hierarchy(&self, readable: &HashSet<String>) -> Vec<TagNode> {
+        // Collect every tag including implicit parents of nested tags
+        let mut all_tags = BTreeSet::new();
+        for (tag, _) in self
+            .tag_notes
+            .iter()
+            .filter(|(_, notes)| notes.iter().any(|n| readable.contains(n)))
+        {
+            let mut prefix = String::new();
+            for segment in tag.split('/') {
+                if !prefix.is_empty() {
+                    prefix.push('/');
+                }
+                prefix.push_str(segment);
+                all_tags.insert(prefix.clone());
+            }
+        }
+
+        self.build_level(&all_tags, "", readable)
+    }
+
+    fn build_level(&self, all_tags: &BTreeSet<String>, parent: &str, readable: &HashSet<String>) -> Vec<TagNode> {
+        all_tags
+            .iter()
+            .filter(|tag| match tag.rsplit_once('/') {
+                Some((p, _)) => p == parent,
+                None => parent.is_empty(),
+            })
+            .map(|tag| {
+                let display = self.display_name(tag);
+                TagNode {
+                    name: display.rsplit('/').next().unwrap_or(&display).to_string(),
+                    count: self.notes_with_tag(tag).iter().filter(|n| readable.contains(*n)).count(),
+                    children: self.build_level(all_tags, tag, readable),
+                    tag: display,
+                }
+            })
+            .collect()
+    }
+
+    fn display_name(&self, tag: &str) -> String {
+        self.display.get(tag).cloned().unwrap_or_else(|| tag.to_string())
+    }
+
+    fn relative_key(&self, path: &Path) -> Option<String> {
+        path.strip_prefix(&self.vault_path)
+            .ok()
+            .map(|p| p.to_string_lossy().to_string())
+    }
+
+    fn index_file(&mut self, path: &Path) -> io::Result<()> {
+        let Some(key) = self.relative_key(path) else {
+            return Ok(());
+        };
+        let content = std::fs::read_to_string(path)?;
+        self.index_content(key, &content);
+        Ok(())
+    }
+
+    fn index_content(&mut self, key: String, content: &str) {
+        self.remove_note(&key);
+
+        let tags = extract_tags(content);
+        for tag in &tags {
+            let normalized = normalize(tag);
+            self.display.entry(normalized.clone()).or_insert_with(|| tag.clone());
+            self.tag_notes.entry(normalized).or_default().insert(key.clone());
+        }
+        if !tags.is_empty() {
+            self.note_tags.insert(key, tags);
+        }
+    }
+
+    fn remove_note(&mut self, key: &str) -> bool {
+        let Some(tags) = self.note_tags.remove(key) else {
+            return false;
+        };
+        for tag in tags {
+            let normalized = normalize(&tag);
+            if let Some(notes) = self.tag_notes.get_mut(&normalized) {
+                notes.remove(key);
+                if notes.is_empty() {
+                    self.tag_notes.remove(&normalized);
+                    self.display.remove(&normalized);
+                }
+            }
+        }
+        true
+    }
+}
+
+// Rewrite `old` (and tags nested below it) to `new` in a note's content
+fn rename_in_content(content: &str, old: &str, new: &str) -> Option<(String, usize)> {
+    let mut occurrences: Vec<TagOccurrence> = find_tags(content)
+        .into_iter()
+        .filter(|t| matches_tag(&t.tag, old))
+        .collect();
+    if occurrences.is_empty() {
+        return None;
+    }
+
+    let old_depth = old.split('/').count();
+    occurrences.sort_by_key(|t| std::cmp::Reverse(t.range.start));
+
+    let mut updated = content.to_string();
+    for occurrence in &occurrences {
+        // Keep the nested part: #old/child -> #new/child
+        let mut renamed = new.to_string();
+        for segment in occurrence.tag.split('/').skip(old_depth) {
+            renamed.push('/');
+            renamed.push_str(segment);
+        }
+        updated.replace_range(occurrence.range.clone(), &renamed);
+    }
+
+    Some((updated, occurrences.len()))
+}
+
+#[tauri::command]
+pub async fn get_tag_counts(
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<Vec<TagCount>, String> {
+    let index_lock = state.tag_index.lock().await;
+    let Some(index) = &*index_lock else {
+        return Err("No vault opened".to_string());
+    };
+
+    // Tags only on notes the user can't read aren't shown
+    let notes = index.note_paths().cloned().collect();
+    let readable = state.auth.readable(&index.vault_path, notes).await;
+    Ok(index.tag_counts(&readable.into_iter().collect()))
+}
+
+#[tauri::command]
+pub async fn get_tag_hierarchy(
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<Vec<TagNode>, String> {
+    let index_lock = state.tag_index.lock().await;
+    let Some(index) = &*index_lock else {
+        return Err("No vault opened".to_string());
+    };
+
+    let notes = index.note_paths().cloned().collect();
+    let readable = state.auth.readable(&index.vault_path, notes).await;
+    Ok(index.hierarchy(&readable.into_iter().collect()))
+}
+
+#[tauri::command]
+pub async fn get_note_tags(
+    file_path: String,
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<Vec<String>, String> {
+    let index_lock = state.tag_index.lock().await;
+    let Some(index) = &*index_lock else {
+        return Err("No vault opened".to_string());
+    };
+
+    state.auth.authorize(&index.vault_path, Path::new(&file_path), VaultAction::Read).await?;
+    Ok(index.tags_for_note(&file_path))
+}
+
+#[tauri::command]
+pub async fn rename_tag(
+    old_tag: String,
+    new_tag: String,
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<TagRenameResult, String> {
+    println!("🏷️ rename_tag called: {} -> {}", old_tag, new_tag);
+
+    let old = normalize(&old_tag);
+    let new = new_tag.trim().trim_start_matches('#').trim_matches('/').to_string();
+    if !is_valid_tag(&old) || !is_valid_tag(&new) {
+        return Err("Invalid tag name".to_string());
+    }
+    if new.chars().any(|c| !(c.is_alphanumeric() || matches!(c, '_' | '-' | '/'))) {
+        return Err("Tags may only contain letters, numbers, '_', '-' and '/'".to_string());
+    }
+
+    let vault_lock = state.vault.lock().await;
+    let Some(vault) = &*vault_lock else {
+        return Err("No vault opened".to_string());
+    };
+
+    let mut index_lock = state.tag_index.lock().await;
+    let Some(index) = index_lock.as_mut() else {
+        return Err("No vault opened".to_string());
+    };
+
+    let mut result = TagRenameResult {
+        changed_files: vec![],
+        occurrences: 0,
+    };
+
+    // Nothing is renamed unless every note with the tag may be changed
+    let notes = index.notes_with_tag(&old);
+    for note in &notes {
+        state.auth.authorize(vault.path(), Path::new(note), VaultAction::Write).await?;
+    }
+
+    for note in notes {
+        let path = Path::new(&note);
+        let content = vault
+            .read_file(path)
+            .map_err(|e| format!("Failed to read {}: {}", note, e))?;
+
+        if let Some((updated, count)) = rename_in_content(&content, &old, &new) {
+            vault
+                .write_file(path, &updated)
+                .map_err(|e| format!("Failed to write {}: {}", note, e))?;
+            index.index_content(note.clone(), &updated);
+            result.occurrences += count;
+            result.changed_files.push(note);
+        }
+    }
+
+    println!("✅ Renamed {} tag occurrences in {} files", result.occurrences, result.changed_files.len());
+    Ok(result)
+}
@@ -0,0 +1,166 @@
+// totp.rs - Time-based one-time passwords (RFC 6238) for two-factor sign-in
+
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
+use rand::{thread_rng, Rng, RngCore};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+// Shown by authenticator apps next to the account name
+const ISSUER: &str = "Aura";
+const PERIOD_SECS: u64 = 30;
+const DIGITS: usize = 6;
+// Steps either side of now a code is accepted for, to allow for clock drift
+const SKEW_STEPS: u64 = 1;
+// 160 bits, the HMAC-SHA1 key size RFC 4226 recommends
+const SECRET_BYTES: usize = 20;
+
+const RECOVERY_CODE_COUNT: usize = 10;
+// Unambiguous characters only; no 0/o or 1/l
+const RECOVERY_ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+
+/// A new random secret, base32 as authenticator apps expect.
+pub fn generate_secret() -> String {
+    let mut secret = [0u8; SECRET_BYTES];
+    thread_rng().fill_bytes(&mut secret);
+    BASE32_NOPAD.encode(&secret)
+}
+
+/// `otpauth://` URI for an authenticator app, usually shown as a QR code.
+pub fn otpauth_uri(secret: &str, account: &str) -> String {
+    format!(
+        "otpauth://totp/{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        urlencoding::encode(&format!("{}:{}", ISSUER, account)),
+        secret,
+        urlencoding::encode(ISSUER),
+        DIGITS,
+        PERIOD_SECS
+    )
+}
+
+// HOTP (RFC 4226) for one counter value
+fn code_at(key: &[u8], step: u64) -> u32 {
+    let mut mac = Hmac::<Sha1>::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(&step.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([digest[offset], digest[offset + 1], digest[offset + 2], digest[offset + 3]])
+        & 0x7fff_ffff;
+    binary % 10u32.pow(DIGITS as u32)
+}
+
+/// Whether `code` looks like a TOTP code rather than a recovery code.
+pub fn is_code(code: &str) -> bool {
+    let code = normalize(code);
+    code.len() == DIGITS && code.chars().all(|c| c.is_ascii_digit())
+}
+
+/// Check a code against the secret at `unix_time`, within the skew window.
+/// Returns the time step it matched, which must be later than `after` so a
+/// code can't be used twice.
+pub fn verify(secret: &str, code: &str, unix_time: u64, after: u64) -> Option<u64> {
+    if !is_code(code) {
+        return None;
+    }
+    let code: u32 = normalize(code).parse().ok()?;
+    let key = BASE32_NOPAD.decode(secret.as_bytes()).ok()?;
+
+    let now = unix_time / PERIOD_SECS;
+    (now.saturating_sub(SKEW_STEPS)..=now + SKEW_STEPS)
+        .filter(|step| *step > after)
+        .find(|step| code_at(&key, *step) == code)
+}
+
+/// Single-use codes for signing in without the authenticator app, as
+/// `xxxxx-xxxxx`.
+pub fn generate_recovery_codes() -> Vec<String> {
+    let mut rng = thread_rng();
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let mut code: String = (0..10)
+                .map(|_| RECOVERY_ALPHABET[rng.gen_range(0..RECOVERY_ALPHABET.len())] as char)
+                .collect();
+            code.insert(5, '-');
+            code
+        })
+        .collect()
+}
+
+/// What's stored for a recovery code. The codes are random enough that a
+/// plain hash is safe, unlike passwords.
+pub fn hash_recovery_code(code: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(normalize(code).replace('-', "").as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+// Authenticator apps show codes as "123 456"; people type what they see
+fn normalize(code: &str) -> String {
+    code.chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 6238 appendix B; its SHA-1 key is the ASCII "12345678901234567890"
+    const RFC_SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+
+    #[test]
+    fn codes_match_rfc_6238_vectors() {
+        let key = BASE32_NOPAD.decode(RFC_SECRET.as_bytes()).unwrap();
+        assert_eq!(key, b"12345678901234567890");
+        // The RFC's 8-digit codes, keeping the last six digits
+        for (time, code) in [
+            (59u64, 287_082),
+            (1_111_111_109, 81_804),
+            (1_111_111_111, 50_471),
+            (1_234_567_890, 5_924),
+            (2_000_000_000, 279_037),
+            (20_000_000_000, 353_130),
+        ] {
+            assert_eq!(code_at(&key, time / PERIOD_SECS), code, "at {}", time);
+        }
+    }
+
+    #[test]
+    fn verify_returns_the_matched_step() {
+        assert_eq!(verify(RFC_SECRET, "287082", 59, 0), Some(1));
+        assert_eq!(verify(RFC_SECRET, "081804", 1_111_111_109, 0), Some(37_037_036));
+        assert_eq!(verify(RFC_SECRET, "287083", 59, 0), None);
+    }
+
+    #[test]
+    fn verify_allows_one_step_of_drift() {
+        // Step 1's code, checked from steps 0, 2 and 3
+        assert_eq!(verify(RFC_SECRET, "287082", 10, 0), Some(1));
+        assert_eq!(verify(RFC_SECRET, "287082", 89, 0), Some(1));
+        assert_eq!(verify(RFC_SECRET, "287082", 119, 0), None);
+    }
+
+    #[test]
+    fn verify_refuses_replayed_codes() {
+        let step = verify(RFC_SECRET, "287082", 59, 0).unwrap();
+        assert_eq!(verify(RFC_SECRET, "287082", 59, step), None);
+    }
+
+    #[test]
+    fn codes_are_read_as_typed() {
+        assert_eq!(verify(RFC_SECRET, " 287 082 ", 59, 0), Some(1));
+        assert!(!is_code("28708"));
+        assert!(!is_code("abcde-fghij"));
+        assert_eq!(verify("not base32!", "287082", 59, 0), None);
+    }
+
+    #[test]
+    fn recovery_codes_hash_the_same_however_typed() {
+        let codes = generate_recovery_codes();
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+        assert!(codes.iter().all(|c| c.len() == 11 && c.as_bytes()[5] == b'-'));
+        assert_eq!(hash_recovery_code("abcde-fghjk"), hash_recovery_code("ABCDE FGHJK"));
+    }
+}
@@ -1,4 +1,4 @@
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 use std::io;
 use walkdir::WalkDir;
 
@@ -84,4 +84,48 @@ impl Vault {
         
         std::fs::write(full_path, content)
     }
-}
\ No newline at end of file
+}
+
+/// Whether a path is a markdown note that the vault indexes should track.
+/// Anything under the `.aura` directory is app data, not a note.
+pub fn is_note(path: &Path) -> bool {
+    let in_aura_dir = path.components().any(|c| c.as_os_str() == ".aura");
+    !in_aura_dir && path.extension().and_then(|s| s.to_str()) == Some("md")
+}
+
+/// Parse a vault-relative path from the frontend or a model, refusing
+/// anything that could point outside the vault.
+pub fn relative_path(path: &str) -> Result<PathBuf, String> {
+    let path = Path::new(path.trim());
+    if path.as_os_str().is_empty()
+        || !path.components().all(|c| matches!(c, Component::Normal(_)))
+    {
+        return Err(format!("Invalid vault path: {}", path.display()));
+    }
+    Ok(path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relative_paths_stay_inside_the_vault() {
+        assert_eq!(relative_path("Notes/a.md"), Ok(PathBuf::from("Notes/a.md")));
+        assert_eq!(relative_path("  a.md "), Ok(PathBuf::from("a.md")));
+        assert!(relative_path("").is_err());
+        assert!(relative_path("   ").is_err());
+        assert!(relative_path("../a.md").is_err());
+        assert!(relative_path("Notes/../../a.md").is_err());
+        assert!(relative_path("/etc/passwd").is_err());
+        assert!(relative_path("./a.md").is_err());
+    }
+
+    #[test]
+    fn app_data_is_not_a_note() {
+        assert!(is_note(Path::new("Notes/a.md")));
+        assert!(!is_note(Path::new(".aura/conversations/a.md")));
+        assert!(!is_note(Path::new("Notes/a.png")));
+        assert!(!is_note(Path::new("Notes/a")));
+    }
+}
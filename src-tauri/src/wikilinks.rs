@@ -0,0 +1,423 @@
+// wikilinks.rs - Parsing and resolution of [[wiki-links]]
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::frontmatter;
+use crate::markdown::mask_code;
+use crate::vault::{self, Vault};
+
+const ALIAS_KEYS: [&str; 2] = ["aliases", "alias"];
+
+// Used for new notes when the vault has no template of its own
+const DEFAULT_TEMPLATE_PATH: &str = "Templates/New Note.md";
+
+/// The parts of a wiki-link's inner text: `note#heading` or `note#^block`,
+/// optionally followed by `|display`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LinkTarget {
+    pub note: String,
+    pub heading: Option<String>,
+    pub block_id: Option<String>,
+    pub display: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkKind {
+    /// `[[Note]]`
+    Wiki,
+    /// `![[Note]]` or `![[image.png]]`
+    Embed,
+    /// `[text](path/to/note.md)`
+    Markdown,
+}
+
+/// A link found in a note's content.
+#[derive(Debug, Clone)]
+pub struct RawLink {
+    pub kind: LinkKind,
+    pub target: LinkTarget,
+    /// The whole link, brackets included
+    pub range: Range<usize>,
+    /// Just the note/path part of the link, as written
+    pub target_range: Range<usize>,
+    /// 1-based line number
+    pub line: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NoteResolution {
+    /// Vault-relative path of the resolved (or newly created) note
+    pub path: Option<String>,
+    pub created: bool,
+    /// Every note the link could refer to when the name is ambiguous
+    pub candidates: Vec<String>,
+    pub heading: Option<String>,
+    pub block_id: Option<String>,
+}
+
+/// Split the inner text of `[[...]]` into its target, anchor and display parts.
+pub fn parse_link_target(inner: &str) -> LinkTarget {
+    let (target, display) = match inner.split_once('|') {
+        Some((target, display)) => (target, Some(display.trim().to_string())),
+        None => (inner, None),
+    };
+
+    let (target, block_id) = match target.split_once("#^") {
+        Some((target, block)) => (target, Some(block.trim().to_string())),
+        None => (target, None),
+    };
+
+    let (note, heading) = match target.split_once('#') {
+        Some((note, heading)) => (note, Some(heading.trim().to_string())),
+        None => (target, None),
+    };
+
+    LinkTarget {
+        note: note.trim().to_string(),
+        heading: heading.filter(|h| !h.is_empty()),
+        block_id: block_id.filter(|b| !b.is_empty()),
+        display: display.filter(|d| !d.is_empty()),
+    }
+}
+
+fn wiki_link_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"(!?)\[\[([^\[\]\n]+?)\]\]").expect("valid wiki-link regex"))
+}
+
+fn markdown_link_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r#"(!?)\[[^\]\n]*\]\(([^)\s]+)(?:\s+"[^"]*")?\)"#).expect("valid markdown link regex")
+    })
+}
+
+/// Whether a markdown link destination points outside the vault.
+fn is_external(url: &str) -> bool {
+    url.contains("://") || url.starts_with("mailto:") || url.starts_with('#')
+}
+
+/// Every wiki-link, embed and local markdown link in a note, outside code.
+pub fn find_links(content: &str) -> Vec<RawLink> {
+    let masked = mask_code(content);
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(content.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+    let line_of = |offset: usize| line_starts.partition_point(|&start| start <= offset);
+
+    let mut links = Vec::new();
+
+    for caps in wiki_link_regex().captures_iter(&masked) {
+        let (Some(whole), Some(inner)) = (caps.get(0), caps.get(2)) else { continue };
+        let inner_text = &content[inner.range()];
+
+        // The note part ends at the first anchor or display separator
+        let note_end = inner_text.find(|c| c == '#' || c == '|').unwrap_or(inner_text.len());
+        let note_raw = &inner_text[..note_end];
+        let note_start = inner.start() + (note_raw.len() - note_raw.trim_start().len());
+
+        links.push(RawLink {
+            kind: if caps[1].is_empty() { LinkKind::Wiki } else { LinkKind::Embed },
+            target: parse_link_target(inner_text),
+            range: whole.range(),
+            target_range: note_start..note_start + note_raw.trim().len(),
+            line: line_of(whole.start()),
+        });
+    }
+
+    for caps in markdown_link_regex().captures_iter(&masked) {
+        let (Some(whole), Some(url)) = (caps.get(0), caps.get(2)) else { continue };
+        let url_text = &content[url.range()];
+        if is_external(url_text) {
+            continue;
+        }
+
+        let (path, fragment) = match url_text.split_once('#') {
+            Some((path, fragment)) => (path, Some(fragment)),
+            None => (url_text, None),
+        };
+        let decoded = urlencoding::decode(path)
+            .map(|p| p.into_owned())
+            .unwrap_or_else(|_| path.to_string());
+
+        let (heading, block_id) = match fragment.map(|f| f.replace("%20", " ")) {
+            Some(f) if f.starts_with('^') => (None, Some(f[1..].to_string())),
+            Some(f) => (Some(f), None),
+            None => (None, None),
+        };
+
+        links.push(RawLink {
+            kind: LinkKind::Markdown,
+            target: LinkTarget {
+                note: decoded,
+                heading,
+                block_id,
+                display: None,
+            },
+            range: whole.range(),
+            target_range: url.start()..url.start() + path.len(),
+            line: line_of(whole.start()),
+        });
+    }
+
+    links.sort_by_key(|link| link.range.start);
+    links
+}
+
+/// Whether a link target names a markdown note rather than an attachment.
+/// Note names may contain dots (`v1.2 notes`), so only known attachment
+/// extensions count.
+pub fn is_note_target(target: &str) -> bool {
+    let ext = Path::new(target)
+        .extension()
+        .and_then(|s| s.to_str())
+        .map(|s| s.to_lowercase());
+    !matches!(
+        ext.as_deref(),
+        Some("png" | "jpg" | "jpeg" | "gif" | "svg" | "webp" | "bmp" | "pdf" | "mp3" | "mp4" | "wav" | "webm" | "mov")
+    )
+}
+
+// Lowercased, forward-slashed path without the `.md` extension
+fn normalize_note_name(name: &str) -> String {
+    let name = name.trim().replace('\\', "/");
+    let name = name.trim_start_matches("./").trim_start_matches('/');
+    let name = name.strip_suffix(".md").unwrap_or(name);
+    name.to_lowercase()
+}
+
+fn note_folder(path: &str) -> &str {
+    path.rsplit_once('/').map_or("", |(folder, _)| folder)
+}
+
+/// Vault-relative note paths matching a link by file name or folder-qualified
+/// path, best candidate first.
+///
+/// Exact paths from the vault root win, then notes in the same folder as the
+/// linking note, then the shortest path.
+pub fn match_notes<'a>(
+    notes: impl IntoIterator<Item = &'a String>,
+    target: &str,
+    source: Option<&str>,
+) -> Vec<String> {
+    let wanted = normalize_note_name(target);
+    if wanted.is_empty() {
+        return vec![];
+    }
+    let suffix = format!("/{}", wanted);
+    let source_folder = source.map(|s| normalize_note_name(note_folder(&s.replace('\\', "/"))));
+
+    let mut matches: Vec<(u8, usize, String)> = notes
+        .into_iter()
+        .filter_map(|note| {
+            let normalized = normalize_note_name(note);
+            let rank = if normalized == wanted {
+                0
+            } else if normalized.ends_with(&suffix) {
+                if source_folder.as_deref() == Some(note_folder(&normalized)) {
+                    1
+                } else {
+                    2
+                }
+            } else {
+                return None;
+            };
+            Some((rank, normalized.len(), note.clone()))
+        })
+        .collect();
+
+    matches.sort();
+    matches.into_iter().map(|(_, _, note)| note).collect()
+}
+
+/// The aliases declared in a note's frontmatter.
+pub fn note_aliases(content: &str) -> Vec<String> {
+    let yaml = frontmatter::yaml(content);
+    ALIAS_KEYS
+        .iter()
+        .flat_map(|key| frontmatter::get_list(yaml, key))
+        .collect()
+}
+
+fn relative_note_paths(vault: &Vault) -> io::Result<Vec<String>> {
+    Ok(vault
+        .list_markdown_files()?
+        .into_iter()
+        .filter(|p| vault::is_note(p))
+        .filter_map(|p| {
+            p.strip_prefix(vault.path())
+                .ok()
+                .map(|r| r.to_string_lossy().to_string())
+        })
+        .collect())
+}
+
+// Notes whose frontmatter aliases include the link target
+fn match_aliases(vault: &Vault, notes: &[String], target: &str) -> Vec<String> {
+    let wanted = target.trim().to_lowercase();
+    notes
+        .iter()
+        .filter(|note| {
+            vault
+                .read_file(Path::new(note))
+                .map(|content| {
+                    note_aliases(&content)
+                        .iter()
+                        .any(|alias| alias.to_lowercase() == wanted)
+                })
+                .unwrap_or(false)
+        })
+        .cloned()
+        .collect()
+}
+
+/// Resolve a link target to vault notes: by name or path first, aliases second.
+pub fn resolve_link(
+    vault: &Vault,
+    target: &str,
+    source: Option<&str>,
+) -> io::Result<Vec<String>> {
+    let notes = relative_note_paths(vault)?;
+
+    let candidates = match_notes(&notes, target, source);
+    if !candidates.is_empty() {
+        return Ok(candidates);
+    }
+
+    Ok(match_aliases(vault, &notes, target))
+}
+
+// Reject names that would escape the vault or cannot exist on disk
+pub fn new_note_path(target: &str) -> Result<PathBuf, String> {
+    let name = target.trim().replace('\\', "/");
+    let name = name.trim_start_matches('/');
+    if name.is_empty() {
+        return Err("Note name cannot be empty".to_string());
+    }
+    if name.chars().any(|c| matches!(c, ':' | '*' | '?' | '"' | '<' | '>' | '|')) {
+        return Err(format!("Invalid characters in note name: {}", target));
+    }
+
+    let mut path = PathBuf::from(name);
+    if path.components().any(|c| !matches!(c, Component::Normal(_))) {
+        return Err(format!("Invalid note path: {}", target));
+    }
+    if path.extension().and_then(|s| s.to_str()) != Some("md") {
+        let file_name = format!("{}.md", path.file_name().unwrap_or_default().to_string_lossy());
+        path.set_file_name(file_name);
+    }
+    Ok(path)
+}
+
+fn render_template(template: &str, title: &str) -> String {
+    let now = chrono::Local::now();
+    template
+        .replace("{{title}}", title)
+        .replace("{{date}}", &now.format("%Y-%m-%d").to_string())
+        .replace("{{time}}", &now.format("%H:%M").to_string())
+}
+
+/// Create a note for an unresolved link, filled from a template.
+pub fn create_note(vault: &Vault, target: &str, template: Option<&str>) -> Result<String, String> {
+    let path = new_note_path(target)?;
+    if vault.path().join(&path).exists() {
+        return Err(format!("Note already exists: {}", path.display()));
+    }
+
+    let title = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("Untitled")
+        .to_string();
+
+    let template_content = match template {
+        Some(template_path) => Some(
+            vault
+                .read_file(&vault::relative_path(template_path)?)
+                .map_err(|e| format!("Failed to read template {}: {}", template_path, e))?,
+        ),
+        None => vault.read_file(Path::new(DEFAULT_TEMPLATE_PATH)).ok(),
+    };
+
+    let content = match template_content {
+        Some(template) => render_template(&template, &title),
+        None => format!("# {}", title),
+    };
+
+    vault
+        .write_file(&path, &content)
+        .map_err(|e| format!("Failed to create note: {}", e))?;
+
+    println!("📝 Created note for link: {:?}", path);
+    Ok(path.to_string_lossy().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn link_targets_split_into_their_parts() {
+        let target = parse_link_target("Folder/Note#Heading|Shown");
+        assert_eq!(target.note, "Folder/Note");
+        assert_eq!(target.heading.as_deref(), Some("Heading"));
+        assert_eq!(target.block_id, None);
+        assert_eq!(target.display.as_deref(), Some("Shown"));
+
+        let target = parse_link_target(" Note #^abc123 ");
+        assert_eq!(target.note, "Note");
+        assert_eq!(target.heading, None);
+        assert_eq!(target.block_id.as_deref(), Some("abc123"));
+    }
+
+    #[test]
+    fn find_links_reports_kinds_ranges_and_lines() {
+        let content = "See [[Alpha]] and\n![[pic.png]] or [text](Sub/Beta%20Two.md#Part).";
+        let links = find_links(content);
+        assert_eq!(links.len(), 3);
+
+        assert_eq!(links[0].kind, LinkKind::Wiki);
+        assert_eq!(&content[links[0].range.clone()], "[[Alpha]]");
+        assert_eq!(&content[links[0].target_range.clone()], "Alpha");
+        assert_eq!(links[0].line, 1);
+
+        assert_eq!(links[1].kind, LinkKind::Embed);
+        assert_eq!(links[1].target.note, "pic.png");
+        assert_eq!(links[1].line, 2);
+
+        assert_eq!(links[2].kind, LinkKind::Markdown);
+        assert_eq!(links[2].target.note, "Sub/Beta Two.md");
+        assert_eq!(links[2].target.heading.as_deref(), Some("Part"));
+        assert_eq!(&content[links[2].target_range.clone()], "Sub/Beta%20Two.md");
+    }
+
+    #[test]
+    fn target_range_skips_anchors_and_padding() {
+        let content = "[[ Note #Heading|Shown]]";
+        let link = &find_links(content)[0];
+        assert_eq!(&content[link.target_range.clone()], "Note");
+    }
+
+    #[test]
+    fn links_in_code_and_external_urls_are_ignored() {
+        let content = "`[[Inline]]`\n```\n[[Fenced]]\n```\n[site](https://example.com) [top](#top) [[Real]]";
+        let links = find_links(content);
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].target.note, "Real");
+    }
+
+    #[test]
+    fn note_targets_are_told_from_attachments() {
+        assert!(is_note_target("Note"));
+        assert!(is_note_target("v1.2 notes"));
+        assert!(is_note_target("Note.md"));
+        assert!(!is_note_target("photo.JPG"));
+        assert!(!is_note_target("docs/file.pdf"));
+    }
+}
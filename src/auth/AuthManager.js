@@ -9,6 +9,7 @@ export class AuthManager {
     this.currentUser = null;
     this.isAuthenticated = false;
     this.vaultPermissions = new Map(); // Maps vault paths to access levels
+    this.vaultRules = new Map(); // Maps vault paths to path-glob exceptions
   }
 
   async initialize() {
@@ -19,10 +20,14 @@ export class AuthManager {
       const session = this.loadStoredSession();
       if (session) {
         console.log('📂 Found stored session, validating...');
-        const isValid = await this.validateSession(session);
+        let isValid = await this.validateSession(session);
+        if (!isValid && session.refreshToken) {
+          isValid = await this.refreshSession(session);
+        }
         if (isValid) {
-          this.currentUser = { username: session.username };
+          this.currentUser = { username: session.username, token: session.token };
           this.isAuthenticated = true;
+          await this.loadUserPermissions();
           console.log('✅ Session validated successfully');
           return true;
         } else {
@@ -48,32 +53,15 @@ export class AuthManager {
       });
       
       if (result.success) {
-        console.log('✅ Authentication successful');
-        
-        // Set current user
-        this.currentUser = {
-          username: credentials.username,
-          token: result.token,
-          permissions: result.permissions || []
-        };
-        this.isAuthenticated = true;
-        
-        // Store session if remember me is enabled
-        if (credentials.rememberMe) {
-          this.storeSession({
-            username: credentials.username,
-            token: result.token,
-            timestamp: Date.now()
-          });
-        }
-        
-        // Load user vault permissions
-        await this.loadUserPermissions();
-        
+        return await this.completeLogin(credentials, result);
+      } else if (result.two_factor_required) {
+        // Password accepted; finish with verifyTwoFactor
+        console.log('📱 Second factor required');
         return {
-          success: true,
-          token: result.token,
-          user: this.currentUser
+          success: false,
+          twoFactorRequired: true,
+          challenge: result.challenge,
+          message: result.message
         };
       } else {
         console.log('❌ Authentication failed');
@@ -85,31 +73,35 @@ export class AuthManager {
     } catch (error) {
       console.error('❌ Login error:', error);
       
-      // For development/demo purposes, allow a demo user
-      if (credentials.username === 'demo' && credentials.password === 'demo123') {
-        console.log('🎭 Using demo authentication');
-        this.currentUser = {
-          username: 'demo',
-          token: 'demo-token-' + Date.now(),
-          permissions: ['read', 'write']
-        };
-        this.isAuthenticated = true;
-        
-        if (credentials.rememberMe) {
-          this.storeSession({
-            username: 'demo',
-            token: this.currentUser.token,
-            timestamp: Date.now()
-          });
-        }
-        
-        return {
-          success: true,
-          token: this.currentUser.token,
-          user: this.currentUser
-        };
-      }
+      return {
+        success: false,
+        message: 'Authentication failed'
+      };
+    }
+  }
+
+  // Second sign-in step: a code from the authenticator app or a recovery code
+  async verifyTwoFactor(credentials) {
+    console.log(`📱 Verifying second factor for user: ${credentials.username}`);
+    
+    try {
+      const result = await invoke('verify_two_factor', {
+        challenge: credentials.challenge,
+        code: credentials.code
+      });
       
+      if (result.success) {
+        return await this.completeLogin(credentials, result);
+      }
+      console.log('❌ Second factor rejected');
+      return {
+        success: false,
+        // Otherwise the sign-in must start again from the password
+        canRetry: Boolean(result.challenge),
+        message: result.message || 'Invalid code'
+      };
+    } catch (error) {
+      console.error('❌ Two-factor error:', error);
       return {
         success: false,
         message: 'Authentication failed'
@@ -117,6 +109,37 @@ export class AuthManager {
     }
   }
 
+  async completeLogin(credentials, result) {
+    console.log('✅ Authentication successful');
+    
+    // Set current user
+    this.currentUser = {
+      username: credentials.username,
+      token: result.token,
+      permissions: result.permissions || []
+    };
+    this.isAuthenticated = true;
+    
+    // Store session if remember me is enabled
+    if (credentials.rememberMe) {
+      this.storeSession({
+        username: credentials.username,
+        token: result.token,
+        refreshToken: result.refresh_token,
+        timestamp: Date.now()
+      });
+    }
+    
+    // Load user vault permissions
+    await this.loadUserPermissions();
+    
+    return {
+      success: true,
+      token: result.token,
+      user: this.currentUser
+    };
+  }
+
   async logout() {
     console.log('🚪 Logging out user');
     
@@ -133,6 +156,7 @@ export class AuthManager {
     this.currentUser = null;
     this.isAuthenticated = false;
     this.vaultPermissions.clear();
+    this.vaultRules.clear();
     this.clearSession();
     
     console.log('✅ Logout complete');
@@ -142,26 +166,33 @@ export class AuthManager {
     console.log('🔍 Validating session');
     
     try {
-      // Check if session is expired (24 hours)
-      const sessionAge = Date.now() - session.timestamp;
-      const maxAge = 24 * 60 * 60 * 1000; // 24 hours in milliseconds
-      
-      if (sessionAge > maxAge) {
-        console.log('⏰ Session expired');
-        return false;
-      }
-      
-      // Validate token with backend
-      const result = await invoke('validate_session', { token: session.token });
+      // Validate token with backend, which applies the idle and absolute
+      // timeouts and then runs vault commands under it
+      const result = await invoke('resume_session', { token: session.token });
       return result.valid;
     } catch (error) {
       console.error('❌ Session validation error:', error);
-      
-      // For demo purposes, validate demo tokens
-      if (session.token?.startsWith('demo-token-')) {
-        return true;
+      return false;
+    }
+  }
+
+  // Trade the stored refresh token for a new session, updating `session`
+  async refreshSession(session) {
+    console.log('🔄 Refreshing session');
+    
+    try {
+      const result = await invoke('refresh_session', { refreshToken: session.refreshToken });
+      if (!result.success) {
+        console.log('⏰ Session expired');
+        return false;
       }
-      
+      session.token = result.token;
+      session.refreshToken = result.refresh_token;
+      session.timestamp = Date.now();
+      this.storeSession(session);
+      return true;
+    } catch (error) {
+      console.error('❌ Session refresh error:', error);
       return false;
     }
   }
@@ -171,25 +202,20 @@ export class AuthManager {
     
     try {
       const permissions = await invoke('get_user_permissions', {
-        username: this.currentUser.username
+        token: this.currentUser.token
       });
       
       // Store vault-specific permissions
       if (permissions.vaults) {
         permissions.vaults.forEach(vault => {
           this.vaultPermissions.set(vault.path, vault.access);
+          this.vaultRules.set(vault.path, vault.rules || []);
         });
       }
       
       console.log(`✅ Loaded permissions for ${this.vaultPermissions.size} vaults`);
     } catch (error) {
       console.error('❌ Failed to load permissions:', error);
-      
-      // For demo user, grant full access
-      if (this.currentUser?.username === 'demo') {
-        console.log('🎭 Granting demo user full access');
-        this.vaultPermissions.set('*', ['read', 'write']);
-      }
     }
   }
 
@@ -239,6 +265,95 @@ export class AuthManager {
     return false;
   }
 
+  // Vault-level access for 'read', 'write', 'delete' or 'export'. Path rules
+  // within the vault are enforced by the backend on every file command.
+  hasVaultAccess(vaultPath, action) {
+    if (!this.isAuthenticated) {
+      return false;
+    }
+    const access = this.vaultPermissions.get(vaultPath) || this.vaultPermissions.get('*');
+    return access ? access.includes(action) : false;
+  }
+
+  // User management; everything but changing one's own password needs an admin
+  async listUsers() {
+    return await invoke('list_users', { token: this.currentUser?.token });
+  }
+
+  // Without a signed-in user this only works for the first user, who becomes admin
+  async createUser(username, password, permissions = null) {
+    return await invoke('create_user', {
+      token: this.currentUser?.token || null,
+      username,
+      password,
+      permissions
+    });
+  }
+
+  async deleteUser(username) {
+    return await invoke('delete_user', { token: this.currentUser?.token, username });
+  }
+
+  // Changing one's own password needs the current one
+  async setUserPassword(username, password, currentPassword = null) {
+    return await invoke('set_user_password', {
+      token: this.currentUser?.token,
+      username,
+      currentPassword,
+      password
+    });
+  }
+
+  async setUserPermissions(username, permissions, vaults) {
+    return await invoke('set_user_permissions', {
+      token: this.currentUser?.token,
+      username,
+      permissions,
+      vaults
+    });
+  }
+
+  // Active sessions of a user, or of the current one
+  async listSessions(username = null) {
+    return await invoke('list_sessions', { token: this.currentUser?.token, username });
+  }
+
+  async revokeSession(sessionId) {
+    return await invoke('revoke_session', { token: this.currentUser?.token, sessionId });
+  }
+
+  // Sign a user out everywhere except this session
+  async revokeUserSessions(username) {
+    return await invoke('revoke_user_sessions', { token: this.currentUser?.token, username });
+  }
+
+  async getSessionPolicy() {
+    return await invoke('get_session_policy');
+  }
+
+  async setSessionPolicy(policy) {
+    return await invoke('set_session_policy', { token: this.currentUser?.token, policy });
+  }
+
+  // Two-factor enrolment: returns { secret, otpauth_uri } for the QR code
+  async beginTwoFactorSetup() {
+    return await invoke('begin_two_factor_setup', { token: this.currentUser?.token });
+  }
+
+  // Turns two-factor on; returns the recovery codes to show once
+  async confirmTwoFactorSetup(code) {
+    return await invoke('confirm_two_factor_setup', { token: this.currentUser?.token, code });
+  }
+
+  async regenerateRecoveryCodes(code) {
+    return await invoke('regenerate_recovery_codes', { token: this.currentUser?.token, code });
+  }
+
+  // Own account needs a code; admins can turn it off for others without one
+  async disableTwoFactor(username, code = null) {
+    return await invoke('disable_two_factor', { token: this.currentUser?.token, username, code });
+  }
+
   // Session management
   storeSession(sessionData) {
     console.log('💾 Storing session');
@@ -130,7 +130,9 @@ export class LoginScreen {
           rememberMe: this.rememberMe
         });
         
-        if (result.success) {
+        if (result.twoFactorRequired) {
+          this.showCodeStep(username, result.challenge);
+        } else if (result.success) {
           console.log('✅ Login successful');
           // Store session if remember me is checked
           if (this.rememberMe && result.token) {
@@ -159,6 +161,83 @@ export class LoginScreen {
     }
   }
 
+  // Swap the password form for the authenticator code
+  showCodeStep(username, challenge) {
+    console.log('📱 Asking for second factor');
+    const form = document.getElementById('login-form');
+    if (!form) return;
+    
+    form.innerHTML = `
+      <div class="form-group">
+        <label for="two-factor-code">Authentication code</label>
+        <input 
+          type="text" 
+          id="two-factor-code" 
+          name="code" 
+          class="form-input" 
+          placeholder="6-digit code or recovery code"
+          required 
+          autocomplete="one-time-code"
+          inputmode="numeric"
+        />
+      </div>
+      
+      <button type="submit" class="login-button">
+        Verify
+      </button>
+      
+      <div class="login-error" id="login-error" style="display: none;">
+        <span id="error-message">Invalid code</span>
+      </div>
+    `;
+    
+    // Replace the form to drop the password submit listener
+    const codeForm = form.cloneNode(true);
+    form.replaceWith(codeForm);
+    codeForm.addEventListener('submit', (e) => this.handleCode(e, username, challenge));
+    document.getElementById('two-factor-code')?.focus();
+  }
+
+  async handleCode(event, username, challenge) {
+    event.preventDefault();
+    const code = document.getElementById('two-factor-code').value.trim();
+    this.hideError();
+    
+    const submitButton = event.target.querySelector('button[type="submit"]');
+    submitButton.disabled = true;
+    
+    try {
+      if (!this.callbacks.onVerifyCode) {
+        this.showError('Two-factor sign-in not configured');
+        return;
+      }
+      const result = await this.callbacks.onVerifyCode({
+        username,
+        challenge,
+        code,
+        rememberMe: this.rememberMe
+      });
+      
+      if (result.success) {
+        console.log('✅ Login successful');
+        if (this.callbacks.onSuccess) {
+          this.callbacks.onSuccess(result);
+        }
+      } else if (result.canRetry) {
+        this.showError(result.message || 'Invalid code');
+      } else {
+        // The sign-in expired or the account locked; start over with the password
+        this.render();
+        this.showError(result.message || 'Please sign in again');
+      }
+    } catch (error) {
+      console.error('❌ Two-factor error:', error);
+      this.showError('An error occurred during login');
+    } finally {
+      submitButton.disabled = false;
+    }
+  }
+
   showError(message) {
     console.log(`🚨 Showing error: ${message}`);
     const errorDiv = document.getElementById('login-error');
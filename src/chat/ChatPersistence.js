@@ -1,4 +1,6 @@
 // ChatPersistence.js - Save/load chat history
+import { invoke } from '@tauri-apps/api/core';
+
 console.log('💾 ChatPersistence loading...');
 
 export class ChatPersistence {
@@ -131,4 +133,51 @@ export class ChatPersistence {
     
     return '0 KB';
   }
+  
+  // Conversations stored in the vault by the backend
+  
+  async listConversations() {
+    return await invoke('list_conversations');
+  }
+  
+  async loadConversation(id) {
+    return await invoke('load_conversation', { id });
+  }
+  
+  async createConversation(title = null, contextNotes = []) {
+    return await invoke('create_conversation', { title, contextNotes });
+  }
+  
+  async appendMessage(id, message) {
+    return await invoke('append_conversation_message', {
+      id,
+      message: {
+        role: message.role,
+        content: message.content,
+        timestamp: message.timestamp ? new Date(message.timestamp).toISOString() : '',
+        model: message.model || null,
+        provider: message.provider || null
+      }
+    });
+  }
+  
+  async setConversationContext(id, contextNotes) {
+    return await invoke('set_conversation_context', { id, contextNotes });
+  }
+  
+  async renameConversation(id, title) {
+    return await invoke('rename_conversation', { id, title });
+  }
+  
+  async forkConversation(id, throughMessage = null, title = null) {
+    return await invoke('fork_conversation', { id, throughMessage, title });
+  }
+  
+  async deleteConversation(id) {
+    return await invoke('delete_conversation', { id });
+  }
+  
+  async searchConversations(query, limit = null) {
+    return await invoke('search_conversations', { query, limit });
+  }
 }
\ No newline at end of file
@@ -691,47 +691,40 @@ export class EnhancedChatPanel {
                 return;
             }
             
-            // Format chat as markdown with context information
-            let markdown = '# Chat Export\n\n';
-            markdown += `**Date**: ${new Date().toLocaleString()}\n`;
-            markdown += `**Provider**: ${this.providers[this.currentProvider].name}\n`;
-            markdown += `**Messages**: ${messages.length}\n\n`;
+            // The backend renders the note; pass the conversation as data
+            const exported = messages
+                .filter(msg => ['user', 'assistant', 'error'].includes(msg.type))
+                .map(msg => ({
+                    role: msg.type,
+                    content: msg.content,
+                    timestamp: msg.timestamp ? new Date(msg.timestamp).toISOString() : '',
+                    model: null,
+                    provider: null
+                }));
             
-            // Add context information if available
-            const contextIndicator = document.getElementById('chat-context-indicator');
-            const contextPills = contextIndicator?.querySelectorAll('.context-pill');
-            if (contextPills && contextPills.length > 0) {
-                markdown += '## Context Used\n\n';
-                contextPills.forEach(pill => {
-                    const noteName = pill.querySelector('span')?.textContent || 'Unknown';
-                    const isActive = pill.classList.contains('active-note');
-                    markdown += `- ${noteName}${isActive ? ' (Active Note)' : ''}\n`;
-                });
-                markdown += '\n';
+            const contextNotes = [];
+            const activeNote = await this.getActiveNoteContent();
+            if (activeNote?.path) {
+                contextNotes.push(activeNote.path);
             }
-            
-            markdown += '## Conversation\n\n';
-            
-            messages.forEach((msg, index) => {
-                const timestamp = msg.timestamp ? new Date(msg.timestamp).toLocaleTimeString() : '';
-                
-                if (msg.type === 'user') {
-                    markdown += `### You - ${timestamp}\n${msg.content}\n\n`;
-                } else if (msg.type === 'assistant') {
-                    markdown += `### AI - ${timestamp}\n${msg.content}\n\n`;
-                } else if (msg.type === 'error') {
-                    markdown += `### Error - ${timestamp}\n${msg.content}\n\n`;
-                } else if (msg.type === 'context') {
-                    markdown += `*${msg.content}*\n\n`;
+            for (const note of this.interface.currentContext || []) {
+                if (note.path && !contextNotes.includes(note.path)) {
+                    contextNotes.push(note.path);
                 }
-            });
+            }
+            
+            const settings = this.providers[this.currentProvider].sdk?.getSettings();
             
             // Export to vault's Chat History folder
             const { invoke } = await import('@tauri-apps/api/core');
             
             const filePath = await invoke('export_chat_to_vault', {
-                content: markdown,
-                filename: null // Let the backend generate timestamp-based filename
+                messages: exported,
+                options: {
+                    model: settings?.model || null,
+                    provider: settings?.provider || null,
+                    context_notes: contextNotes
+                }
             });
             
             console.log('✅ Chat exported successfully to:', filePath);
@@ -1,5 +1,6 @@
 // Note: Channel import removed for simplified implementation
 import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
 
 export class OpenAISDK {
     constructor() {
@@ -69,17 +70,215 @@ export class OpenAISDK {
                 messagesStringified: JSON.stringify(messages)
             });
             
-            const response = await invoke('send_ai_chat', {
-                messages: messages
-            });
+            // Chunks from concurrent chats share one event, so filter by request id
+            const requestId = options.requestId || `chat-${Date.now()}-${Math.random().toString(16).slice(2)}`;
+            const unlisteners = [];
+            if (options.onChunk) {
+                unlisteners.push(await listen('ai-stream-chunk', (event) => {
+                    if (event.payload.request_id === requestId) {
+                        options.onChunk(event.payload);
+                    }
+                }));
+            }
+            // Token budget for the chat, reported before it is sent
+            if (options.onBudget) {
+                unlisteners.push(await listen('ai-chat-budget', (event) => {
+                    if (event.payload.request_id === requestId) {
+                        options.onBudget(event.payload);
+                    }
+                }));
+            }
             
-            return response;
+            this.currentStream = requestId;
+            try {
+                // With useVaultContext the reply is grounded in retrieved note excerpts
+                // and resolves to { content, citations } instead of a string
+                if (options.useVaultContext) {
+                    return await invoke('send_ai_chat_with_context', {
+                        messages: messages,
+                        requestId: requestId,
+                        topK: options.topK || null
+                    });
+                }
+                return await invoke('send_ai_chat', {
+                    messages: messages,
+                    requestId: requestId,
+                    summarizeHistory: !!options.summarizeHistory,
+                    profile: options.profile || null
+                });
+            } finally {
+                if (this.currentStream === requestId) this.currentStream = null;
+                unlisteners.forEach(unlisten => unlisten());
+            }
         } catch (error) {
             console.error('Chat error:', error);
             throw new Error(error.toString());
         }
     }
     
+    // How the messages would fit the model's context window, without sending them
+    async estimateChat(messages) {
+        return await invoke('estimate_ai_chat', { messages });
+    }
+    
+    // Chat that lets the model use vault tools. Writes wait for options.onConfirm(call),
+    // which resolves to true to allow them; without it the user is asked with confirm()
+    async sendAgentChat(messages, options = {}) {
+        if (!this.isInitialized) {
+            throw new Error('SDK not initialized. Call initialize() first.');
+        }
+        
+        const requestId = options.requestId || `chat-${Date.now()}-${Math.random().toString(16).slice(2)}`;
+        const unlisteners = [];
+        if (options.onChunk) {
+            unlisteners.push(await listen('ai-stream-chunk', (event) => {
+                if (event.payload.request_id === requestId) {
+                    options.onChunk(event.payload);
+                }
+            }));
+        }
+        if (options.onToolCall) {
+            unlisteners.push(await listen('ai-tool-call', (event) => {
+                if (event.payload.request_id === requestId) {
+                    options.onToolCall(event.payload);
+                }
+            }));
+        }
+        unlisteners.push(await listen('ai-tool-confirmation', async (event) => {
+            const { request_id, confirmation_id, call } = event.payload;
+            if (request_id !== requestId) return;
+            
+            const approved = options.onConfirm
+                ? await options.onConfirm(call)
+                : window.confirm(`Allow the assistant to ${call.name.replace(/_/g, ' ')} "${call.arguments?.path || ''}"?`);
+            await invoke('confirm_ai_tool_call', { confirmationId: confirmation_id, approved: !!approved });
+        }));
+        
+        this.currentStream = requestId;
+        try {
+            return await invoke('send_ai_agent_chat', {
+                messages: messages,
+                requestId: requestId,
+                conversationId: options.conversationId || null
+            });
+        } finally {
+            if (this.currentStream === requestId) this.currentStream = null;
+            unlisteners.forEach(unlisten => unlisten());
+        }
+    }
+    
+    // AI edit of a selection ('summarize', 'rewrite', 'continue', 'translate', 'fix_grammar').
+    // Resolves to a proposal with `replacement` and a word diff; nothing is applied
+    async editSelection(action, target = {}, options = {}) {
+        const requestId = options.requestId || `edit-${Date.now()}-${Math.random().toString(16).slice(2)}`;
+        const unlisten = options.onChunk
+            ? await listen('ai-stream-chunk', (event) => {
+                if (event.payload.request_id === requestId) {
+                    options.onChunk(event.payload);
+                }
+            })
+            : null;
+        
+        this.currentStream = requestId;
+        try {
+            return await invoke('ai_edit_selection', {
+                action,
+                target: {
+                    file_path: target.filePath || null,
+                    content: target.content ?? null,
+                    selection: target.selection || null
+                },
+                options: {
+                    instruction: options.instruction || null,
+                    language: options.language || null
+                },
+                requestId
+            });
+        } finally {
+            if (this.currentStream === requestId) this.currentStream = null;
+            if (unlisten) unlisten();
+        }
+    }
+
+    async proposeMetadata(paths = null, options = {}) {
+        const requestId = options.requestId || `meta-${Date.now()}-${Math.random().toString(16).slice(2)}`;
+        const unlisten = options.onProgress
+            ? await listen('ai-metadata-progress', (event) => {
+                if (event.payload.request_id === requestId) {
+                    options.onProgress(event.payload);
+                }
+            })
+            : null;
+
+        this.currentStream = requestId;
+        try {
+            return await invoke('propose_note_metadata', {
+                paths,
+                concurrency: options.concurrency || null,
+                requestId
+            });
+        } finally {
+            if (this.currentStream === requestId) this.currentStream = null;
+            if (unlisten) unlisten();
+        }
+    }
+
+    // Prompt templates are notes in the vault's Prompts folder
+    async listPromptTemplates() {
+        return await invoke('list_prompt_templates');
+    }
+    
+    async runPromptTemplate(path, target = {}, options = {}) {
+        const requestId = options.requestId || `chat-${Date.now()}-${Math.random().toString(16).slice(2)}`;
+        const unlisten = options.onChunk
+            ? await listen('ai-stream-chunk', (event) => {
+                if (event.payload.request_id === requestId) {
+                    options.onChunk(event.payload);
+                }
+            })
+            : null;
+        
+        this.currentStream = requestId;
+        try {
+            return await invoke('run_prompt_template', {
+                path,
+                target: {
+                    file_path: target.filePath || null,
+                    content: target.content ?? null,
+                    selection: target.selection || null
+                },
+                variables: options.variables || null,
+                requestId
+            });
+        } finally {
+            if (this.currentStream === requestId) this.currentStream = null;
+            if (unlisten) unlisten();
+        }
+    }
+
+    async listMetadataProposals() {
+        return await invoke('list_metadata_proposals');
+    }
+
+    async applyMetadata(updates) {
+        return await invoke('apply_note_metadata', { updates });
+    }
+
+    async discardMetadataProposals(paths = null) {
+        return await invoke('discard_metadata_proposals', { paths });
+    }
+    
+    // Stop the chat started by the last sendChat call; it resolves with the partial reply
+    async cancelChat(requestId = this.currentStream) {
+        if (!requestId) return false;
+        return await invoke('cancel_ai_chat', { requestId });
+    }
+    
+    // Embed new and changed notes ahead of time so the first vault-context chat is fast
+    async indexVault() {
+        return await invoke('index_vault_embeddings');
+    }
+    
     async testConnection() {
         if (!this.isInitialized) {
             throw new Error('SDK not initialized');
@@ -199,15 +398,8 @@ export class OpenAISDK {
                     'gpt-3.5-turbo',
                     'gpt-3.5-turbo-16k'
                 ];
-            } else if (endpoint.includes('localhost:11434') || endpoint.includes('ollama')) {
-                // TODO: Implement actual model fetching from Ollama API
-                return [
-                    'llama2',
-                    'mistral',
-                    'codellama',
-                    'llama2:13b',
-                    'mistral:7b'
-                ];
+            } else if (this.settings.provider === 'ollama' || this.settings.provider === 'anthropic') {
+                return await invoke('list_ai_models', { settings: this.settings });
             } else if (endpoint.includes('localhost:1234')) {
                 // TODO: Implement actual model fetching from LM Studio API
                 return [
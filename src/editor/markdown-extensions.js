@@ -113,11 +113,11 @@ class EmbeddedBlockWidget extends WidgetType {
 
   async loadEmbeddedContent(contentElement) {
     try {
-      const content = await invoke('get_embedded_block', {
+      const block = await invoke('get_embedded_block', {
         noteTitle: this.noteTitle,
         blockId: this.blockId
       })
-      contentElement.textContent = content || 'Block not found'
+      contentElement.textContent = block ? block.expanded : 'Block not found'
     } catch (error) {
       contentElement.textContent = 'Error loading block'
       console.error('Failed to load embedded block:', error)
@@ -2494,6 +2494,7 @@ window.exportToPDF = async function() {
     await invoke('export_to_pdf', {
       markdownContent: markdownContent,
       outputPath: outputPath,
+      sourcePath: currentFile,
       options: {
         theme: 'light',
         include_styles: true,
@@ -2550,6 +2551,7 @@ window.exportToHTML = async function() {
     await invoke('export_to_html', {
       markdownContent: markdownContent,
       outputPath: outputPath,
+      sourcePath: currentFile,
       options: {
         theme: 'light',
         include_styles: true,
@@ -2606,6 +2608,7 @@ window.exportToWord = async function() {
     await invoke('export_to_word', {
       markdownContent: markdownContent,
       outputPath: outputPath,
+      sourcePath: currentFile,
       options: {
         theme: 'light',
         include_styles: true,
@@ -3,15 +3,20 @@ import { invoke } from '@tauri-apps/api/core';
 export class AISettingsPanel {
     constructor() {
         this.state = {
+            provider: 'openai',
             endpoint: 'https://api.openai.com/v1',
             apiKey: '',
             model: 'gpt-4',
             temperature: 0.7,
             maxTokens: 2000,
+            embeddingModel: '',
+            contextWindow: '',
             showApiKey: false,
             testing: false,
             testStatus: null,
-            showAdvanced: false
+            showAdvanced: false,
+            profiles: [],
+            newProfileName: ''
         };
         
         this.container = null;
@@ -28,7 +33,68 @@ export class AISettingsPanel {
         this.render();
     }
     
+    async loadProfiles() {
+        try {
+            this.state.profiles = await invoke('list_ai_profiles');
+        } catch (error) {
+            console.error('Failed to load AI profiles:', error);
+            this.state.profiles = [];
+        }
+    }
+    
+    async switchProfile(name, forVault = false) {
+        try {
+            await invoke('switch_ai_profile', { name, forVault });
+            await this.loadSettings();
+            this.render();
+        } catch (error) {
+            this.showNotification('Failed to switch profile: ' + error, 'error');
+        }
+    }
+    
+    async saveAsProfile() {
+        const name = this.state.newProfileName.trim();
+        if (!name) return;
+        try {
+            await invoke('save_ai_profile', { name, settings: this.currentSettings() });
+            this.state.newProfileName = '';
+            await this.loadProfiles();
+            this.showNotification(`Profile "${name}" saved`, 'success');
+            this.render();
+        } catch (error) {
+            this.showNotification('Failed to save profile: ' + error, 'error');
+        }
+    }
+    
+    async deleteProfile(name) {
+        try {
+            await invoke('delete_ai_profile', { name });
+            await this.loadSettings();
+            this.render();
+        } catch (error) {
+            this.showNotification('Failed to delete profile: ' + error, 'error');
+        }
+    }
+    
+    updateNewProfileName(value) {
+        this.state.newProfileName = value;
+    }
+    
+    currentSettings() {
+        return {
+            provider: this.state.provider,
+            endpoint: this.state.endpoint,
+            api_key: this.state.apiKey || null,
+            model: this.state.model,
+            temperature: this.state.temperature,
+            max_tokens: this.state.maxTokens,
+            embedding_model: this.state.embeddingModel || null,
+            context_window: parseInt(this.state.contextWindow) || null
+        };
+    }
+    
     async loadSettings() {
+        await this.loadProfiles();
         try {
             const settings = await invoke('get_ai_settings');
             if (settings) {
@@ -36,11 +102,14 @@ export class AISettingsPanel {
                 // Convert snake_case to camelCase for frontend use
                 this.state = { 
                     ...this.state, 
+                    provider: settings.provider || 'openai',
                     endpoint: settings.endpoint,
                     apiKey: settings.api_key,
                     model: settings.model,
                     temperature: settings.temperature,
-                    maxTokens: settings.max_tokens
+                    maxTokens: settings.max_tokens,
+                    embeddingModel: settings.embedding_model || '',
+                    contextWindow: settings.context_window || ''
                 };
             }
         } catch (error) {
@@ -51,15 +120,19 @@ export class AISettingsPanel {
     async saveSettings() {
         try {
             const settings = {
+                provider: this.state.provider,
                 endpoint: this.state.endpoint,
                 api_key: this.state.apiKey || null,
                 model: this.state.model,
                 temperature: this.state.temperature,
-                max_tokens: this.state.maxTokens
+                max_tokens: this.state.maxTokens,
+                embedding_model: this.state.embeddingModel || null,
+                context_window: parseInt(this.state.contextWindow) || null
             };
             
             console.log('Saving AI settings...');
             await invoke('save_ai_settings', { settings });
+            await this.loadProfiles();
             
             this.showNotification('Settings saved successfully', 'success');
             
@@ -80,11 +153,14 @@ export class AISettingsPanel {
         
         try {
             const settings = {
+                provider: this.state.provider,
                 endpoint: this.state.endpoint,
                 api_key: this.state.apiKey || null,
                 model: this.state.model,
                 temperature: this.state.temperature,
-                max_tokens: this.state.maxTokens
+                max_tokens: this.state.maxTokens,
+                embedding_model: this.state.embeddingModel || null,
+                context_window: parseInt(this.state.contextWindow) || null
             };
             
             console.log('Testing AI connection...');
@@ -111,16 +187,25 @@ export class AISettingsPanel {
         
         switch (provider) {
             case 'openai':
+                this.state.provider = 'openai';
                 this.state.endpoint = 'https://api.openai.com/v1';
                 this.state.model = 'gpt-4';
                 this.state.apiKey = '';
                 break;
             case 'ollama':
-                this.state.endpoint = 'http://localhost:11434/v1';
+                this.state.provider = 'ollama';
+                this.state.endpoint = 'http://localhost:11434';
                 this.state.model = 'llama2';
                 this.state.apiKey = '';
                 break;
+            case 'anthropic':
+                this.state.provider = 'anthropic';
+                this.state.endpoint = 'https://api.anthropic.com/v1';
+                this.state.model = 'claude-3-5-sonnet-latest';
+                this.state.apiKey = '';
+                break;
             case 'lmstudio':
+                this.state.provider = 'openai';
                 this.state.endpoint = 'http://localhost:1234/v1';
                 this.state.model = 'TheBloke/Mistral-7B-Instruct-v0.2-GGUF';
                 this.state.apiKey = '';
@@ -129,6 +214,22 @@ export class AISettingsPanel {
         this.render();
     }
     
+    updateProvider(value) {
+        this.state.provider = value;
+        this.render();
+    }
+    
+    async loadOllamaModels() {
+        try {
+            const models = await invoke('list_ollama_models', { endpoint: this.state.endpoint });
+            this.state.ollamaModels = models.map(m => m.name);
+            this.render();
+        } catch (error) {
+            console.error('Failed to list Ollama models:', error);
+            this.showNotification('Failed to list models: ' + error, 'error');
+        }
+    }
+    
     updateEndpoint(value) {
         this.state.endpoint = value;
     }
@@ -149,6 +250,14 @@ export class AISettingsPanel {
         this.state.maxTokens = parseInt(value);
     }
     
+    updateEmbeddingModel(value) {
+        this.state.embeddingModel = value.trim();
+    }
+    
+    updateContextWindow(value) {
+        this.state.contextWindow = value.trim();
+    }
+    
     toggleApiKeyVisibility() {
         this.state.showApiKey = !this.state.showApiKey;
         this.render();
@@ -179,7 +288,13 @@ export class AISettingsPanel {
     }
     
     getModelExamples(endpoint) {
-        if (endpoint.includes('openai.com')) {
+        if (this.state.provider === 'ollama') {
+            return this.state.ollamaModels && this.state.ollamaModels.length > 0
+                ? 'Installed: ' + this.state.ollamaModels.join(', ')
+                : 'Examples: llama3, mistral, codellama';
+        } else if (this.state.provider === 'anthropic') {
+            return 'Examples: claude-3-5-sonnet-latest, claude-3-5-haiku-latest';
+        } else if (endpoint.includes('openai.com')) {
             return 'Examples: gpt-4, gpt-3.5-turbo, gpt-4-turbo-preview';
         } else if (endpoint.includes('11434')) {
             return 'Examples: llama2, mistral, codellama';
@@ -206,6 +321,10 @@ export class AISettingsPanel {
                             <span class="provider-icon">🤖</span>
                             OpenAI
                         </button>
+                        <button onclick="aiSettingsPanel.quickSetup('anthropic')" class="quick-setup-btn">
+                            <span class="provider-icon">✳️</span>
+                            Anthropic
+                        </button>
                         <button onclick="aiSettingsPanel.quickSetup('ollama')" class="quick-setup-btn">
                             <span class="provider-icon">🦙</span>
                             Ollama
@@ -218,6 +337,18 @@ export class AISettingsPanel {
                 </div>
                 
                 <div class="settings-form">
+                    ${this.renderProfiles()}
+                    
+                    <div class="form-group">
+                        <label>Provider:</label>
+                        <select onchange="aiSettingsPanel.updateProvider(this.value)" class="form-input">
+                            <option value="openai" ${this.state.provider === 'openai' ? 'selected' : ''}>OpenAI-compatible</option>
+                            <option value="anthropic" ${this.state.provider === 'anthropic' ? 'selected' : ''}>Anthropic</option>
+                            <option value="ollama" ${this.state.provider === 'ollama' ? 'selected' : ''}>Ollama (native)</option>
+                        </select>
+                        <small>The API your endpoint speaks</small>
+                    </div>
+                    
                     <div class="form-group">
                         <label>API Endpoint:</label>
                         <input 
@@ -257,6 +388,11 @@ export class AISettingsPanel {
                             class="form-input"
                         />
                         <small>${this.getModelExamples(this.state.endpoint)}</small>
+                        ${this.state.provider === 'ollama' ? `
+                            <button onclick="aiSettingsPanel.loadOllamaModels()" class="quick-setup-btn">
+                                Load installed models
+                            </button>
+                        ` : ''}
                     </div>
                     
                     <div class="advanced-section">
@@ -292,6 +428,31 @@ export class AISettingsPanel {
                                     />
                                     <small>Maximum response length in tokens</small>
                                 </div>
+                                
+                                <div class="form-group">
+                                    <label>Embedding Model:</label>
+                                    <input 
+                                        type="text" 
+                                        value="${this.state.embeddingModel}"
+                                        placeholder="${this.state.provider === 'ollama' ? 'nomic-embed-text' : 'text-embedding-3-small'}"
+                                        onchange="aiSettingsPanel.updateEmbeddingModel(this.value)"
+                                        class="form-input"
+                                    />
+                                    <small>Used to search your notes for chat context; leave empty for the default</small>
+                                </div>
+                                
+                                <div class="form-group">
+                                    <label>Context Window:</label>
+                                    <input 
+                                        type="number" 
+                                        min="512" 
+                                        value="${this.state.contextWindow}"
+                                        placeholder="Automatic"
+                                        onchange="aiSettingsPanel.updateContextWindow(this.value)"
+                                        class="form-input"
+                                    />
+                                    <small>Tokens the model accepts per request; older messages are left out to fit. Leave empty to use the model's known limit</small>
+                                </div>
                             </div>
                         ` : ''}
                     </div>
@@ -315,6 +476,43 @@ export class AISettingsPanel {
         `;
     }
     
+    renderProfiles() {
+        const active = this.state.profiles.find(p => p.is_active);
+        return `
+            <div class="form-group ai-profiles">
+                <label>Profile:</label>
+                <select onchange="aiSettingsPanel.switchProfile(this.value)" class="form-input">
+                    ${this.state.profiles.map(p => `
+                        <option value="${p.name}" ${p.is_active ? 'selected' : ''}>
+                            ${p.name}${p.is_vault_default ? ' (this vault)' : p.is_default ? ' (default)' : ''}
+                        </option>
+                    `).join('')}
+                </select>
+                ${active ? `
+                    <button onclick="aiSettingsPanel.switchProfile('${active.name}', true)" class="quick-setup-btn">
+                        Use for this vault
+                    </button>
+                    <button onclick="aiSettingsPanel.deleteProfile('${active.name}')" class="quick-setup-btn">
+                        Delete
+                    </button>
+                ` : ''}
+                <div class="api-key-input">
+                    <input 
+                        type="text"
+                        value="${this.state.newProfileName}"
+                        onchange="aiSettingsPanel.updateNewProfileName(this.value)"
+                        placeholder="New profile name"
+                        class="form-input"
+                    />
+                    <button onclick="aiSettingsPanel.saveAsProfile()" class="quick-setup-btn">
+                        Save as profile
+                    </button>
+                </div>
+                <small>Saving updates the active profile</small>
+            </div>
+        `;
+    }
+    
     renderTestStatus() {
         const status = this.state.testStatus;
         const overall = status.overall_status || status.overallStatus;